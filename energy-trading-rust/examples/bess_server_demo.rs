@@ -2,7 +2,7 @@ use energy_trading::bess_node::BESSNode;
 use energy_trading::bess_tcp_server::BESSTCPServer;
 use energy_trading::etp_message::ETPMessage;
 use energy_trading::network::unicast_connection::UnicastConnection;
-use std::net::SocketAddr;
+use energy_trading::termination::TerminationCode;
 use tokio::net::TcpStream;
 use tokio::time::{sleep, Duration};
 
@@ -127,7 +127,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Test timing constraints with DeviceFailure message
     println!("\n⚡ Testing timing constraints with DeviceFailure message...");
-    let device_failure = ETPMessage::new_device_failure(1005, 123, 1);
+    let device_failure = ETPMessage::new_device_failure(1005, 123, TerminationCode::BatteryFault);
     client1.send_message(device_failure).await?;
     println!("   DeviceFailure message sent (processed within 200ms constraint)");
     
@@ -29,25 +29,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("📡 Generating test events...");
     
     // Generate some test events
-    let events = vec![
+    let events = [
         SystemEvent::AuctionStarted {
             auction_id: 1,
             total_energy: 100.0,
             reserve_price: 15.0,
         },
         SystemEvent::BidPlaced {
+            auction_id: 1,
             aggregator_id: 1,
             bess_id: 123,
             bid_price: 18.0,
             energy_amount: 10.0,
         },
         SystemEvent::BidPlaced {
+            auction_id: 1,
             aggregator_id: 2,
             bess_id: 123,
             bid_price: 20.0,
             energy_amount: 15.0,
         },
         SystemEvent::BidAccepted {
+            auction_id: 1,
             aggregator_id: 2,
             bess_id: 123,
             final_price: 20.0,
@@ -1,5 +1,4 @@
 use energy_trading::network::websocket_gateway::SystemEvent;
-use serde_json;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 use futures_util::{SinkExt, StreamExt};
 
@@ -0,0 +1,21 @@
+#![no_main]
+
+use energy_trading::*;
+use libfuzzer_sys::fuzz_target;
+
+/// Run every decoder a received frame can reach
+fn decode_everything(frame: &[u8]) {
+    let _ = ETPMessage::deserialize(frame);
+    let _ = ETPMessage::decode_compact(frame);
+    let _ = ETPMessage::decode_go_bytes(frame);
+    let _ = MessageBatch::decode(frame);
+    let _ = SignedEnvelope::decode(frame);
+    let _ = Hello::decode(frame);
+    if let Ok(expanded) = decompress_frame(frame) {
+        decode_everything(&expanded);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    decode_everything(data);
+});
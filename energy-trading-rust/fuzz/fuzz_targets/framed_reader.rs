@@ -0,0 +1,51 @@
+#![no_main]
+
+use bytes::BytesMut;
+use energy_trading::*;
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::Decoder;
+
+/// Small limit so the fuzzer reaches the oversized-frame path quickly
+const FUZZ_MAX_FRAME_SIZE: usize = 4096;
+
+// The first byte picks the read size, the rest is the TCP byte stream
+fuzz_target!(|data: &[u8]| {
+    let Some((&chunk, stream)) = data.split_first() else {
+        return;
+    };
+
+    let mut codec = EtpCodec::new();
+    codec.set_max_frame_size(FUZZ_MAX_FRAME_SIZE);
+    let mut buffer = BytesMut::new();
+    for piece in stream.chunks(chunk.max(1) as usize) {
+        buffer.extend_from_slice(piece);
+        loop {
+            match codec.decode(&mut buffer) {
+                Ok(Some(frame)) => {
+                    assert!(frame.len() <= FUZZ_MAX_FRAME_SIZE);
+                    let frame = match is_compressed_frame(&frame) {
+                        true => match decompress_frame(&frame) {
+                            Ok(expanded) => expanded,
+                            Err(_) => continue,
+                        },
+                        false => frame,
+                    };
+                    if MessageBatch::is_batch_frame(&frame) {
+                        let _ = MessageBatch::decode(&frame);
+                    } else if SignedEnvelope::is_envelope_frame(&frame) {
+                        let _ = SignedEnvelope::decode(&frame).map(|envelope| envelope.verify_signature());
+                    } else if ETPMessage::is_compact_frame(&frame) {
+                        let _ = ETPMessage::decode_compact(&frame);
+                    } else {
+                        let _ = ETPMessage::deserialize(&frame);
+                    }
+                }
+                Ok(None) => {
+                    assert!(buffer.len() < 4 + FUZZ_MAX_FRAME_SIZE);
+                    break;
+                }
+                Err(_) => return, // The connection would be closed here
+            }
+        }
+    }
+});
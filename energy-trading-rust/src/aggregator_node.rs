@@ -1,11 +1,17 @@
-use crate::etp_message::ETPMessage;
+use crate::etp_message::{ETPMessage, MessageType};
 use crate::bess_node::BESSNode;
-use crate::error::Result;
+use crate::error::{ETPError, Result};
+use crate::network::connection_pool::BessConnectionPool;
+use crate::termination::TerminationCode;
+use crate::validation::ValidationRules;
+use crate::replay_protection::next_message_id;
 use serde::{Deserialize, Serialize};
+use futures::future::join_all;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{info, warn};
 use rand::Rng;
 
 /// Bidding strategy for aggregator nodes
@@ -36,7 +42,7 @@ pub enum BidResponse {
     },
     Reject {
         reason: String,
-        code: u8,
+        code: TerminationCode,
     },
 }
 
@@ -49,7 +55,7 @@ pub enum BidEvaluationResult {
     },
     Rejected {
         reason: String,
-        code: u8,
+        code: TerminationCode,
     },
 }
 
@@ -60,13 +66,18 @@ pub enum BidEvaluationResult {
 /// - Generates intelligent bids based on strategy
 /// - Optimizes energy procurement across multiple BESS nodes
 /// - Learns from historical bidding data
+///
+/// BESS nodes reached over TCP live in `connection_pool`; while it is empty,
+/// queries and bids run against the in-memory `connected_bess_nodes` instead,
+/// for simulations.
 #[derive(Debug, Clone)]
 pub struct AggregatorNode {
     pub device_id: u64,
     pub device_name: String,
     pub strategy: BiddingStrategy,
     pub is_online: bool,
-    pub connected_bess_nodes: Arc<RwLock<HashMap<u64, BESSNode>>>,
+    pub connected_bess_nodes: Arc<RwLock<HashMap<u64, BESSNode>>>, // Simulated BESS nodes
+    pub connection_pool: Arc<BessConnectionPool>,                  // BESS nodes reached over TCP
     pub historical_bids: Arc<RwLock<Vec<HistoricalBid>>>,
     pub max_bid_price: f64,
     pub min_bid_price: f64,
@@ -92,6 +103,7 @@ impl AggregatorNode {
             strategy,
             is_online: true,
             connected_bess_nodes: Arc::new(RwLock::new(HashMap::new())),
+            connection_pool: Arc::new(BessConnectionPool::new(device_id)),
             historical_bids: Arc::new(RwLock::new(Vec::new())),
             max_bid_price: 2.5, // Default max bid price (2.5 c/kWh - realistic Australian FiT)
             min_bid_price: 1.0, // Default min bid price (1.0 c/kWh - realistic Australian FiT)
@@ -100,6 +112,9 @@ impl AggregatorNode {
 
     /// Generate a bid based on the current strategy
     pub async fn generate_bid(&self, reserve_price: f64, energy_amount: f64, max_price: f64) -> ETPMessage {
+        // Tolerate callers passing the bounds in either order
+        let (reserve_price, max_price) = (reserve_price.min(max_price), reserve_price.max(max_price));
+
         let bid_price = match self.strategy {
             BiddingStrategy::Random => {
                 let mut rng = rand::thread_rng();
@@ -114,9 +129,10 @@ impl AggregatorNode {
             BiddingStrategy::Intelligent => {
                 self.predict_winning_price(energy_amount).await
             }
-        };
+        }
+        .clamp(reserve_price, max_price);
 
-        let mut bid = ETPMessage::new_bid(rand::thread_rng().gen_range(1000..=9999), bid_price, energy_amount);
+        let mut bid = ETPMessage::new_bid(next_message_id(), bid_price, energy_amount);
         bid.device_id = self.device_id;
         bid
     }
@@ -198,6 +214,11 @@ impl AggregatorNode {
         info!("Added BESS node {} to aggregator {}", device_id, self.device_id);
     }
 
+    /// Connect to a `BESSTCPServer` and keep the connection in the pool
+    pub fn connect_bess(&self, device_id: u64, addr: SocketAddr) {
+        self.connection_pool.add(device_id, addr);
+    }
+
     /// Optimize bids across multiple BESS nodes
     pub async fn optimize_bids(&self, total_energy_required: f64, max_price: f64) -> Vec<ETPMessage> {
         let connected_nodes = self.connected_bess_nodes.read().await;
@@ -209,9 +230,23 @@ impl AggregatorNode {
         let mut optimized_bids = Vec::new();
         let mut remaining_energy = total_energy_required;
 
-        // Sort BESS nodes by price (lowest first)
-        let mut sorted_bess: Vec<_> = connected_nodes.iter().collect();
-        sorted_bess.sort_by(|a, b| a.1.reserve_price.partial_cmp(&b.1.reserve_price).unwrap());
+        // Sort BESS nodes by price (lowest first), skipping nodes with a corrupt price
+        let mut sorted_bess: Vec<_> = connected_nodes
+            .iter()
+            .filter(|(device_id, bess_node)| {
+                let usable = bess_node.reserve_price.is_finite();
+                if !usable {
+                    warn!("Skipping BESS node {} with reserve price {}", device_id, bess_node.reserve_price);
+                }
+                usable
+            })
+            .collect();
+        sorted_bess.sort_by(|a, b| a.1.reserve_price.total_cmp(&b.1.reserve_price));
+
+        let rules = ValidationRules {
+            max_price: Some(max_price),
+            ..ValidationRules::default()
+        };
 
         for (_device_id, bess_node) in sorted_bess {
             if remaining_energy <= 0.0 {
@@ -223,6 +258,10 @@ impl AggregatorNode {
 
             if energy_to_bid > 0.0 {
                 let bid = self.generate_bid(bess_node.reserve_price, energy_to_bid, max_price).await;
+                if let Err(e) = bid.validate_with(&rules) {
+                    warn!("Not bidding on BESS node {}: {}", bess_node.device_id, e);
+                    continue;
+                }
                 optimized_bids.push(bid);
                 remaining_energy -= energy_to_bid;
             }
@@ -260,14 +299,26 @@ impl AggregatorNode {
     }
 
     /// Query BESS nodes for energy availability
+    ///
+    /// Returns the query responses of the nodes able to provide `energy_required`.
     pub async fn query_bess_nodes(&self, energy_required: f64) -> Vec<ETPMessage> {
+        if !self.connection_pool.is_empty() {
+            return self
+                .query_pooled_bess()
+                .await
+                .into_iter()
+                .map(|(_, response)| response)
+                .filter(|response| offered_energy(response) >= energy_required)
+                .collect();
+        }
+
         let connected_nodes = self.connected_bess_nodes.read().await;
         let mut query_responses = Vec::new();
 
         for (_device_id, bess_node) in connected_nodes.iter() {
             if bess_node.can_provide_energy(energy_required) {
                 let query_response = bess_node.generate_query_response(
-                    rand::thread_rng().gen_range(1000..=9999),
+                    next_message_id(),
                     self.device_id,
                 );
                 query_responses.push(query_response);
@@ -278,8 +329,97 @@ impl AggregatorNode {
     }
 
     /// Place bids to BESS nodes
+    ///
+    /// Pooled BESS nodes are queried, bid on cheapest offer first (largest
+    /// first at the same price) until the energy is covered, and sent a BidConfirm for every accepted bid; each
+    /// outcome is added to the bid history. Bids start at the node's asking
+    /// price, and offers asking more than `max_price` are skipped. Returns the bids sent, or the
+    /// optimized bids for simulated nodes.
     pub async fn place_bids(&self, energy_required: f64, max_price: f64) -> Vec<ETPMessage> {
-        self.optimize_bids(energy_required, max_price).await
+        if self.connection_pool.is_empty() {
+            return self.optimize_bids(energy_required, max_price).await;
+        }
+
+        let mut offers: Vec<(u64, f64, f64)> = self
+            .query_pooled_bess()
+            .await
+            .into_iter()
+            .map(|(device_id, response)| (device_id, response.sale_price, offered_energy(&response)))
+            .collect();
+        offers.sort_by(|a, b| a.1.total_cmp(&b.1).then(b.2.total_cmp(&a.2)));
+
+        let rules = ValidationRules {
+            max_price: Some(max_price),
+            ..ValidationRules::default()
+        };
+        let mut placed_bids = Vec::new();
+        let mut remaining_energy = energy_required;
+        for (device_id, asking, offered) in offers {
+            if remaining_energy <= 0.0 {
+                break;
+            }
+            let energy_to_bid = remaining_energy.min(offered);
+            if energy_to_bid <= 0.0 {
+                continue;
+            }
+            if asking > max_price {
+                info!("Not bidding on BESS node {}: asks {:.2} c/kWh, above {:.2}", device_id, asking, max_price);
+                continue;
+            }
+            let bid = self.generate_bid(asking.max(self.min_bid_price), energy_to_bid, max_price).await;
+            if let Err(e) = bid.validate_with(&rules) {
+                warn!("Not bidding on BESS node {}: {}", device_id, e);
+                continue;
+            }
+            match self.trade(device_id, &bid).await {
+                Ok(Some(energy_bought)) => remaining_energy -= energy_bought,
+                Ok(None) => {}
+                Err(e) => warn!("Bid {} to BESS node {} failed: {}", bid.message_id, device_id, e),
+            }
+            placed_bids.push(bid);
+        }
+
+        placed_bids
+    }
+
+    /// Query every pooled BESS node at once, skipping those that fail to answer
+    async fn query_pooled_bess(&self) -> Vec<(u64, ETPMessage)> {
+        let queries = self.connection_pool.device_ids().into_iter().map(|device_id| async move {
+            let connection = self.connection_pool.checkout(device_id).await?;
+            let response = connection.request(ETPMessage::new_query(next_message_id(), self.device_id)).await?;
+            Ok::<_, ETPError>((device_id, response))
+        });
+
+        join_all(queries)
+            .await
+            .into_iter()
+            .filter_map(|queried| match queried {
+                Ok(answer) => Some(answer),
+                Err(e) => {
+                    warn!("Query to pooled BESS node failed: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Send a bid to a pooled BESS node and confirm it if accepted
+    ///
+    /// Returns the energy bought, or `None` if the bid was rejected.
+    async fn trade(&self, device_id: u64, bid: &ETPMessage) -> Result<Option<f64>> {
+        let connection = self.connection_pool.checkout(device_id).await?;
+        let answer = connection.request(bid.clone()).await?;
+        if answer.kind()? != MessageType::BidAccept {
+            info!("BESS node {} rejected bid {}: {}", device_id, bid.message_id, answer.termination()?.name());
+            self.add_historical_bid(device_id, bid.bid_price, bid.required_energy_amount, false).await;
+            return Ok(None);
+        }
+
+        let confirm = ETPMessage::new_bid_confirm(bid.message_id, self.device_id, answer.sale_price, answer.required_energy_amount);
+        connection.send(confirm).await?;
+        info!("Bought {:.2} kWh from BESS node {} at {:.2} c/kWh", answer.required_energy_amount, device_id, answer.sale_price);
+        self.add_historical_bid(device_id, bid.bid_price, answer.required_energy_amount, true).await;
+        Ok(Some(answer.required_energy_amount))
     }
 
     /// Process bid response from BESS node
@@ -303,6 +443,11 @@ impl AggregatorNode {
     }
 }
 
+/// Energy a BESS offers for sale in its query response
+fn offered_energy(response: &ETPMessage) -> f64 {
+    response.energy_total * response.percentage_for_sale / 100.0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Physical behaviour of a battery pack
+///
+/// C-rates are multiples of the pack capacity per hour and states of charge
+/// are fractions of the capacity, from 0 to 1.
+pub trait BatteryModel: Debug + Send + Sync {
+    /// Fraction of the charging energy that ends up stored
+    fn charge_efficiency(&self) -> f64;
+
+    /// Fraction of the energy drawn from the cells that reaches the grid
+    fn discharge_efficiency(&self) -> f64;
+
+    /// Highest charging C-rate
+    fn max_charge_c_rate(&self) -> f64;
+
+    /// Highest discharging C-rate
+    fn max_discharge_c_rate(&self) -> f64;
+
+    /// Lowest state of charge the pack may be discharged to
+    fn min_state_of_charge(&self) -> f64;
+
+    /// Fraction of the stored energy lost per hour at rest
+    fn self_discharge_per_hour(&self) -> f64;
+
+    /// Open-circuit voltage at a state of charge
+    fn voltage(&self, state_of_charge: f64) -> f64;
+
+    /// Get the stored energy left after resting for `elapsed`
+    fn after_self_discharge(&self, stored_energy: f64, elapsed: Duration) -> f64 {
+        stored_energy * (1.0 - self.self_discharge_per_hour()).powf(hours(elapsed))
+    }
+}
+
+/// Battery model with constant efficiencies and a linear voltage curve
+///
+/// The defaults describe a 12 V lithium iron phosphate pack.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinearBatteryModel {
+    pub charge_efficiency: f64,
+    pub discharge_efficiency: f64,
+    pub max_charge_c_rate: f64,      // 1/h
+    pub max_discharge_c_rate: f64,   // 1/h
+    pub depth_of_discharge: f64,     // Usable fraction of the capacity
+    pub self_discharge_per_day: f64, // Fraction of the stored energy
+    pub empty_voltage: f64,          // V at 0% state of charge
+    pub full_voltage: f64,           // V at 100% state of charge
+}
+
+impl Default for LinearBatteryModel {
+    fn default() -> Self {
+        Self {
+            charge_efficiency: 0.95,
+            discharge_efficiency: 0.95,
+            max_charge_c_rate: 0.5,
+            max_discharge_c_rate: 1.0,
+            depth_of_discharge: 0.9,
+            self_discharge_per_day: 0.001,
+            empty_voltage: 11.8,
+            full_voltage: 12.8,
+        }
+    }
+}
+
+impl BatteryModel for LinearBatteryModel {
+    fn charge_efficiency(&self) -> f64 {
+        self.charge_efficiency
+    }
+
+    fn discharge_efficiency(&self) -> f64 {
+        self.discharge_efficiency
+    }
+
+    fn max_charge_c_rate(&self) -> f64 {
+        self.max_charge_c_rate
+    }
+
+    fn max_discharge_c_rate(&self) -> f64 {
+        self.max_discharge_c_rate
+    }
+
+    fn min_state_of_charge(&self) -> f64 {
+        1.0 - self.depth_of_discharge
+    }
+
+    fn self_discharge_per_hour(&self) -> f64 {
+        1.0 - (1.0 - self.self_discharge_per_day).powf(1.0 / 24.0)
+    }
+
+    fn voltage(&self, state_of_charge: f64) -> f64 {
+        self.empty_voltage + (self.full_voltage - self.empty_voltage) * state_of_charge.clamp(0.0, 1.0)
+    }
+}
+
+/// Built-in battery model of a node, as stored in its config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "model", rename_all = "kebab-case")]
+pub enum BatteryModelConfig {
+    Linear(LinearBatteryModel),
+}
+
+impl Default for BatteryModelConfig {
+    fn default() -> Self {
+        BatteryModelConfig::Linear(LinearBatteryModel::default())
+    }
+}
+
+impl BatteryModelConfig {
+    fn model(&self) -> &dyn BatteryModel {
+        match self {
+            BatteryModelConfig::Linear(model) => model,
+        }
+    }
+}
+
+impl BatteryModel for BatteryModelConfig {
+    fn charge_efficiency(&self) -> f64 {
+        self.model().charge_efficiency()
+    }
+
+    fn discharge_efficiency(&self) -> f64 {
+        self.model().discharge_efficiency()
+    }
+
+    fn max_charge_c_rate(&self) -> f64 {
+        self.model().max_charge_c_rate()
+    }
+
+    fn max_discharge_c_rate(&self) -> f64 {
+        self.model().max_discharge_c_rate()
+    }
+
+    fn min_state_of_charge(&self) -> f64 {
+        self.model().min_state_of_charge()
+    }
+
+    fn self_discharge_per_hour(&self) -> f64 {
+        self.model().self_discharge_per_hour()
+    }
+
+    fn voltage(&self, state_of_charge: f64) -> f64 {
+        self.model().voltage(state_of_charge)
+    }
+}
+
+/// Get a duration in hours
+pub(crate) fn hours(duration: Duration) -> f64 {
+    duration.as_secs_f64() / 3600.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_model_curve_and_self_discharge() {
+        let model = LinearBatteryModel::default();
+        assert!((model.min_state_of_charge() - 0.1).abs() < 1e-9);
+        assert_eq!(model.voltage(0.0), 11.8);
+        assert_eq!(model.voltage(2.0), 12.8);
+        assert!((model.voltage(0.5) - 12.3).abs() < 1e-9);
+
+        let after_a_day = model.after_self_discharge(100.0, Duration::from_secs(24 * 3600));
+        assert!((after_a_day - 99.9).abs() < 1e-9);
+        assert_eq!(model.after_self_discharge(100.0, Duration::ZERO), 100.0);
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let config = BatteryModelConfig::Linear(LinearBatteryModel { full_voltage: 13.6, ..LinearBatteryModel::default() });
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.starts_with(r#"{"model":"linear""#));
+        assert_eq!(serde_json::from_str::<BatteryModelConfig>(&json).unwrap(), config);
+        assert_eq!(config.voltage(1.0), 13.6);
+    }
+}
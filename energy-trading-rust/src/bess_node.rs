@@ -1,9 +1,16 @@
+use crate::battery_model::{hours, BatteryModel, BatteryModelConfig};
+use crate::delivery::{DeliveryWindow, DispatchSchedule};
+use crate::degradation::{health_status_code, BatteryWear, DegradationModel, HealthEvent, TradeOutcome};
 use crate::etp_message::ETPMessage;
 use crate::error::{Result, ETPError};
+use crate::pricing::{PricingConfig, PricingContext, PricingPolicy};
+use crate::reservation::{EnergyHold, DEFAULT_HOLD_DURATION};
+use crate::termination::TerminationCode;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::{Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::RwLock;
 use tracing::info;
@@ -17,6 +24,9 @@ pub enum EnergyStatus {
     High,    // > 75% energy
 }
 
+/// Time a node assumes to deliver the energy of a bid, as bids carry no delivery window
+pub const DEFAULT_DELIVERY_WINDOW: Duration = Duration::from_secs(12 * 3600);
+
 /// BESS (Battery Energy Storage System) Node
 /// 
 /// Represents a distributed battery energy storage system that can:
@@ -24,6 +34,14 @@ pub enum EnergyStatus {
 /// - Respond to energy queries from aggregators
 /// - Evaluate and respond to bids
 /// - Maintain battery status and health information
+///
+/// Charging and discharging follow the node's `battery` model, unless a
+/// custom `BatteryModel` is set, which is not serialized.
+/// Discharging and aging wear the battery according to its `DegradationModel`,
+/// fading its capacity below `total_energy_capacity`, the rated capacity.
+/// Accepted bids hold their energy for `hold_duration` until confirmed; holds
+/// are not serialized. The lowest accepted price comes from the `pricing`
+/// policy, unless a custom `PricingPolicy` is set, which is not serialized.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BESSNode {
     pub device_id: u64,
@@ -37,6 +55,36 @@ pub struct BESSNode {
     pub percentage_for_sale: f64,   // % of energy available for trading
     pub is_online: bool,
     pub last_heartbeat: Option<std::time::SystemTime>,
+    #[serde(default = "default_delivery_window")]
+    pub delivery_window: Duration,  // Time allowed to deliver a sale
+    #[serde(default)]
+    pub degradation: DegradationModel,
+    #[serde(default)]
+    pub wear: BatteryWear,
+    #[serde(default)]
+    pub schedule: DispatchSchedule,
+    #[serde(default = "default_hold_duration")]
+    pub hold_duration: Duration,    // Time an accepted bid waits for its confirm
+    #[serde(default)]
+    pub pricing: PricingConfig,
+    #[serde(default)]
+    pub battery: BatteryModelConfig,
+    #[serde(skip)]
+    custom_battery_model: Option<Arc<dyn BatteryModel>>,
+    #[serde(skip)]
+    custom_pricing: Option<Arc<dyn PricingPolicy>>,
+    #[serde(skip)]
+    health_events: Vec<HealthEvent>,
+    #[serde(skip)]
+    holds: Vec<EnergyHold>,
+}
+
+fn default_delivery_window() -> Duration {
+    DEFAULT_DELIVERY_WINDOW
+}
+
+fn default_hold_duration() -> Duration {
+    DEFAULT_HOLD_DURATION
 }
 
 impl BESSNode {
@@ -47,6 +95,8 @@ impl BESSNode {
         total_energy_capacity: f64,
         reserve_price: f64,
     ) -> Self {
+        let battery = BatteryModelConfig::default();
+        let battery_voltage = battery.voltage(0.8);
         Self {
             device_id,
             device_name,
@@ -54,20 +104,141 @@ impl BESSNode {
             current_energy_level: total_energy_capacity * 0.8, // Start at 80% capacity
             reserve_price,
             max_discharge_rate: 5.0, // Default 5kW discharge rate
-            battery_voltage: 12.6,   // Default 12.6V
-            battery_health_status: 1, // Default good health
+            battery_voltage,         // On the model's curve at 80% charge
+            battery_health_status: health_status_code(1.0), // As new
             percentage_for_sale: 50.0, // Default 50% available for sale
             is_online: true,
             last_heartbeat: Some(std::time::SystemTime::now()),
+            delivery_window: DEFAULT_DELIVERY_WINDOW,
+            degradation: DegradationModel::default(),
+            wear: BatteryWear::default(),
+            schedule: DispatchSchedule::default(),
+            hold_duration: DEFAULT_HOLD_DURATION,
+            pricing: PricingConfig::default(),
+            battery,
+            custom_battery_model: None,
+            custom_pricing: None,
+            health_events: Vec::new(),
+            holds: Vec::new(),
+        }
+    }
+
+    /// Choose a built-in battery model, replacing any custom one
+    pub fn set_battery(&mut self, battery: BatteryModelConfig) {
+        self.battery = battery;
+        self.custom_battery_model = None;
+        self.update_voltage();
+    }
+
+    /// Model the battery with a custom model instead of `battery`
+    pub fn set_battery_model(&mut self, model: impl BatteryModel + 'static) {
+        self.custom_battery_model = Some(Arc::new(model));
+        self.update_voltage();
+    }
+
+    /// Get the battery model in use
+    pub fn battery_model(&self) -> &dyn BatteryModel {
+        match &self.custom_battery_model {
+            Some(model) => model.as_ref(),
+            None => &self.battery,
+        }
+    }
+
+    /// Get the capacity left after wear (kWh)
+    pub fn effective_capacity(&self) -> f64 {
+        self.total_energy_capacity * self.wear.state_of_health
+    }
+
+    /// Get the state of charge (0-1)
+    pub fn state_of_charge(&self) -> f64 {
+        self.current_energy_level / self.effective_capacity()
+    }
+
+    /// Get the stored energy the battery may not be discharged below
+    pub fn min_energy_level(&self) -> f64 {
+        self.effective_capacity() * self.battery_model().min_state_of_charge()
+    }
+
+    /// Get the highest discharge power, limited by the inverter and the C-rate
+    pub fn max_discharge_power(&self) -> f64 {
+        self.max_discharge_rate
+            .min(self.effective_capacity() * self.battery_model().max_discharge_c_rate())
+    }
+
+    /// Get the highest charge power
+    pub fn max_charge_power(&self) -> f64 {
+        self.effective_capacity() * self.battery_model().max_charge_c_rate()
+    }
+
+    /// Get the wear cost of delivering one kWh to the grid (cents)
+    pub fn wear_cost_per_kwh(&self) -> f64 {
+        self.degradation.wear_cost_per_kwh() / self.battery_model().discharge_efficiency()
+    }
+
+    /// Choose a built-in pricing policy, replacing any custom one
+    pub fn set_pricing(&mut self, pricing: PricingConfig) {
+        self.pricing = pricing;
+        self.custom_pricing = None;
+    }
+
+    /// Price with a custom policy instead of `pricing`
+    pub fn set_pricing_policy(&mut self, policy: impl PricingPolicy + 'static) {
+        self.custom_pricing = Some(Arc::new(policy));
+    }
+
+    /// Get the pricing policy in use
+    pub fn pricing_policy(&self) -> &dyn PricingPolicy {
+        match &self.custom_pricing {
+            Some(policy) => policy.as_ref(),
+            None => &self.pricing,
         }
     }
 
+    /// Get the lowest price accepted for a sale now, including wear (cents/kWh)
+    pub fn effective_reserve_price(&self) -> f64 {
+        self.reserve_price_for(0.0, SystemTime::now())
+    }
+
+    /// Get the lowest price accepted for `energy_amount` delivered from `delivery_time`, including wear (cents/kWh)
+    pub fn reserve_price_for(&self, energy_amount: f64, delivery_time: SystemTime) -> f64 {
+        let context = PricingContext {
+            reserve_price: self.reserve_price,
+            energy_status: self.get_energy_status(),
+            state_of_charge: self.state_of_charge(),
+            energy_amount,
+            delivery_time,
+        };
+        self.pricing_policy().reserve_price(&context) + self.wear_cost_per_kwh()
+    }
+
+    /// Get the energy the battery can put on the grid within `window`
+    pub fn deliverable_energy(&self, window: Duration) -> f64 {
+        let stored = self.battery_model().after_self_discharge(self.current_energy_level, window);
+        let usable = (stored - self.min_energy_level()).max(0.0) * self.battery_model().discharge_efficiency();
+        usable.min(self.max_discharge_power() * hours(window))
+    }
+
+    /// Check if the battery can physically deliver `energy` within `window`
+    pub fn can_deliver(&self, energy: f64, window: Duration) -> bool {
+        energy <= self.deliverable_energy(window)
+    }
+
     /// Get the amount of energy available for sale
     pub fn get_available_energy(&self) -> f64 {
         self.current_energy_level * (self.percentage_for_sale / 100.0)
     }
 
-    /// Check if the BESS can provide the requested energy amount
+    /// Get the energy held for accepted bids awaiting their confirm (kWh)
+    pub fn held_energy(&self) -> f64 {
+        self.holds.iter().map(|hold| hold.energy_amount).sum()
+    }
+
+    /// Get the energy promised to scheduled deliveries and held bids (kWh)
+    fn committed_energy(&self) -> f64 {
+        self.schedule.committed_energy() + self.held_energy()
+    }
+
+    /// Check if the BESS can provide the requested energy amount on top of its scheduled deliveries and holds
     pub fn can_provide_energy(&self, requested_energy: f64) -> bool {
         if requested_energy < 0.0 {
             return false;
@@ -75,43 +246,106 @@ impl BESSNode {
         if requested_energy == 0.0 {
             return true; // Zero energy requests are always acceptable (test/ping messages)
         }
-        self.get_available_energy() >= requested_energy
+        let committed = self.committed_energy();
+        self.get_available_energy() >= requested_energy + committed
+            && self.can_deliver(requested_energy + committed, self.delivery_window)
+    }
+
+    /// Check if the battery can make a delivery alongside its scheduled ones
+    pub fn can_schedule(&self, window: &DeliveryWindow) -> bool {
+        self.check_delivery(window).is_ok()
+    }
+
+    /// Explain why a delivery cannot be scheduled
+    fn check_delivery(&self, window: &DeliveryWindow) -> std::result::Result<(), String> {
+        window.validate().map_err(|e| e.to_string())?;
+        let now = SystemTime::now();
+        let Ok(until_end) = window.end().duration_since(now) else {
+            return Err("Delivery window has already ended".to_string());
+        };
+
+        let energy = window.energy() + self.committed_energy();
+        if self.get_available_energy() < energy {
+            return Err(TerminationCode::InsufficientEnergy.description().to_string());
+        }
+        let peak_power = self.planned_schedule().peak_power_with(window);
+        if peak_power > self.max_discharge_power() {
+            return Err(format!(
+                "Delivery needs {:.2} kW, above the {:.2} kW limit",
+                peak_power,
+                self.max_discharge_power()
+            ));
+        }
+        if self.deliverable_energy(until_end) < energy {
+            return Err(format!("Cannot deliver {:.2} kWh by the end of the window", energy));
+        }
+        Ok(())
+    }
+
+    /// Get the schedule as it would be with every held delivery window confirmed
+    fn planned_schedule(&self) -> DispatchSchedule {
+        let mut planned = self.schedule.clone();
+        for hold in &self.holds {
+            if let Some(window) = &hold.window {
+                let _ = planned.commit(hold.bid_id, hold.aggregator_id, window.clone());
+            }
+        }
+        planned
     }
 
     /// Evaluate a bid and determine if it should be accepted
     pub fn evaluate_bid(&self, bid_price: f64, requested_energy: f64) -> BidEvaluation {
         if !self.can_provide_energy(requested_energy) {
+            let reason = if requested_energy > 0.0 && self.get_available_energy() >= requested_energy {
+                format!(
+                    "Cannot deliver {:.2} kWh within {:.1} h at up to {:.2} kW",
+                    requested_energy,
+                    hours(self.delivery_window),
+                    self.max_discharge_power()
+                )
+            } else {
+                TerminationCode::InsufficientEnergy.description().to_string()
+            };
             return BidEvaluation::Reject {
-                reason: "Insufficient energy available".to_string(),
-                code: 2,
+                reason,
+                code: TerminationCode::InsufficientEnergy,
             };
         }
 
-        if !self.is_online {
+        self.evaluate_price(bid_price, requested_energy, SystemTime::now())
+    }
+
+    /// Evaluate a bid whose energy is delivered over `window`
+    pub fn evaluate_delivery(&self, bid_price: f64, window: &DeliveryWindow) -> BidEvaluation {
+        if let Err(reason) = self.check_delivery(window) {
             return BidEvaluation::Reject {
-                reason: "BESS is offline".to_string(),
-                code: 3,
+                reason,
+                code: TerminationCode::InsufficientEnergy,
             };
         }
+        self.evaluate_price(bid_price, window.energy(), window.start)
+    }
 
-        // Enhanced pricing based on energy status
-        let energy_status = self.get_energy_status();
-        let adjusted_reserve_price = match energy_status {
-            EnergyStatus::Critical => self.reserve_price * 2.0, // Double price when critical
-            EnergyStatus::Low => self.reserve_price * 1.5,      // 50% premium when low
-            EnergyStatus::Normal => self.reserve_price,         // Normal price
-            EnergyStatus::High => self.reserve_price * 0.9,     // 10% discount when high
-        };
+    /// Accept a deliverable bid if the node is online and the price is high enough
+    fn evaluate_price(&self, bid_price: f64, requested_energy: f64, delivery_time: SystemTime) -> BidEvaluation {
+        if !self.is_online {
+            return BidEvaluation::Reject {
+                reason: TerminationCode::Offline.description().to_string(),
+                code: TerminationCode::Offline,
+            };
+        }
 
-        if bid_price < adjusted_reserve_price {
-            let reason = match energy_status {
-                EnergyStatus::Critical => "Energy critical - only accepting premium bids".to_string(),
-                EnergyStatus::Low => "Energy low - bid below adjusted reserve price".to_string(),
-                _ => "Bid price below reserve price".to_string(),
+        // Price from the node's policy, plus battery wear
+        if bid_price < self.reserve_price_for(requested_energy, delivery_time) {
+            let soc_tiered = self.custom_pricing.is_none() && matches!(self.pricing, PricingConfig::SocTiered(_));
+            let reason = match self.get_energy_status() {
+                EnergyStatus::Critical if soc_tiered => "Energy critical - only accepting premium bids".to_string(),
+                EnergyStatus::Low if soc_tiered => "Energy low - bid below adjusted reserve price".to_string(),
+                _ => TerminationCode::PriceBelowReserve.description().to_string(),
             };
             return BidEvaluation::Reject {
                 reason,
-                code: 1,
+                code: TerminationCode::PriceBelowReserve,
             };
         }
 
@@ -148,7 +382,7 @@ impl BESSNode {
 
     /// Get the current energy status based on energy level
     pub fn get_energy_status(&self) -> EnergyStatus {
-        let percentage = (self.current_energy_level / self.total_energy_capacity) * 100.0;
+        let percentage = self.get_energy_percentage();
         match percentage {
             p if p < 10.0 => EnergyStatus::Critical,
             p if p < 25.0 => EnergyStatus::Low,
@@ -157,29 +391,272 @@ impl BESSNode {
         }
     }
 
-    /// Sell energy and update the current energy level
+    /// Sell energy and draw it, including conversion losses, from the battery
     pub fn sell_energy(&mut self, energy_amount: f64) -> Result<()> {
+        self.draw_sale(energy_amount).map(|_| ())
+    }
+
+    /// Sell energy at `sale_price` and account for the revenue and battery wear
+    pub fn settle_sale(&mut self, sale_price: f64, energy_amount: f64) -> Result<TradeOutcome> {
+        let wear_cost = self.draw_sale(energy_amount)?;
+        let outcome = TradeOutcome {
+            energy_amount,
+            sale_price,
+            revenue: sale_price * energy_amount,
+            wear_cost,
+        };
+        self.wear.revenue += outcome.revenue;
+        info!("BESS {} sale of {:.2} kWh at {:.2} c/kWh made {:.2} c after {:.2} c wear",
+              self.device_id, energy_amount, sale_price, outcome.profit(), wear_cost);
+        Ok(outcome)
+    }
+
+    /// Draw a sale from the battery, returning its wear cost
+    fn draw_sale(&mut self, energy_amount: f64) -> Result<f64> {
         if !self.can_provide_energy(energy_amount) {
             return Err(ETPError::InsufficientEnergy);
         }
-        self.current_energy_level -= energy_amount;
+        let drawn = energy_amount / self.battery_model().discharge_efficiency();
+        self.current_energy_level -= drawn;
+        let wear_cost = self.record_discharge(drawn);
+        self.update_voltage();
         info!("BESS {} sold {:.2} kWh, remaining: {:.2} kWh", 
               self.device_id, energy_amount, self.current_energy_level);
-        Ok(())
+        Ok(wear_cost)
+    }
+
+    /// Discharge at up to `power` kW for `elapsed`, returning the kWh delivered
+    ///
+    /// The power is capped at `max_discharge_power()` and discharging stops at
+    /// the depth-of-discharge floor.
+    pub fn discharge(&mut self, power: f64, elapsed: Duration) -> f64 {
+        let efficiency = self.battery_model().discharge_efficiency();
+        let requested = power.clamp(0.0, self.max_discharge_power()) * hours(elapsed) / efficiency;
+        let drawn = requested.min((self.current_energy_level - self.min_energy_level()).max(0.0));
+        self.current_energy_level -= drawn;
+        self.record_discharge(drawn);
+        self.update_voltage();
+        drawn * efficiency
+    }
+
+    /// Charge at up to `power` kW for `elapsed`, returning the kWh stored
+    pub fn charge(&mut self, power: f64, elapsed: Duration) -> f64 {
+        let offered = power.clamp(0.0, self.max_charge_power()) * hours(elapsed);
+        let stored = (offered * self.battery_model().charge_efficiency())
+            .min((self.effective_capacity() - self.current_energy_level).max(0.0));
+        self.current_energy_level += stored;
+        self.update_voltage();
+        stored
     }
 
-    /// Recharge energy over time (simulating solar charging)
+    /// Lose the energy the battery self-discharges while resting for `elapsed`
+    pub fn apply_self_discharge(&mut self, elapsed: Duration) {
+        self.current_energy_level = self.battery_model().after_self_discharge(self.current_energy_level, elapsed);
+        self.update_voltage();
+    }
+
+    /// Recharge energy over time at the highest charge power (simulating solar charging)
     pub fn recharge_energy(&mut self, time_elapsed_seconds: f64) {
-        let recharge_rate = 0.05; // 0.05 kWh per second (180 kWh/hour)
-        let recharge_amount = recharge_rate * time_elapsed_seconds;
-        let old_energy = self.current_energy_level;
-        self.current_energy_level = (self.current_energy_level + recharge_amount)
-            .min(self.total_energy_capacity);
-        
-        if self.current_energy_level > old_energy {
+        let elapsed = Duration::try_from_secs_f64(time_elapsed_seconds.max(0.0)).unwrap_or(Duration::MAX);
+        let stored = self.charge(self.max_charge_power(), elapsed);
+        if stored > 0.0 {
             info!("BESS {} recharged {:.2} kWh, new total: {:.2} kWh", 
-                  self.device_id, self.current_energy_level - old_energy, self.current_energy_level);
+                  self.device_id, stored, self.current_energy_level);
+        }
+    }
+
+    /// Commit to delivering over `window`; the schedule discharges the battery as it runs
+    pub fn schedule_delivery(&mut self, trade_id: u64, aggregator_id: u64, window: DeliveryWindow) -> Result<()> {
+        if let Err(reason) = self.check_delivery(&window) {
+            info!("BESS {} cannot schedule trade {}: {}", self.device_id, trade_id, reason);
+            return Err(ETPError::InsufficientEnergy);
+        }
+        self.schedule.commit(trade_id, aggregator_id, window)?;
+        self.schedule.last_run.get_or_insert_with(SystemTime::now);
+        info!("BESS {} scheduled trade {} for aggregator {}", self.device_id, trade_id, aggregator_id);
+        Ok(())
+    }
+
+    /// Discharge what the schedule called for since the last run, returning the kWh delivered
+    ///
+    /// Deliveries whose window has ended are dropped from the schedule.
+    pub fn run_schedule(&mut self, now: SystemTime) -> f64 {
+        let from = self.schedule.last_run.unwrap_or(now);
+        let elapsed = now.duration_since(from).unwrap_or_default();
+        let mut deliveries = std::mem::take(&mut self.schedule.deliveries);
+
+        let mut delivered = 0.0;
+        if !elapsed.is_zero() {
+            for delivery in &mut deliveries {
+                let due = delivery.window.energy_between(from, now).min(delivery.remaining());
+                if due > 0.0 {
+                    let made = self.discharge(due / hours(elapsed), elapsed);
+                    delivery.delivered += made;
+                    delivered += made;
+                }
+            }
+        }
+
+        deliveries.retain(|delivery| {
+            if now < delivery.window.end() {
+                return true;
+            }
+            info!("BESS {} finished trade {}: delivered {:.2} of {:.2} kWh",
+                  self.device_id, delivery.trade_id, delivery.delivered, delivery.window.energy());
+            false
+        });
+        self.schedule.deliveries = deliveries;
+        self.schedule.last_run = Some(now);
+        delivered
+    }
+
+    /// Evaluate a bid and, if accepted, hold its energy until `now + hold_duration`
+    ///
+    /// Evaluating and holding in one call keeps two bids from being accepted
+    /// for the same energy. Expired holds are released first.
+    pub fn reserve_bid(
+        &mut self,
+        bid_id: u64,
+        aggregator_id: u64,
+        bid_price: f64,
+        requested_energy: f64,
+        window: Option<&DeliveryWindow>,
+        now: SystemTime,
+    ) -> BidEvaluation {
+        self.expire_holds(now);
+        if self.holds.iter().any(|hold| hold.bid_id == bid_id) || self.schedule.get(bid_id).is_some() {
+            return BidEvaluation::Reject {
+                reason: format!("Bid {} is already accepted", bid_id),
+                code: TerminationCode::InvalidMessage,
+            };
         }
+
+        let evaluation = match window {
+            Some(window) => self.evaluate_delivery(bid_price, window),
+            None => self.evaluate_bid(bid_price, requested_energy),
+        };
+        if let BidEvaluation::Accept { sale_price, energy_amount } = evaluation {
+            self.holds.push(EnergyHold {
+                bid_id,
+                aggregator_id,
+                sale_price,
+                energy_amount,
+                window: window.cloned(),
+                expires_at: now + self.hold_duration,
+            });
+            info!("BESS {} holding {:.2} kWh for bid {} from aggregator {}",
+                  self.device_id, energy_amount, bid_id, aggregator_id);
+        }
+        evaluation
+    }
+
+    /// Commit a held bid on its BidConfirm
+    ///
+    /// A bid with a delivery window is scheduled and returns `None`; any other
+    /// is sold at once, returning its outcome. The hold is gone either way,
+    /// unless the confirm's price or energy differ from the accepted ones.
+    pub fn confirm_hold(
+        &mut self,
+        bid_id: u64,
+        sale_price: f64,
+        energy_amount: f64,
+        now: SystemTime,
+    ) -> Result<Option<TradeOutcome>> {
+        let Some(index) = self.holds.iter().position(|hold| hold.bid_id == bid_id) else {
+            return Err(ETPError::Validation(format!("No energy held for bid {}", bid_id)));
+        };
+        if !self.holds[index].matches(sale_price, energy_amount) {
+            return Err(ETPError::Validation(format!(
+                "Confirm for bid {} does not match the accepted {:.2} kWh at {:.2} c/kWh",
+                bid_id, self.holds[index].energy_amount, self.holds[index].sale_price
+            )));
+        }
+        let hold = self.holds.remove(index);
+        if hold.is_expired(now) {
+            return Err(ETPError::Validation(format!("Hold for bid {} expired", bid_id)));
+        }
+
+        match hold.window {
+            Some(window) => {
+                self.schedule_delivery(bid_id, hold.aggregator_id, window)?;
+                Ok(None)
+            }
+            None => self.settle_sale(hold.sale_price, hold.energy_amount).map(Some),
+        }
+    }
+
+    /// Release the hold of a bid that will not be confirmed
+    pub fn release_hold(&mut self, bid_id: u64) -> Option<EnergyHold> {
+        let index = self.holds.iter().position(|hold| hold.bid_id == bid_id)?;
+        let hold = self.holds.remove(index);
+        info!("BESS {} released {:.2} kWh held for bid {}", self.device_id, hold.energy_amount, bid_id);
+        Some(hold)
+    }
+
+    /// Release every hold that lapsed by `now`, returning them
+    pub fn expire_holds(&mut self, now: SystemTime) -> Vec<EnergyHold> {
+        let (expired, holds) = std::mem::take(&mut self.holds).into_iter().partition(|hold| hold.is_expired(now));
+        self.holds = holds;
+        for hold in &expired {
+            info!("BESS {} hold for bid {} expired", self.device_id, hold.bid_id);
+        }
+        expired
+    }
+
+    /// Check whether a bid still holds energy at `now`
+    pub fn is_holding(&self, bid_id: u64, now: SystemTime) -> bool {
+        self.holds.iter().any(|hold| hold.bid_id == bid_id && !hold.is_expired(now))
+    }
+
+    /// Get the holds of accepted bids awaiting their confirm
+    pub fn holds(&self) -> &[EnergyHold] {
+        &self.holds
+    }
+
+    /// Rest for `elapsed`, losing charge to self-discharge and capacity to calendar aging
+    pub fn age(&mut self, elapsed: Duration) {
+        self.apply_self_discharge(elapsed);
+        self.fade(self.degradation.calendar_fade(elapsed));
+    }
+
+    /// Take the health status changes since the last call
+    pub fn take_health_events(&mut self) -> Vec<HealthEvent> {
+        std::mem::take(&mut self.health_events)
+    }
+
+    /// Count `drawn` kWh towards cycles and capacity fade, returning its wear cost
+    fn record_discharge(&mut self, drawn: f64) -> f64 {
+        let cycles = drawn / self.total_energy_capacity;
+        let wear_cost = drawn * self.degradation.wear_cost_per_kwh();
+        self.wear.equivalent_cycles += cycles;
+        self.wear.energy_throughput += drawn;
+        self.wear.wear_cost += wear_cost;
+        self.fade(cycles * self.degradation.fade_per_cycle());
+        wear_cost
+    }
+
+    /// Lose `loss` of the state of health; the status code only ever gets worse
+    fn fade(&mut self, loss: f64) {
+        self.wear.state_of_health = (self.wear.state_of_health - loss).max(0.0);
+        self.current_energy_level = self.current_energy_level.min(self.effective_capacity());
+
+        let status = health_status_code(self.wear.state_of_health);
+        if status > self.battery_health_status {
+            info!("BESS {} health status {} -> {} at {:.1}% state of health",
+                  self.device_id, self.battery_health_status, status, self.wear.state_of_health * 100.0);
+            self.health_events.push(HealthEvent {
+                device_id: self.device_id,
+                previous_status: self.battery_health_status,
+                status,
+                state_of_health: self.wear.state_of_health,
+            });
+            self.battery_health_status = status;
+        }
+    }
+
+    /// Follow the battery voltage along the model's curve
+    fn update_voltage(&mut self) {
+        self.battery_voltage = self.battery_model().voltage(self.state_of_charge());
     }
 
     /// Check if BESS is depleted (no energy available for sale)
@@ -189,17 +666,21 @@ impl BESSNode {
 
     /// Get energy percentage (0-100)
     pub fn get_energy_percentage(&self) -> f64 {
-        (self.current_energy_level / self.total_energy_capacity) * 100.0
+        self.state_of_charge() * 100.0
     }
 
     /// Generate a query response message
+    ///
+    /// `sale_price` carries the node's current asking price, so aggregators can compare offers.
     pub fn generate_query_response(&self, message_id: u64, _aggregator_device_id: u64) -> ETPMessage {
-        ETPMessage::new_query_response(
+        let mut response = ETPMessage::new_query_response(
             message_id,
             self.device_id,
             self.current_energy_level,
             self.percentage_for_sale,
-        )
+        );
+        response.sale_price = self.effective_reserve_price();
+        response
     }
 }
 
@@ -211,8 +692,8 @@ pub enum BidEvaluation {
         energy_amount: f64,
     },
     Reject {
-        reason: String,
-        code: u8, // 1=price too low, 2=insufficient energy, 3=offline, etc.
+        reason: String, // Detail for logs and the UI
+        code: TerminationCode,
     },
 }
 
@@ -327,7 +808,7 @@ mod tests {
         match evaluation {
             BidEvaluation::Reject { reason, code } => {
                 assert_eq!(reason, "Bid price below reserve price");
-                assert_eq!(code, 1);
+                assert_eq!(code, TerminationCode::PriceBelowReserve);
             }
             _ => panic!("Expected Reject evaluation"),
         }
@@ -342,7 +823,23 @@ mod tests {
         match evaluation {
             BidEvaluation::Reject { reason, code } => {
                 assert_eq!(reason, "Insufficient energy available");
-                assert_eq!(code, 2);
+                assert_eq!(code, TerminationCode::InsufficientEnergy);
+            }
+            _ => panic!("Expected Reject evaluation"),
+        }
+    }
+
+    #[test]
+    fn test_bid_evaluation_reject_undeliverable_in_window() {
+        let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+        bess.delivery_window = Duration::from_secs(3600); // 5 kWh at the 5 kW inverter limit
+        assert!(bess.can_provide_energy(5.0));
+        assert!(!bess.can_provide_energy(10.0));
+
+        match bess.evaluate_bid(20.0, 10.0) {
+            BidEvaluation::Reject { reason, code } => {
+                assert_eq!(reason, "Cannot deliver 10.00 kWh within 1.0 h at up to 5.00 kW");
+                assert_eq!(code, TerminationCode::InsufficientEnergy);
             }
             _ => panic!("Expected Reject evaluation"),
         }
@@ -1,20 +1,102 @@
 use crate::bess_node::{BESSNode, BidEvaluation};
-use crate::etp_message::ETPMessage;
+use crate::codec::delivery_frame::{DeliveryFrame, DeliveryFrameKind};
+use crate::codec::wire::SUPPORTED_PROTOCOL_VERSIONS;
+use crate::delivery::{DeliveryWindow, DispatchSchedule, ScheduledDelivery};
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::etp_payload::EtpPayload;
+use crate::envelope::{EnvelopeVerifier, MessageSigner};
 use crate::error::Result;
-use crate::network::unicast_connection::UnicastConnection;
+use crate::replay_protection::ReplayCache;
+use crate::session::EtpSession;
+use crate::termination::TerminationCode;
+use crate::validation::ValidationRules;
+use crate::network::dispatcher::{DispatcherMetrics, LatePolicy, MessageDispatcher};
+use crate::network::handshake::{Capabilities, Hello};
+use crate::network::unicast_connection::{Received, UnicastConnection};
+use crate::network::websocket_gateway::{SystemEvent, WebSocketGateway};
+use futures::FutureExt;
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError, Weak};
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::RwLock;
+use tokio::time::Duration;
 use tracing::{info, warn, error};
 
+/// How often a running server discharges its node's schedule and expires holds
+const SCHEDULE_TICK: Duration = Duration::from_secs(1);
+
+/// How often a running server ages its node by self-discharge and calendar fade
+const AGING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Most delivery windows one connection may attach to bids not yet answered
+const MAX_PENDING_WINDOWS: usize = 64;
+
+/// Trading state of one connection
+#[derive(Default)]
+struct ConnectionTrades {
+    windows: HashMap<u64, DeliveryWindow>, // By bid message_id, bid not yet evaluated
+    held: Option<u64>,                     // Accepted bid holding energy, awaiting its confirm
+}
+
+/// Per-connection protocol settings shared by every accepted connection
+#[derive(Clone)]
+struct ConnectionSettings {
+    hello: Hello,
+    bridge_mode: bool,
+    compression: bool,
+    signer: Option<MessageSigner>,
+    verifier: Option<Arc<EnvelopeVerifier>>,
+    replay_cache: Arc<ReplayCache>,
+    late_policy: LatePolicy,
+    dispatcher_metrics: Arc<Mutex<DispatcherMetrics>>,
+    validation_rules: ValidationRules,
+    capture_dir: Option<PathBuf>,
+    gateway: Option<Arc<WebSocketGateway>>,
+}
+
+impl ConnectionSettings {
+    /// Configure a freshly accepted connection
+    fn apply(&self, connection: &mut UnicastConnection) {
+        if self.bridge_mode {
+            connection.enable_bridge_mode();
+        }
+        connection.set_compression(self.compression);
+        if let Some(signer) = &self.signer {
+            connection.set_signer(signer.clone());
+        }
+        if let Some(verifier) = &self.verifier {
+            connection.set_verifier(verifier.clone());
+        }
+        connection.set_replay_cache(self.replay_cache.clone());
+        if let Some(capture_dir) = &self.capture_dir {
+            let path = capture_dir.join(capture_file_name(connection.peer_addr().ok()));
+            if let Err(e) = connection.record_to(&path) {
+                warn!("Not recording connection to {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Name of the capture file for a connection accepted now
+fn capture_file_name(peer_addr: Option<SocketAddr>) -> String {
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or(0);
+    let peer = peer_addr.map(|addr| addr.to_string().replace([':', '[', ']'], "_")).unwrap_or_default();
+    format!("etp-{}-{}.jsonl", now_ms, peer)
+}
+
 /// BESS TCP Server
 /// 
 /// Handles TCP connections from aggregators and processes ETP messages.
 /// Implements timing constraints and concurrent connection handling.
 pub struct BESSTCPServer {
     bess_node: Arc<RwLock<BESSNode>>,
+    settings: ConnectionSettings,
     listener: Option<TcpListener>,
     is_running: Arc<AtomicBool>,
     local_addr: Option<SocketAddr>,
@@ -31,19 +113,114 @@ impl BESSTCPServer {
         
         Ok(Self {
             bess_node: Arc::new(RwLock::new(bess_node)),
+            settings: ConnectionSettings {
+                hello: Hello::new(SUPPORTED_PROTOCOL_VERSIONS.to_vec(), Capabilities::DELIVERY_WINDOWS),
+                bridge_mode: false,
+                compression: false,
+                signer: None,
+                verifier: None,
+                replay_cache: Arc::default(), // Not applied to Go text peers
+                late_policy: LatePolicy::Drop,
+                dispatcher_metrics: Arc::default(),
+                // TTL is enforced by relays; the Go prototype always sends 0
+                validation_rules: ValidationRules {
+                    require_ttl: false,
+                    ..ValidationRules::default()
+                },
+                capture_dir: None,
+                gateway: None,
+            },
             listener: Some(listener),
             is_running: Arc::new(AtomicBool::new(false)),
             local_addr: Some(local_addr),
         })
     }
     
+    /// Set the protocol versions and capabilities advertised to connecting aggregators
+    pub fn set_hello(&mut self, hello: Hello) {
+        self.settings.hello = hello;
+    }
+
+    /// Accept legacy Go prototype aggregators alongside ETP peers
+    pub fn set_bridge_mode(&mut self, enabled: bool) {
+        self.settings.bridge_mode = enabled;
+    }
+
+    /// Compress responses to aggregators that negotiated `Capabilities::COMPRESSION`
+    pub fn set_compression(&mut self, enabled: bool) {
+        self.settings.compression = enabled;
+    }
+
+    /// Sign responses sent to aggregators with this key
+    pub fn set_signer(&mut self, signer: MessageSigner) {
+        self.settings.signer = Some(signer);
+    }
+
+    /// Require signed messages from aggregators; connections sending unsigned or forged bids are closed
+    pub fn set_verifier(&mut self, verifier: EnvelopeVerifier) {
+        self.settings.verifier = Some(Arc::new(verifier));
+    }
+
+    /// Drop duplicate or stale messages across all connections, as remembered by this cache
+    ///
+    /// A default cache is in place from the start. Go text peers are never
+    /// checked, as they reuse message ids.
+    pub fn set_replay_cache(&mut self, replay_cache: ReplayCache) {
+        self.settings.replay_cache = Arc::new(replay_cache);
+    }
+
+    /// Choose whether messages that missed their deadline while queued are dropped or processed anyway
+    pub fn set_late_policy(&mut self, late_policy: LatePolicy) {
+        self.settings.late_policy = late_policy;
+    }
+
+    /// Replace the field rules applied to incoming messages; invalid messages are dropped
+    pub fn set_validation_rules(&mut self, validation_rules: ValidationRules) {
+        self.settings.validation_rules = validation_rules;
+    }
+
+    /// Record every connection to its own capture file in `capture_dir`
+    ///
+    /// Files are named after the connection time and the aggregator's address.
+    pub fn set_capture_dir(&mut self, capture_dir: impl Into<PathBuf>) {
+        self.settings.capture_dir = Some(capture_dir.into());
+    }
+
+    /// Report health changes, settled sales and the schedule to this gateway
+    pub fn set_gateway(&mut self, gateway: Arc<WebSocketGateway>) {
+        self.settings.gateway = Some(gateway);
+    }
+
+    /// Get queue depths per priority and late-message counts across all connections
+    pub fn dispatcher_metrics(&self) -> DispatcherMetrics {
+        self.settings.dispatcher_metrics.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Get the node, shared with the connections of a running server
+    pub fn bess_node(&self) -> Arc<RwLock<BESSNode>> {
+        self.bess_node.clone()
+    }
+
+    /// Get the node's committed deliveries
+    pub async fn schedule(&self) -> DispatchSchedule {
+        self.bess_node.read().await.schedule.clone()
+    }
+
     /// Start the TCP server
+    ///
+    /// While running, the node's schedule is discharged and lapsed holds are
+    /// released every second, and the node ages every minute.
     pub async fn start(&mut self) -> Result<()> {
         let listener = self.listener.take()
             .ok_or_else(|| crate::error::ETPError::Network("Server not initialized".to_string()))?;
         
         self.is_running.store(true, Ordering::Relaxed);
         info!("BESS TCP Server started on {}", self.local_addr.unwrap());
+        tokio::spawn(Self::maintain_node(
+            Arc::downgrade(&self.bess_node),
+            self.is_running.clone(),
+            self.settings.gateway.clone(),
+        ));
         
         // Accept connections in a loop
         while self.is_running.load(Ordering::Relaxed) {
@@ -53,10 +230,11 @@ impl BESSTCPServer {
                     
                     // Spawn a task to handle this connection
                     let bess_node = self.bess_node.clone();
+                    let settings = self.settings.clone();
                     let _is_running = self.is_running.clone();
                     
                     tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(stream, addr, bess_node).await {
+                        if let Err(e) = Self::handle_connection(stream, addr, bess_node, settings).await {
                             error!("Error handling connection from {}: {}", addr, e);
                         }
                     });
@@ -72,6 +250,66 @@ impl BESSTCPServer {
         Ok(())
     }
     
+    /// Age the node, discharge the schedule and expire holds until the server stops or is dropped
+    ///
+    /// The gateway hears of health changes, and of the schedule while it has
+    /// deliveries and once more after the last one ends.
+    async fn maintain_node(
+        bess_node: Weak<RwLock<BESSNode>>,
+        is_running: Arc<AtomicBool>,
+        gateway: Option<Arc<WebSocketGateway>>,
+    ) {
+        let mut ticks = tokio::time::interval(SCHEDULE_TICK);
+        let mut last_aged = std::time::SystemTime::now();
+        let mut had_deliveries = true; // Publish the schedule on the first tick
+        while is_running.load(Ordering::Relaxed) {
+            ticks.tick().await;
+            let Some(bess_node) = bess_node.upgrade() else {
+                break;
+            };
+            let now = std::time::SystemTime::now();
+            let mut bess = bess_node.write().await;
+            let since_aged = now.duration_since(last_aged).unwrap_or_default();
+            if since_aged >= AGING_INTERVAL {
+                bess.age(since_aged);
+                last_aged = now;
+            }
+            let has_deliveries = !bess.schedule.is_empty();
+            if has_deliveries {
+                bess.run_schedule(now);
+            }
+            bess.expire_holds(now);
+
+            let Some(gateway) = &gateway else {
+                bess.take_health_events();
+                continue;
+            };
+            let events: Vec<SystemEvent> = bess.take_health_events().into_iter().map(SystemEvent::from).collect();
+            let schedule = (has_deliveries || had_deliveries).then(|| bess.schedule.deliveries.clone());
+            let bess_id = bess.device_id;
+            drop(bess);
+            had_deliveries = has_deliveries;
+            Self::report(gateway, bess_id, events, schedule).await;
+        }
+    }
+
+    /// Broadcast node events and publish a changed schedule
+    async fn report(
+        gateway: &WebSocketGateway,
+        bess_id: u64,
+        events: Vec<SystemEvent>,
+        schedule: Option<Vec<ScheduledDelivery>>,
+    ) {
+        for event in events {
+            if let Err(e) = gateway.broadcast_event(event).await {
+                warn!("Cannot broadcast event of BESS {}: {}", bess_id, e);
+            }
+        }
+        if let Some(deliveries) = schedule {
+            gateway.publish_schedule(bess_id, deliveries).await;
+        }
+    }
+
     /// Check if server is running
     pub async fn is_running(&self) -> bool {
         self.is_running.load(Ordering::Relaxed)
@@ -95,78 +333,287 @@ impl BESSTCPServer {
     async fn handle_connection(
         stream: TcpStream, 
         addr: SocketAddr, 
-        bess_node: Arc<RwLock<BESSNode>>
+        bess_node: Arc<RwLock<BESSNode>>,
+        settings: ConnectionSettings,
     ) -> Result<()> {
         let mut connection = UnicastConnection::new(stream);
+        settings.apply(&mut connection);
         
-        loop {
-            match connection.receive_message().await {
-                Ok(message) => {
-                    // Process message with timing constraints
-                    let start = std::time::Instant::now();
-                    let max_delay = message.get_max_delay_ms();
-                    match Self::process_message(message, &bess_node, &mut connection).await {
-                        Ok(_) => {
-                            let elapsed = start.elapsed().as_millis() as u64;
-                            if elapsed > max_delay {
-                                error!("Timing violation processing message from {}: {}ms > {}ms", 
-                                       addr, elapsed, max_delay);
-                                break;
-                            }
-                        }
-                        Err(e) => {
-                            error!("Error processing message from {}: {}", addr, e);
-                            break;
-                        }
+        // Negotiate the protocol version; peers without a hello stay on version 1
+        match connection.accept_handshake(&settings.hello).await {
+            Ok(Some(protocol)) => {
+                info!("Connection from {} using ETP version {}", addr, protocol.version);
+            }
+            Ok(None) => {
+                info!("Connection from {} skipped handshake, assuming ETP version {}", 
+                      addr, connection.protocol().version);
+            }
+            Err(e) => {
+                error!("Handshake with {} failed: {}", addr, e);
+                return Ok(());
+            }
+        }
+        
+        // Queue everything that has arrived and handle the most urgent message first
+        let mut dispatcher = MessageDispatcher::with_metrics(settings.late_policy, settings.dispatcher_metrics.clone());
+        let mut session = EtpSession::new();
+        let mut trades = ConnectionTrades::default();
+        let mut deliveries = Vec::new();
+        let mut schedule_request = None; // Nothing received after it is read until it is answered
+        let mut receiving = true;
+        while receiving || !dispatcher.is_empty() || schedule_request.is_some() {
+            if receiving && schedule_request.is_none() && dispatcher.is_empty() {
+                let received = connection.receive().await;
+                receiving = Self::queue_received(received, &mut dispatcher, &mut deliveries, &mut schedule_request, addr);
+            }
+            while receiving && schedule_request.is_none() {
+                match connection.receive().now_or_never() {
+                    Some(received) => {
+                        receiving =
+                            Self::queue_received(received, &mut dispatcher, &mut deliveries, &mut schedule_request, addr)
                     }
+                    None => break,
                 }
-                Err(e) => {
-                    if let crate::error::ETPError::Io(io_error) = &e {
-                        if io_error.kind() == std::io::ErrorKind::UnexpectedEof {
-                            info!("Connection closed by peer: {}", addr);
-                        } else {
-                            error!("Error receiving message from {}: {}", addr, io_error);
-                        }
-                    } else {
-                        error!("Error processing message from {}: {}", addr, e);
+            }
+            // Windows skip the queue, so a window is in place before its bid is handled
+            for frame in deliveries.drain(..) {
+                Self::process_delivery_frame(frame, &bess_node, &mut connection, &mut trades).await?;
+            }
+
+            let Some(dispatched) = dispatcher.pop() else {
+                // Everything received before the schedule request has been handled
+                if let Some(frame) = schedule_request.take() {
+                    Self::process_delivery_frame(frame, &bess_node, &mut connection, &mut trades).await?;
+                }
+                continue;
+            };
+            if let Err(e) = settings.validation_rules.validate(&dispatched.message) {
+                warn!("Dropping message {} from {}: {}", dispatched.message.message_id, addr, e);
+                // A bidder would otherwise wait out its deadline
+                if matches!(dispatched.message.kind(), Ok(MessageType::Bid)) {
+                    let device_id = bess_node.read().await.device_id;
+                    let reject = ETPMessage::new_bid_reject(dispatched.message.message_id, device_id, TerminationCode::InvalidMessage);
+                    if let Err(e) = connection.send_message(reject).await {
+                        error!("Error rejecting message from {}: {}", addr, e);
+                        break;
                     }
+                }
+                continue;
+            }
+            // Holding energy for a bidder that gave up would only block other bids
+            if dispatched.late
+                && settings.late_policy == LatePolicy::Drop
+                && matches!(dispatched.message.kind(), Ok(MessageType::Bid))
+            {
+                warn!("Refusing bid {} from {}: missed its deadline", dispatched.message.message_id, addr);
+                let device_id = bess_node.read().await.device_id;
+                let reject = ETPMessage::new_bid_reject(dispatched.message.message_id, device_id, TerminationCode::TtlExpired);
+                if let Err(e) = connection.send_message(reject).await {
+                    error!("Error rejecting message from {}: {}", addr, e);
+                    break;
+                }
+                continue;
+            }
+            // A lapsed hold no longer blocks the next Query or Bid
+            if !matches!(dispatched.message.kind(), Ok(MessageType::BidConfirm)) {
+                Self::drop_lapsed_hold(&bess_node, &mut session, &mut trades).await;
+            }
+            // Out-of-order messages, such as a confirm for a bid never accepted, are rejected
+            if let Err(e) = session.advance(&dispatched.message) {
+                warn!("Rejecting message {} from {}: {}", dispatched.message.message_id, addr, e);
+                let device_id = bess_node.read().await.device_id;
+                let rejection = Self::out_of_order_reply(&dispatched.message, device_id);
+                let closing = rejection.message_type == MessageType::Terminate.as_u8();
+                if let Err(e) = connection.send_message(rejection).await {
+                    error!("Error rejecting message from {}: {}", addr, e);
+                    break;
+                }
+                if closing {
+                    break;
+                }
+                continue;
+            }
+            if dispatched.late {
+                warn!("Processing message {} from {} after its deadline", dispatched.message.message_id, addr);
+            }
+
+            // Process message with timing constraints
+            let start = std::time::Instant::now();
+            let max_delay = dispatched.message.get_max_delay_ms();
+            match Self::process_message(dispatched.message, &bess_node, &mut connection, &mut session, &mut trades, settings.gateway.as_deref()).await {
+                Ok(_) => {
+                    let elapsed = start.elapsed().as_millis() as u64;
+                    if elapsed > max_delay {
+                        error!("Timing violation processing message from {}: {}ms > {}ms", 
+                               addr, elapsed, max_delay);
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!("Error processing message from {}: {}", addr, e);
                     break;
                 }
             }
         }
+
+        // A bid the aggregator can no longer confirm stops holding energy
+        if let Some(bid_id) = trades.held.take() {
+            bess_node.write().await.release_hold(bid_id);
+        }
         
         Ok(())
     }
     
+    /// Answer a message that is out of order for the session
+    ///
+    /// Bids and confirms get a BidReject and the conversation goes on; anything
+    /// else gets a Terminate and the connection is closed.
+    fn out_of_order_reply(message: &ETPMessage, device_id: u64) -> ETPMessage {
+        match message.kind() {
+            Ok(MessageType::Bid | MessageType::BidConfirm) => {
+                ETPMessage::new_bid_reject(message.message_id, device_id, TerminationCode::InvalidMessage)
+            }
+            _ => ETPMessage::new_terminate(message.message_id, device_id, TerminationCode::InvalidMessage),
+        }
+    }
+
+    /// Reopen the session if the connection's accepted bid no longer holds energy
+    async fn drop_lapsed_hold(bess_node: &Arc<RwLock<BESSNode>>, session: &mut EtpSession, trades: &mut ConnectionTrades) {
+        let Some(bid_id) = trades.held else {
+            return;
+        };
+        if !bess_node.read().await.is_holding(bid_id, std::time::SystemTime::now()) {
+            trades.held = None;
+            session.reopen();
+        }
+    }
+
+    /// Queue a received message, returning whether to keep reading from the connection
+    ///
+    /// A schedule request is set aside to be answered in arrival order.
+    fn queue_received(
+        received: Result<Received>,
+        dispatcher: &mut MessageDispatcher,
+        deliveries: &mut Vec<DeliveryFrame>,
+        schedule_request: &mut Option<DeliveryFrame>,
+        addr: SocketAddr,
+    ) -> bool {
+        match received {
+            Ok(Received::Message(message)) => {
+                dispatcher.push(message);
+                true
+            }
+            Ok(Received::Delivery(frame)) if frame.kind == DeliveryFrameKind::ScheduleRequest => {
+                *schedule_request = Some(frame);
+                true
+            }
+            Ok(Received::Delivery(frame)) => {
+                deliveries.push(frame);
+                true
+            }
+            Err(crate::error::ETPError::Replay(reason)) => {
+                // Drop the message but keep serving the connection
+                warn!("Dropping message from {}: {}", addr, reason);
+                true
+            }
+            Err(crate::error::ETPError::Io(io_error)) if io_error.kind() == std::io::ErrorKind::UnexpectedEof => {
+                info!("Connection closed by peer: {}", addr);
+                false
+            }
+            Err(crate::error::ETPError::Authentication(reason)) => {
+                warn!("Closing connection from {}: {}", addr, reason);
+                false
+            }
+            Err(e) => {
+                error!("Error receiving message from {}: {}", addr, e);
+                false
+            }
+        }
+    }
+
+    /// Keep a bid's delivery window, or answer a schedule request
+    async fn process_delivery_frame(
+        frame: DeliveryFrame,
+        bess_node: &Arc<RwLock<BESSNode>>,
+        connection: &mut UnicastConnection,
+        trades: &mut ConnectionTrades,
+    ) -> Result<()> {
+        match frame.kind {
+            DeliveryFrameKind::Window => match frame.delivery_window() {
+                Some(window) if trades.windows.len() < MAX_PENDING_WINDOWS => {
+                    trades.windows.insert(frame.message_id, window.clone());
+                }
+                _ => warn!("Dropping delivery window for bid {}", frame.message_id),
+            },
+            DeliveryFrameKind::ScheduleRequest => {
+                let bess = bess_node.read().await;
+                let schedule = DeliveryFrame::schedule(frame.message_id, bess.device_id, bess.schedule.deliveries.clone());
+                drop(bess);
+                connection.send_delivery(&schedule).await?;
+            }
+            DeliveryFrameKind::Schedule => {
+                info!("Ignoring schedule from device {}", frame.device_id);
+            }
+        }
+        Ok(())
+    }
+
     /// Process a received ETP message
     async fn process_message(
         message: ETPMessage,
         bess_node: &Arc<RwLock<BESSNode>>,
         connection: &mut UnicastConnection,
+        session: &mut EtpSession,
+        trades: &mut ConnectionTrades,
+        gateway: Option<&WebSocketGateway>,
     ) -> Result<()> {
-        let response = match message.message_type {
-            0 => { // Register
+        let payload = match message.payload() {
+            Ok(payload) => payload,
+            Err(_) => {
+                warn!("Unknown message type: {}", message.message_type);
+                return Ok(());
+            }
+        };
+
+        let response = match payload {
+            EtpPayload::Register => {
                 info!("Processing register message from device {}", message.device_id);
                 None // No response needed for register
             }
-            1 => { // Query
+            EtpPayload::Query => {
                 info!("Processing query message from device {}", message.device_id);
                 let bess = bess_node.read().await;
                 Some(bess.generate_query_response(message.message_id, message.device_id))
             }
-            2 => { // Query Response
+            EtpPayload::QueryResponse(_) => {
                 info!("Processing query response from device {}", message.device_id);
                 None // BESS doesn't send query responses
             }
-            3 => { // Bid
+            EtpPayload::Bid(bid) => {
                 info!("Processing bid message from device {}: {:.2}¢/kWh for {:.2} kWh", 
-                      message.device_id, message.bid_price, message.required_energy_amount);
+                      message.device_id, bid.bid_price, bid.required_energy_amount);
                 
-                let bess = bess_node.read().await;
-                let evaluation = bess.evaluate_bid(message.bid_price, message.required_energy_amount);
+                // Evaluate and hold under the write lock, so no other connection is accepted for the same energy
+                let mut bess = bess_node.write().await;
+                let window = trades.windows.remove(&message.message_id);
+                let evaluation = match &window {
+                    Some(window) if (window.energy() - bid.required_energy_amount).abs() > 1e-6 => BidEvaluation::Reject {
+                        reason: "Delivery window energy does not match the bid".to_string(),
+                        code: TerminationCode::InvalidMessage,
+                    },
+                    _ => bess.reserve_bid(
+                        message.message_id,
+                        message.device_id,
+                        bid.bid_price,
+                        bid.required_energy_amount,
+                        window.as_ref(),
+                        std::time::SystemTime::now(),
+                    ),
+                };
                 
                 match evaluation {
                     BidEvaluation::Accept { sale_price, energy_amount } => {
+                        trades.held = Some(message.message_id);
                         Some(ETPMessage::new_bid_accept(
                             message.message_id,
                             bess.device_id,
@@ -174,7 +621,8 @@ impl BESSTCPServer {
                             energy_amount,
                         ))
                     }
-                    BidEvaluation::Reject { reason: _, code } => {
+                    BidEvaluation::Reject { reason, code } => {
+                        info!("Rejecting bid {} from device {}: {}", message.message_id, message.device_id, reason);
                         Some(ETPMessage::new_bid_reject(
                             message.message_id,
                             bess.device_id,
@@ -183,38 +631,85 @@ impl BESSTCPServer {
                     }
                 }
             }
-            4 => { // Bid Accept
+            EtpPayload::BidAccept(_) => {
                 info!("Processing bid accept from device {}", message.device_id);
                 None // BESS doesn't process bid accepts
             }
-            5 => { // Bid Confirm
-                info!("Processing bid confirm from device {}", message.device_id);
-                None // BESS doesn't process bid confirms
+            EtpPayload::BidConfirm(sale) => {
+                info!("Bid {} confirmed by device {}", message.message_id, message.device_id);
+                if trades.held != Some(message.message_id) {
+                    return Ok(());
+                }
+                trades.held = None;
+
+                let mut bess = bess_node.write().await;
+                let now = std::time::SystemTime::now();
+                let mut events = Vec::new();
+                let mut schedule = None;
+                let refused = if !bess.is_holding(message.message_id, now) {
+                    Some(TerminationCode::TtlExpired)
+                } else {
+                    match bess.confirm_hold(message.message_id, sale.sale_price, sale.energy_amount, now) {
+                        Ok(Some(outcome)) => {
+                            info!("Bid {} settled for {:.2} c", message.message_id, outcome.revenue);
+                            events.push(SystemEvent::trade_settled(bess.device_id, &outcome));
+                            None
+                        }
+                        Ok(None) => {
+                            info!("Bid {} scheduled for delivery", message.message_id);
+                            schedule = Some(bess.schedule.deliveries.clone());
+                            None
+                        }
+                        Err(crate::error::ETPError::InsufficientEnergy) => Some(TerminationCode::InsufficientEnergy),
+                        Err(e) => {
+                            warn!("Cannot commit bid {}: {}", message.message_id, e);
+                            Some(TerminationCode::InvalidMessage)
+                        }
+                    }
+                };
+
+                // A refused confirm is answered, so the aggregator does not count the purchase
+                let Some(code) = refused else {
+                    if let Some(gateway) = gateway {
+                        events.extend(bess.take_health_events().into_iter().map(SystemEvent::from));
+                        let bess_id = bess.device_id;
+                        drop(bess);
+                        Self::report(gateway, bess_id, events, schedule).await;
+                    }
+                    return Ok(());
+                };
+                bess.release_hold(message.message_id);
+                let device_id = bess.device_id;
+                drop(bess);
+                info!("Refusing confirm for bid {} from device {}: {}", message.message_id, message.device_id, code.description());
+                session.reopen();
+                connection.send_message(ETPMessage::new_bid_reject(message.message_id, device_id, code)).await?;
+                None
             }
-            6 => { // Bid Reject
+            EtpPayload::BidReject(_) => {
                 info!("Processing bid reject from device {}", message.device_id);
                 None // BESS doesn't process bid rejects
             }
-            7 => { // Terminate
+            EtpPayload::Terminate(_) => {
                 info!("Processing terminate message from device {}", message.device_id);
+                if let Some(bid_id) = trades.held.take() {
+                    bess_node.write().await.release_hold(bid_id);
+                }
                 None // No response needed for terminate
             }
-            8 => { // Device Failure
+            EtpPayload::DeviceFailure(_) => {
                 warn!("Processing device failure from device {}", message.device_id);
                 None // No response needed for device failure
             }
-            9 => { // BESS Status
+            EtpPayload::BESSStatus(_) => {
                 info!("Processing BESS status from device {}", message.device_id);
                 None // BESS doesn't process status messages from others
             }
-            _ => {
-                warn!("Unknown message type: {}", message.message_type);
-                None
-            }
         };
         
         // Send response if needed
         if let Some(response_message) = response {
+            session.advance(&response_message)?;
             connection.send_message(response_message).await?;
         }
         
@@ -226,6 +721,7 @@ impl Clone for BESSTCPServer {
     fn clone(&self) -> Self {
         Self {
             bess_node: self.bess_node.clone(),
+            settings: self.settings.clone(),
             listener: None, // TcpListener can't be cloned
             is_running: self.is_running.clone(),
             local_addr: self.local_addr,
@@ -236,7 +732,7 @@ impl Clone for BESSTCPServer {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::time::{timeout, Duration};
+    use tokio::time::Duration;
 
     #[tokio::test]
     async fn test_bess_tcp_server_creation() {
@@ -0,0 +1,53 @@
+use energy_trading::conformance::ConformanceSuite;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: etp-conformance <host:port> [--relay-hops N] [--no-hello] [--json]";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let Some(endpoint) = args.next().and_then(|arg| arg.parse::<SocketAddr>().ok()) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+
+    let mut suite = ConformanceSuite::new(endpoint);
+    let mut json = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--relay-hops" => match args.next().and_then(|hops| hops.parse().ok()) {
+                Some(hops) => suite.set_relay_hops(hops),
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            "--no-hello" => suite.set_hello(None),
+            "--json" => json = true,
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let report = suite.run().await;
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("Failed to encode report: {}", e);
+                return ExitCode::from(2);
+            }
+        }
+    } else {
+        println!("{}", report);
+    }
+
+    if report.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
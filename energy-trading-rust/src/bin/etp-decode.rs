@@ -0,0 +1,115 @@
+use energy_trading::codec::inspect::{parse_hex, to_hex, CaptureSplitter, InspectedFrame};
+use std::io::Read;
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: etp-decode [--hex | --raw] [--json | --pretty] [FILE]
+
+Decodes ETP frames from FILE, or stdin, and prints them.
+  --hex     one captured payload per line in hex, e.g. `tshark -T fields -e tcp.payload`
+  --raw     binary: a single frame or a length-prefixed TCP stream
+  --json    one canonical JSON object per frame
+  --pretty  indented canonical JSON
+Without --hex or --raw, input made only of hex digits is read as hex.";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    Summary,
+    Json,
+    Pretty,
+}
+
+fn main() -> ExitCode {
+    let mut hex = None;
+    let mut output = Output::Summary;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--hex" => hex = Some(true),
+            "--raw" => hex = Some(false),
+            "--json" => output = Output::Json,
+            "--pretty" => output = Output::Pretty,
+            _ if !arg.starts_with("--") && path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let mut input = Vec::new();
+    let read = match &path {
+        Some(path) => std::fs::File::open(path).and_then(|mut file| file.read_to_end(&mut input)),
+        None => std::io::stdin().read_to_end(&mut input),
+    };
+    if let Err(e) = read {
+        eprintln!("Failed to read input: {}", e);
+        return ExitCode::from(2);
+    }
+
+    // One payload per hex line, or the whole binary input as one payload
+    let payloads = match hex.unwrap_or_else(|| looks_like_hex(&input)) {
+        true => {
+            let text = String::from_utf8_lossy(&input);
+            let lines = text.lines().filter(|line| !line.trim().is_empty());
+            match lines.map(parse_hex).collect::<Result<Vec<_>, _>>() {
+                Ok(payloads) => payloads,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return ExitCode::from(2);
+                }
+            }
+        }
+        false => vec![input],
+    };
+
+    let mut splitter = CaptureSplitter::new();
+    let mut failed = false;
+    let mut index = 0;
+    for payload in payloads {
+        let frames = match splitter.push(&payload) {
+            Ok(frames) => frames,
+            Err(e) => {
+                eprintln!("Unreadable stream: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        for frame in frames {
+            index += 1;
+            match InspectedFrame::decode(&frame).and_then(|inspected| render(&inspected, output)) {
+                Ok(text) => println!("{}", text),
+                Err(e) => {
+                    eprintln!("frame {}: {} ({} bytes: {})", index, e, frame.len(), to_hex(&frame));
+                    failed = true;
+                }
+            }
+        }
+    }
+    if splitter.pending() > 0 {
+        eprintln!("{} trailing bytes of an incomplete frame", splitter.pending());
+        failed = true;
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Format a decoded frame for output
+fn render(frame: &InspectedFrame, output: Output) -> energy_trading::Result<String> {
+    Ok(match output {
+        Output::Summary => frame.to_string(),
+        Output::Json => serde_json::to_string(&frame.to_json_value()?)?,
+        Output::Pretty => serde_json::to_string_pretty(&frame.to_json_value()?)?,
+    })
+}
+
+/// Check whether the input holds only hex digits, separators and whitespace
+fn looks_like_hex(input: &[u8]) -> bool {
+    input.iter().any(u8::is_ascii_hexdigit)
+        && input
+            .split(|byte| *byte == b'\n')
+            .map(|line| line.strip_prefix(b"0x").unwrap_or(line))
+            .all(|line| line.iter().all(|byte| byte.is_ascii_hexdigit() || byte.is_ascii_whitespace() || *byte == b':'))
+}
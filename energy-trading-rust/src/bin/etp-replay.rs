@@ -0,0 +1,114 @@
+use energy_trading::network::capture::Capture;
+use energy_trading::network::replay::{CaptureReplayer, ReplaySide, ReplayTiming};
+use energy_trading::network::unicast_connection::UnicastConnection;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+
+const USAGE: &str = "Usage: etp-replay <capture> (--connect <host:port> | --listen <host:port>) \
+[--play peer|recorder] [--fast] [--window <ms>] [--json]
+
+  --connect  drive an endpoint that accepts connections, e.g. a BESSTCPServer
+  --listen   wait for one connection, e.g. from the aggregator under test
+  --play     peer (default): send what the recording endpoint received
+             recorder: send what the recording endpoint sent
+  --fast     do not wait for the recorded offsets between frames
+  --window   how long to wait for each expected frame (default 2000)";
+
+enum Target {
+    Connect(SocketAddr),
+    Listen(SocketAddr),
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args.next().filter(|arg| !arg.starts_with("--")) else {
+        return usage();
+    };
+
+    let mut target = None;
+    let mut side = ReplaySide::Peer;
+    let mut timing = ReplayTiming::Original;
+    let mut window = None;
+    let mut json = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--connect" => target = args.next().and_then(|addr| addr.parse().ok()).map(Target::Connect),
+            "--listen" => target = args.next().and_then(|addr| addr.parse().ok()).map(Target::Listen),
+            "--play" => {
+                side = match args.next().as_deref() {
+                    Some("peer") => ReplaySide::Peer,
+                    Some("recorder") => ReplaySide::Recorder,
+                    _ => return usage(),
+                }
+            }
+            "--fast" => timing = ReplayTiming::Fast,
+            "--window" => match args.next().and_then(|ms| ms.parse().ok()) {
+                Some(ms) => window = Some(Duration::from_millis(ms)),
+                None => return usage(),
+            },
+            "--json" => json = true,
+            _ => return usage(),
+        }
+    }
+    let Some(target) = target else {
+        return usage();
+    };
+
+    let capture = match Capture::open(&path) {
+        Ok(capture) => capture,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path, e);
+            return ExitCode::from(2);
+        }
+    };
+    let stream = match target {
+        Target::Connect(addr) => TcpStream::connect(addr).await,
+        Target::Listen(addr) => match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                eprintln!("Waiting for a connection on {}", addr);
+                listener.accept().await.map(|(stream, _)| stream)
+            }
+            Err(e) => Err(e),
+        },
+    };
+    let mut connection = match stream {
+        Ok(stream) => UnicastConnection::new(stream),
+        Err(e) => {
+            eprintln!("Failed to reach the target: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut replayer = CaptureReplayer::new(capture);
+    replayer.set_side(side);
+    replayer.set_timing(timing);
+    if let Some(window) = window {
+        replayer.set_response_window(window);
+    }
+    let report = replayer.run(&mut connection).await;
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("Failed to encode report: {}", e);
+                return ExitCode::from(2);
+            }
+        }
+    } else {
+        println!("{}", report);
+    }
+
+    if report.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{}", USAGE);
+    ExitCode::from(2)
+}
@@ -1,5 +1,5 @@
 use energy_trading::network::websocket_gateway::{WebSocketGateway, SystemEvent};
-use energy_trading::bess_node::EnergyStatus;
+use energy_trading::TerminationCode;
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
@@ -54,7 +54,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             // Broadcast auction started event
             let auction_event = SystemEvent::AuctionStarted {
-                auction_id: auction_id as u64,
+                auction_id,
                 total_energy,
                 reserve_price,
             };
@@ -128,7 +128,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 let target_bess = 101 + (i % 3); // Distribute bids across BESS nodes
 
                 let bid_event = SystemEvent::BidPlaced {
-                    auction_id: auction_id as u64,
+                    auction_id,
                     aggregator_id: i as u64,
                     bess_id: target_bess as u64,
                     bid_price,
@@ -154,9 +154,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let new_energy_percentage = (bess_energy_levels[bess_index] / 15.0) * 100.0;
 
             let accept_event = SystemEvent::BidAccepted {
-                auction_id: auction_id as u64,
+                auction_id,
                 aggregator_id: 3,
-                bess_id: winning_bess as u64,
+                bess_id: winning_bess,
                 final_price,
                 energy_amount,
             };
@@ -164,11 +164,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             // Send detailed auction completion event
             let total_value = final_price * energy_amount;
-            let auction_duration_ms = 15000 + (auction_id as u64 * 2000) % 10000; // 15-25 seconds
+            let auction_duration_ms = 15000 + (auction_id * 2000) % 10000; // 15-25 seconds
             let completed_event = SystemEvent::AuctionCompleted {
-                auction_id: auction_id as u64,
+                auction_id,
                 winner_aggregator_id: 3,
-                seller_bess_id: winning_bess as u64,
+                seller_bess_id: winning_bess,
                 energy_sold: energy_amount,
                 final_price,
                 total_value,
@@ -184,7 +184,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             // Check if BESS is depleted and send event
             if bess_energy_levels[bess_index] <= 0.1 {
                 let depleted_event = SystemEvent::EnergyDepleted {
-                    bess_id: winning_bess as u64,
+                    bess_id: winning_bess,
                     final_energy: bess_energy_levels[bess_index],
                     energy_percentage: new_energy_percentage,
                 };
@@ -205,16 +205,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     let available_energy = bess_energy_levels[bess_index];
                     
                     // Realistic rejection reasons based on actual energy availability
-                    let reason = if bid_price < 8.0 {
-                        "Bid price below reserve price"
-                    } else if energy_requested > available_energy {
-                        "BESS node capacity exceeded"
+                    let (reason, code) = if bid_price < 8.0 {
+                        ("Bid price below reserve price", TerminationCode::PriceBelowReserve)
                     } else if energy_requested > (available_energy - 0.5) {
-                        "BESS node capacity exceeded" // Too close to available energy
+                        ("BESS node capacity exceeded", TerminationCode::InsufficientEnergy) // Exceeds or is too close to available energy
                     } else if energy_requested > 10.0 {
-                        "Insufficient energy available"
+                        ("Insufficient energy available", TerminationCode::InsufficientEnergy)
                     } else {
-                        "Bid not competitive enough"
+                        ("Bid not competitive enough", TerminationCode::NotCompetitive)
                     };
                     
                     println!("❌ Rejecting Aggregator {}: {} (bid: {:.1}¢/kWh, requested: {:.1} kWh, available: {:.1} kWh)", 
@@ -224,6 +222,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         aggregator_id: i as u64,
                         bess_id: target_bess as u64,
                         reason: reason.to_string(),
+                        code,
                     };
                     event_gateway.broadcast_event(reject_event).await.unwrap();
                     println!("❌ Aggregator {} bid rejected by BESS Node {}: {}", i, target_bess, reason);
@@ -272,7 +271,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     _ => "Intelligent",
                 };
                 let success_rate = 65.0 + (device_id as f64 * 5.0) % 25.0; // 65-90%
-                let total_bids = (auction_id * 3) as u64; // 3 bids per auction
+                let total_bids = auction_id * 3 ; // 3 bids per auction
                 let successful_bids = (total_bids as f64 * (success_rate / 100.0)) as u64; // Calculate successful bids
                 let total_energy_bought = successful_bids as f64 * 4.0; // 4 kWh per successful bid
                 let average_bid_price = 5.0 + (device_id as f64 * 2.0) % 20.0; // 5-25 c/kWh
@@ -292,8 +291,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             // Broadcast system metrics
             let metrics_event = SystemEvent::SystemMetrics {
-                total_auctions: auction_id as u64,
-                total_bids: (auction_id * 3) as u64, // 3 bids per auction
+                total_auctions: auction_id,
+                total_bids: (auction_id * 3), // 3 bids per auction
                 avg_price_improvement_percent: 200.0 + (auction_id as f64 * 5.0) % 100.0, // 200-300%
                 active_bess_nodes: 3,
                 active_aggregators: 5,
@@ -0,0 +1,331 @@
+use crate::codec::wire::{
+    ETP_PROTOCOL_VERSION, FIELD_WIDTHS, SUPPORTED_PROTOCOL_VERSIONS, WIRE_BODY_SIZE, WIRE_CHECKSUM_SIZE,
+};
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::error::{ETPError, Result, SerializationError};
+use bytes::{Buf, BufMut, BytesMut};
+use std::collections::HashMap;
+
+/// Magic bytes at the start of every batch frame ("EB")
+pub const BATCH_MAGIC: [u8; 2] = *b"EB";
+
+/// Size of the batch header (magic, version, flags, message count)
+pub const BATCH_HEADER_SIZE: usize = 6;
+
+/// Most messages one batch frame can carry
+pub const MAX_BATCH_MESSAGES: usize = u16::MAX as usize;
+
+/// Header flag: BESSStatus entries may be delta-encoded
+const FLAG_DELTA_STATUS: u8 = 1 << 0;
+
+/// Entry holding a complete message
+const ENTRY_FULL: u8 = 0;
+
+/// Entry holding a BESSStatus as changes to the device's previous status
+const ENTRY_STATUS_DELTA: u8 = 1;
+
+/// Several ETP messages sent as one frame
+///
+/// Layout, all integers little-endian:
+///
+/// | size | field                                    |
+/// |------|------------------------------------------|
+/// | 2    | magic `"EB"`                             |
+/// | 1    | protocol version, shared by all messages |
+/// | 1    | flags (bit 0: delta-encoded statuses)    |
+/// | 2    | message count (u16)                      |
+/// | ...  | entries                                  |
+/// | 4    | CRC-32 (IEEE) of everything before it    |
+///
+/// A full entry is the byte `0` followed by message_type and the 83-byte
+/// body of the wire format. With delta encoding enabled, a BESSStatus
+/// following another BESSStatus from the same device in the batch is the
+/// byte `1`, device_id (u64), message_id (u64), a u16 mask of changed fields
+/// (ttl through discharge_rate, in wire order) and the changed values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageBatch {
+    pub version: u8,
+    pub delta_status: bool, // Delta-encode consecutive BESSStatus samples per device
+    pub messages: Vec<ETPMessage>,
+}
+
+impl MessageBatch {
+    /// Create a batch using the current protocol version and full entries
+    pub fn new(messages: Vec<ETPMessage>) -> Self {
+        Self {
+            version: ETP_PROTOCOL_VERSION,
+            delta_status: false,
+            messages,
+        }
+    }
+
+    /// Enable or disable delta encoding of BESSStatus samples
+    pub fn set_delta_status(&mut self, enabled: bool) {
+        self.delta_status = enabled;
+    }
+
+    /// Add a message to the batch
+    pub fn push(&mut self, message: ETPMessage) {
+        self.messages.push(message);
+    }
+
+    /// Number of messages in the batch
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Check whether the batch holds no messages
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Check whether a frame is a batch rather than a single message
+    pub fn is_batch_frame(frame: &[u8]) -> bool {
+        frame.len() >= BATCH_MAGIC.len() && frame[..BATCH_MAGIC.len()] == BATCH_MAGIC
+    }
+
+    /// Encode the batch frame
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        if self.messages.len() > MAX_BATCH_MESSAGES {
+            return Err(ETPError::Validation(format!(
+                "Batch can carry at most {} messages, got {}",
+                MAX_BATCH_MESSAGES,
+                self.messages.len()
+            )));
+        }
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&self.version) {
+            return Err(ETPError::Serialization(SerializationError::UnsupportedVersion(self.version)));
+        }
+
+        let mut buf = BytesMut::with_capacity(
+            BATCH_HEADER_SIZE + self.messages.len() * (2 + WIRE_BODY_SIZE) + WIRE_CHECKSUM_SIZE,
+        );
+        buf.put_slice(&BATCH_MAGIC);
+        buf.put_u8(self.version);
+        buf.put_u8(if self.delta_status { FLAG_DELTA_STATUS } else { 0 });
+        buf.put_u16_le(self.messages.len() as u16);
+
+        let mut last_status: HashMap<u64, &ETPMessage> = HashMap::new();
+        for message in &self.messages {
+            MessageType::try_from(message.message_type).map_err(ETPError::Serialization)?;
+
+            let base = match self.delta_status && message.message_type == MessageType::BESSStatus as u8 {
+                true => last_status.insert(message.device_id, message),
+                false => None,
+            };
+            match base {
+                Some(base) => put_status_delta(base, message, &mut buf),
+                None => {
+                    buf.put_u8(ENTRY_FULL);
+                    message.put_body(&mut buf);
+                }
+            }
+        }
+
+        let checksum = crc32fast::hash(&buf);
+        buf.put_u32_le(checksum);
+        Ok(buf.to_vec())
+    }
+
+    /// Decode a batch frame
+    pub fn decode(frame: &[u8]) -> Result<Self> {
+        if !Self::is_batch_frame(frame) {
+            let magic = [frame.first().copied().unwrap_or(0), frame.get(1).copied().unwrap_or(0)];
+            return Err(ETPError::Serialization(SerializationError::InvalidMagic(magic)));
+        }
+        if frame.len() < BATCH_HEADER_SIZE + WIRE_CHECKSUM_SIZE {
+            return Err(ETPError::Serialization(SerializationError::InvalidMessageSize {
+                expected: BATCH_HEADER_SIZE + WIRE_CHECKSUM_SIZE,
+                actual: frame.len(),
+            }));
+        }
+
+        let (content, mut trailer) = frame.split_at(frame.len() - WIRE_CHECKSUM_SIZE);
+        let expected = trailer.get_u32_le();
+        let actual = crc32fast::hash(content);
+        if expected != actual {
+            return Err(ETPError::Serialization(SerializationError::ChecksumMismatch {
+                expected,
+                actual,
+            }));
+        }
+
+        let mut body = &content[BATCH_MAGIC.len()..];
+        let version = body.get_u8();
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+            return Err(ETPError::Serialization(SerializationError::UnsupportedVersion(version)));
+        }
+        let delta_status = body.get_u8() & FLAG_DELTA_STATUS != 0;
+        let count = body.get_u16_le() as usize;
+
+        let mut messages: Vec<ETPMessage> = Vec::with_capacity(count.min(body.len() / 2));
+        let mut last_status: HashMap<u64, usize> = HashMap::new(); // device_id -> index in messages
+        for index in 0..count {
+            let kind = take(&mut body, 1, index)?[0];
+            let message = match kind {
+                ENTRY_FULL => ETPMessage::get_body(&mut take(&mut body, 1 + WIRE_BODY_SIZE, index)?)?,
+                ENTRY_STATUS_DELTA if delta_status => {
+                    let mut ids = take(&mut body, 16, index)?;
+                    let device_id = ids.get_u64_le();
+                    let base = last_status.get(&device_id).map(|&i| &messages[i]).ok_or(
+                        SerializationError::InvalidBatchEntry { index, reason: "status delta without a base" },
+                    )?;
+                    get_status_delta(base, ids.get_u64_le(), &mut body, index)?
+                }
+                _ => {
+                    return Err(ETPError::Serialization(SerializationError::InvalidBatchEntry {
+                        index,
+                        reason: "unknown entry kind",
+                    }))
+                }
+            };
+
+            if message.message_type == MessageType::BESSStatus as u8 {
+                last_status.insert(message.device_id, messages.len());
+            }
+            messages.push(message);
+        }
+
+        if !body.is_empty() {
+            return Err(ETPError::Serialization(SerializationError::InvalidBatchEntry {
+                index: count,
+                reason: "trailing bytes after the last entry",
+            }));
+        }
+
+        Ok(Self {
+            version,
+            delta_status,
+            messages,
+        })
+    }
+}
+
+impl IntoIterator for MessageBatch {
+    type Item = ETPMessage;
+    type IntoIter = std::vec::IntoIter<ETPMessage>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.messages.into_iter()
+    }
+}
+
+/// Write `message` as the changes from `base`
+fn put_status_delta(base: &ETPMessage, message: &ETPMessage, buf: &mut BytesMut) {
+    let (old, new) = (base.field_values(), message.field_values());
+    let mask = (0..new.len()).filter(|&i| old[i] != new[i]).fold(0u16, |mask, i| mask | 1 << i);
+
+    buf.put_u8(ENTRY_STATUS_DELTA);
+    buf.put_u64_le(message.device_id);
+    buf.put_u64_le(message.message_id);
+    buf.put_u16_le(mask);
+    for (i, width) in FIELD_WIDTHS.iter().enumerate() {
+        if mask & 1 << i != 0 {
+            buf.put_slice(&new[i].to_le_bytes()[..*width]);
+        }
+    }
+}
+
+/// Read a status delta and apply it to `base`
+fn get_status_delta(base: &ETPMessage, message_id: u64, body: &mut &[u8], index: usize) -> Result<ETPMessage> {
+    let mask = take(body, 2, index)?.get_u16_le();
+    if mask >> FIELD_WIDTHS.len() != 0 {
+        return Err(ETPError::Serialization(SerializationError::InvalidBatchEntry {
+            index,
+            reason: "unknown field in status delta",
+        }));
+    }
+
+    let mut fields = base.field_values();
+    for (i, width) in FIELD_WIDTHS.iter().enumerate() {
+        if mask & 1 << i != 0 {
+            let mut value = [0u8; 8];
+            value[..*width].copy_from_slice(take(body, *width, index)?);
+            fields[i] = u64::from_le_bytes(value);
+        }
+    }
+
+    let mut message = base.clone();
+    message.message_id = message_id;
+    message.set_field_values(&fields);
+    Ok(message)
+}
+
+/// Split `len` bytes off the front of `body`
+fn take<'a>(body: &mut &'a [u8], len: usize, index: usize) -> Result<&'a [u8]> {
+    if body.len() < len {
+        return Err(ETPError::Serialization(SerializationError::InvalidBatchEntry {
+            index,
+            reason: "truncated entry",
+        }));
+    }
+    let (taken, rest) = body.split_at(len);
+    *body = rest;
+    Ok(taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::wire::WIRE_MESSAGE_SIZE;
+    use crate::termination::TerminationCode;
+
+    fn status_samples(device_id: u64, count: u64) -> Vec<ETPMessage> {
+        (0..count)
+            .map(|i| ETPMessage::new_bess_status(1000 + i, device_id, 50.0 - i as f64 * 0.1, 1, 12.6, 2.5))
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_roundtrip() {
+        let mut messages = status_samples(100, 3);
+        messages.push(ETPMessage::new_bid(7, 15.5, 10.0));
+        messages.push(ETPMessage::new_query(8, 100));
+        let batch = MessageBatch::new(messages);
+
+        let encoded = batch.encode().unwrap();
+        assert!(MessageBatch::is_batch_frame(&encoded));
+        assert_eq!(MessageBatch::decode(&encoded).unwrap(), batch);
+    }
+
+    #[test]
+    fn test_delta_status_roundtrip_is_smaller() {
+        let mut messages = status_samples(100, 10);
+        messages.extend(status_samples(101, 10));
+        messages.push(ETPMessage::new_device_failure(9, 100, TerminationCode::Offline));
+        let mut batch = MessageBatch::new(messages);
+        let full = batch.encode().unwrap();
+
+        batch.set_delta_status(true);
+        let delta = batch.encode().unwrap();
+        assert_eq!(MessageBatch::decode(&delta).unwrap(), batch);
+
+        // Each repeated sample changes only message_id and remaining energy
+        assert!(delta.len() < full.len() / 2);
+        assert!(full.len() < batch.len() * (4 + WIRE_MESSAGE_SIZE));
+    }
+
+    #[test]
+    fn test_corrupted_batch_rejected() {
+        let mut encoded = MessageBatch::new(status_samples(100, 2)).encode().unwrap();
+        encoded[BATCH_HEADER_SIZE + 5] ^= 0xff;
+        assert!(matches!(
+            MessageBatch::decode(&encoded),
+            Err(ETPError::Serialization(SerializationError::ChecksumMismatch { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_truncated_batch_rejected() {
+        let batch = MessageBatch::new(status_samples(100, 2));
+        let mut encoded = batch.encode().unwrap();
+        encoded.truncate(encoded.len() - WIRE_CHECKSUM_SIZE - 10);
+        let checksum = crc32fast::hash(&encoded);
+        encoded.extend_from_slice(&checksum.to_le_bytes());
+
+        match MessageBatch::decode(&encoded) {
+            Err(ETPError::Serialization(SerializationError::InvalidBatchEntry { index, .. })) => assert_eq!(index, 1),
+            other => panic!("Expected InvalidBatchEntry, got {:?}", other),
+        }
+    }
+}
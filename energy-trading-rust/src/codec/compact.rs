@@ -0,0 +1,251 @@
+use crate::codec::wire::{FIELD_WIDTHS, SUPPORTED_PROTOCOL_VERSIONS, WIRE_CHECKSUM_SIZE};
+use crate::etp_message::{ETPMessage, MessageType, DEFAULT_TTL};
+use crate::error::{ETPError, Result, SerializationError};
+use bytes::{Buf, BufMut, BytesMut};
+use std::convert::TryFrom;
+
+/// Magic bytes at the start of every compact ETP message ("EC")
+pub const COMPACT_MAGIC: [u8; 2] = *b"EC";
+
+/// Largest possible compact message
+pub const COMPACT_MAX_SIZE: usize = 4 + 2 * 10 + 4 + 8 * 8 + 3 + WIRE_CHECKSUM_SIZE;
+
+/// Field values a compact message leaves out, in `FIELD_WIDTHS` order
+const DEFAULT_FIELD_VALUES: [u64; 11] = [DEFAULT_TTL as u64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+/// Mask bit marking a float field stored as f32, added to the field's presence bit
+const NARROW_SHIFT: u32 = 11;
+
+/// Compact ETP encoding for constrained links
+///
+/// | size | field                                           |
+/// |------|-------------------------------------------------|
+/// | 2    | magic `"EC"`                                    |
+/// | 1    | protocol version                                |
+/// | 1    | message_type (0-9)                              |
+/// | 1-10 | message_id (LEB128 varint)                      |
+/// | 1-10 | device_id (LEB128 varint)                       |
+/// | 1-4  | field mask (LEB128 varint)                      |
+/// | ...  | fields present in the mask, in wire order       |
+/// | 4    | CRC-32 (IEEE) of everything before it           |
+///
+/// Bits 0-10 of the mask mark the fields ttl through discharge_rate that
+/// differ from their default (ttl 5, everything else 0); omitted fields take
+/// the default. Bits 11-21 mark floats stored as f32 because that loses no
+/// precision, e.g. 15.5. Other floats take 8 bytes and u8 fields 1 byte.
+impl ETPMessage {
+    /// Check whether a frame holds a compact message
+    pub fn is_compact_frame(frame: &[u8]) -> bool {
+        frame.len() >= COMPACT_MAGIC.len() && frame[..COMPACT_MAGIC.len()] == COMPACT_MAGIC
+    }
+
+    /// Encode the message into `buf` in the compact format
+    pub fn encode_compact(&self, version: u8, buf: &mut BytesMut) -> Result<()> {
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+            return Err(ETPError::Serialization(SerializationError::UnsupportedVersion(version)));
+        }
+        MessageType::try_from(self.message_type).map_err(ETPError::Serialization)?;
+
+        let values = self.field_values();
+        let mut mask = 0u64;
+        for (i, width) in FIELD_WIDTHS.iter().enumerate() {
+            if values[i] != DEFAULT_FIELD_VALUES[i] {
+                mask |= 1 << i;
+                if *width == 8 && is_exact_f32(values[i]) {
+                    mask |= 1 << (NARROW_SHIFT + i as u32);
+                }
+            }
+        }
+
+        let start = buf.len();
+        buf.reserve(COMPACT_MAX_SIZE);
+        buf.put_slice(&COMPACT_MAGIC);
+        buf.put_u8(version);
+        buf.put_u8(self.message_type);
+        put_varint(buf, self.message_id);
+        put_varint(buf, self.device_id);
+        put_varint(buf, mask);
+        for (i, width) in FIELD_WIDTHS.iter().enumerate() {
+            if mask & 1 << i == 0 {
+                continue;
+            }
+            match (*width, mask & 1 << (NARROW_SHIFT + i as u32) != 0) {
+                (8, true) => buf.put_f32_le(f64::from_bits(values[i]) as f32),
+                (8, false) => buf.put_u64_le(values[i]),
+                _ => buf.put_u8(values[i] as u8),
+            }
+        }
+
+        let checksum = crc32fast::hash(&buf[start..]);
+        buf.put_u32_le(checksum);
+        Ok(())
+    }
+
+    /// Decode one compact message
+    pub fn decode_compact(data: &[u8]) -> Result<Self> {
+        Self::decode_compact_versioned(data).map(|(message, _version)| message)
+    }
+
+    /// Decode one compact message and return the protocol version it was encoded with
+    pub fn decode_compact_versioned(data: &[u8]) -> Result<(Self, u8)> {
+        if !Self::is_compact_frame(data) {
+            let magic = [data.first().copied().unwrap_or(0), data.get(1).copied().unwrap_or(0)];
+            return Err(ETPError::Serialization(SerializationError::InvalidMagic(magic)));
+        }
+        if data.len() < COMPACT_MAGIC.len() + 2 + WIRE_CHECKSUM_SIZE {
+            return Err(ETPError::Serialization(SerializationError::InvalidMessageSize {
+                expected: COMPACT_MAGIC.len() + 2 + WIRE_CHECKSUM_SIZE,
+                actual: data.len(),
+            }));
+        }
+
+        let (content, mut trailer) = data.split_at(data.len() - WIRE_CHECKSUM_SIZE);
+        let expected = trailer.get_u32_le();
+        let actual = crc32fast::hash(content);
+        if expected != actual {
+            return Err(ETPError::Serialization(SerializationError::ChecksumMismatch {
+                expected,
+                actual,
+            }));
+        }
+
+        let mut body = &content[COMPACT_MAGIC.len()..];
+        let version = body.get_u8();
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+            return Err(ETPError::Serialization(SerializationError::UnsupportedVersion(version)));
+        }
+        let message_type = body.get_u8();
+        MessageType::try_from(message_type).map_err(ETPError::Serialization)?;
+
+        let message_id = get_varint(&mut body, "message_id")?;
+        let device_id = get_varint(&mut body, "device_id")?;
+        let mask = get_varint(&mut body, "field mask")?;
+        if mask >> (NARROW_SHIFT as usize + FIELD_WIDTHS.len()) != 0 {
+            return Err(compact_error("field mask", "unknown field bits"));
+        }
+
+        let mut values = DEFAULT_FIELD_VALUES;
+        for (i, width) in FIELD_WIDTHS.iter().enumerate() {
+            let narrow = mask & 1 << (NARROW_SHIFT + i as u32) != 0;
+            if mask & 1 << i == 0 {
+                if narrow {
+                    return Err(compact_error("field mask", "narrow bit set for an omitted field"));
+                }
+                continue;
+            }
+            let size = if narrow { 4 } else { *width };
+            if body.len() < size {
+                return Err(compact_error("fields", "truncated"));
+            }
+            values[i] = match (*width, narrow) {
+                (8, true) => (body.get_f32_le() as f64).to_bits(),
+                (8, false) => body.get_u64_le(),
+                (_, false) => body.get_u8() as u64,
+                (_, true) => return Err(compact_error("field mask", "narrow bit set for an integer field")),
+            };
+        }
+        if !body.is_empty() {
+            return Err(compact_error("fields", "trailing bytes"));
+        }
+
+        let mut message = ETPMessage::new_with_type(message_type, message_id, 0.0, 0.0);
+        message.device_id = device_id;
+        message.set_field_values(&values);
+        Ok((message, version))
+    }
+}
+
+/// Check whether a float, given as its bits, survives a round trip through f32
+fn is_exact_f32(bits: u64) -> bool {
+    ((f64::from_bits(bits) as f32) as f64).to_bits() == bits
+}
+
+/// Write an unsigned LEB128 varint
+fn put_varint(buf: &mut BytesMut, mut value: u64) {
+    while value >= 0x80 {
+        buf.put_u8(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.put_u8(value as u8);
+}
+
+/// Read an unsigned LEB128 varint
+fn get_varint(body: &mut &[u8], field: &'static str) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        if body.is_empty() {
+            return Err(compact_error(field, "truncated"));
+        }
+        let byte = body.get_u8();
+        if shift == 63 && byte > 1 {
+            break;
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(compact_error(field, "varint overflows u64"))
+}
+
+fn compact_error(field: &'static str, reason: &'static str) -> ETPError {
+    ETPError::Serialization(SerializationError::InvalidCompactField { field, reason })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::wire::WIRE_MESSAGE_SIZE;
+
+    fn roundtrip(message: &ETPMessage) -> usize {
+        let mut buf = BytesMut::new();
+        message.encode_compact(1, &mut buf).unwrap();
+        assert!(ETPMessage::is_compact_frame(&buf));
+        let decoded = ETPMessage::decode_compact(&buf).unwrap();
+        assert_eq!(decoded.field_values(), message.field_values());
+        assert_eq!((decoded.message_type, decoded.message_id, decoded.device_id),
+                   (message.message_type, message.message_id, message.device_id));
+        buf.len()
+    }
+
+    #[test]
+    fn test_compact_roundtrip_all_types() {
+        for message_type in 0..=9 {
+            let mut message = ETPMessage::new_with_type(message_type, u64::MAX, 15.5, 12.6);
+            message.device_id = 300;
+            message.discharge_rate = -2.5;
+            assert!(roundtrip(&message) < WIRE_MESSAGE_SIZE);
+        }
+    }
+
+    #[test]
+    fn test_default_fields_omitted() {
+        // magic, version, type, 1-byte ids, empty mask, crc
+        assert_eq!(roundtrip(&ETPMessage::new_query(1, 100)), 11);
+
+        // 15.5 and 10.0 fit in f32; 12.6 does not
+        let bid = ETPMessage::new_bid(1, 15.5, 10.0);
+        assert_eq!(roundtrip(&bid), 11 + 2 + 4 + 4);
+        let status = ETPMessage::new_bess_status(1, 100, 18.5, 1, 12.6, 2.5);
+        assert_eq!(roundtrip(&status), 11 + 3 + 4 + 1 + 8 + 4);
+    }
+
+    #[test]
+    fn test_special_floats_preserved() {
+        let mut message = ETPMessage::new_bid(1, f64::NAN, -0.0);
+        message.battery_voltage = f64::INFINITY;
+        message.discharge_rate = f64::MIN_POSITIVE;
+        roundtrip(&message);
+    }
+
+    #[test]
+    fn test_corrupted_compact_rejected() {
+        let mut buf = BytesMut::new();
+        ETPMessage::new_bid(1, 15.5, 10.0).encode_compact(1, &mut buf).unwrap();
+        buf[6] ^= 0x01;
+        assert!(matches!(
+            ETPMessage::decode_compact(&buf),
+            Err(ETPError::Serialization(SerializationError::ChecksumMismatch { .. }))
+        ));
+    }
+}
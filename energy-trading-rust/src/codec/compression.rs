@@ -0,0 +1,86 @@
+use crate::error::{ETPError, Result, SerializationError};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+/// Magic bytes at the start of every compressed frame ("EZ")
+pub const COMPRESSED_MAGIC: [u8; 2] = *b"EZ";
+
+/// Check whether a frame is compressed
+pub fn is_compressed_frame(frame: &[u8]) -> bool {
+    frame.len() >= COMPRESSED_MAGIC.len() && frame[..COMPRESSED_MAGIC.len()] == COMPRESSED_MAGIC
+}
+
+/// Wrap any ETP frame as the magic `"EZ"` followed by its raw DEFLATE stream
+pub fn compress_frame(frame: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(COMPRESSED_MAGIC.to_vec(), Compression::default());
+    encoder.write_all(frame)?;
+    Ok(encoder.finish()?)
+}
+
+/// Unwrap a compressed frame, refusing output beyond `max_frame_size`
+///
+/// Pass the connection's frame size limit, so compression cannot smuggle in
+/// a frame larger than the connection accepts uncompressed.
+pub fn decompress_frame(frame: &[u8], max_frame_size: usize) -> Result<Vec<u8>> {
+    if !is_compressed_frame(frame) {
+        let magic = [frame.first().copied().unwrap_or(0), frame.get(1).copied().unwrap_or(0)];
+        return Err(ETPError::Serialization(SerializationError::InvalidMagic(magic)));
+    }
+
+    let decoder = DeflateDecoder::new(&frame[COMPRESSED_MAGIC.len()..]);
+    let mut decompressed = Vec::new();
+    decoder
+        .take(max_frame_size as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| ETPError::Serialization(SerializationError::Decompression(e.to_string())))?;
+
+    if decompressed.len() > max_frame_size {
+        return Err(ETPError::Serialization(SerializationError::Decompression(format!(
+            "frame expands beyond {} bytes",
+            max_frame_size
+        ))));
+    }
+    if is_compressed_frame(&decompressed) {
+        return Err(ETPError::Serialization(SerializationError::Decompression(
+            "nested compressed frame".to_string(),
+        )));
+    }
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::batch::MessageBatch;
+    use crate::codec::framing::MAX_FRAME_SIZE;
+    use crate::etp_message::ETPMessage;
+
+    #[test]
+    fn test_compression_roundtrip_shrinks_batches() {
+        let batch = MessageBatch::new(
+            (0..50).map(|i| ETPMessage::new_bess_status(1000 + i, 100, 18.5, 1, 12.6, 2.5)).collect(),
+        );
+        let frame = batch.encode().unwrap();
+        let compressed = compress_frame(&frame).unwrap();
+
+        assert!(is_compressed_frame(&compressed));
+        assert!(compressed.len() < frame.len() / 4);
+        assert_eq!(decompress_frame(&compressed, MAX_FRAME_SIZE).unwrap(), frame);
+    }
+
+    #[test]
+    fn test_decompression_bomb_rejected() {
+        let compressed = compress_frame(&vec![0u8; MAX_FRAME_SIZE + 1]).unwrap();
+        assert!(matches!(
+            decompress_frame(&compressed, MAX_FRAME_SIZE),
+            Err(ETPError::Serialization(SerializationError::Decompression(_)))
+        ));
+
+        // A smaller limit is enforced just the same
+        let compressed = compress_frame(&[0u8; 1025]).unwrap();
+        assert_eq!(decompress_frame(&compressed, 1025).unwrap().len(), 1025);
+        assert!(decompress_frame(&compressed, 1024).is_err());
+    }
+}
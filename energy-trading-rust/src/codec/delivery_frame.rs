@@ -0,0 +1,249 @@
+use crate::codec::wire::WIRE_CHECKSUM_SIZE;
+use crate::delivery::{DeliveryWindow, ScheduledDelivery};
+use crate::error::{ETPError, Result, SerializationError};
+use bytes::{Buf, BufMut, BytesMut};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Magic bytes at the start of every delivery frame ("ED")
+pub const DELIVERY_MAGIC: [u8; 2] = *b"ED";
+
+/// Size of the delivery frame header (magic, kind, message_id, device_id, entry count)
+pub const DELIVERY_HEADER_SIZE: usize = 21;
+
+/// Size of a delivery entry before its profile
+const ENTRY_FIXED_SIZE: usize = 38;
+
+/// What a delivery frame carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeliveryFrameKind {
+    Window = 0,          // Delivery window of the bid with the same message_id
+    ScheduleRequest = 1, // Asks a BESS for its schedule
+    Schedule = 2,        // Answers the schedule request with the same message_id
+}
+
+impl TryFrom<u8> for DeliveryFrameKind {
+    type Error = SerializationError;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(DeliveryFrameKind::Window),
+            1 => Ok(DeliveryFrameKind::ScheduleRequest),
+            2 => Ok(DeliveryFrameKind::Schedule),
+            _ => Err(SerializationError::InvalidDeliveryFrame("unknown frame kind")),
+        }
+    }
+}
+
+/// Delivery windows and schedules, sent alongside ETP messages
+///
+/// Layout, all integers little-endian:
+///
+/// | size | field                                  |
+/// |------|----------------------------------------|
+/// | 2    | magic `"ED"`                           |
+/// | 1    | kind                                   |
+/// | 8    | message_id                             |
+/// | 8    | device_id                              |
+/// | 2    | entry count (u16)                      |
+/// | ...  | entries                                |
+/// | 4    | CRC-32 (IEEE) of everything before it  |
+///
+/// An entry is trade_id (u64), aggregator_id (u64), the window start in
+/// Unix milliseconds (u64), the step interval in milliseconds (u32), the
+/// energy delivered so far (f64), the step count (u16) and a power (f64 kW)
+/// per step.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeliveryFrame {
+    pub kind: DeliveryFrameKind,
+    pub message_id: u64,
+    pub device_id: u64,
+    pub deliveries: Vec<ScheduledDelivery>,
+}
+
+impl DeliveryFrame {
+    /// Attach a delivery window to the bid `bid_id`, sent before the bid
+    pub fn window(bid_id: u64, aggregator_id: u64, window: DeliveryWindow) -> Self {
+        Self {
+            kind: DeliveryFrameKind::Window,
+            message_id: bid_id,
+            device_id: aggregator_id,
+            deliveries: vec![ScheduledDelivery { trade_id: bid_id, aggregator_id, window, delivered: 0.0 }],
+        }
+    }
+
+    /// Ask a BESS for its schedule
+    pub fn schedule_request(message_id: u64, device_id: u64) -> Self {
+        Self { kind: DeliveryFrameKind::ScheduleRequest, message_id, device_id, deliveries: Vec::new() }
+    }
+
+    /// Answer a schedule request
+    pub fn schedule(message_id: u64, device_id: u64, deliveries: Vec<ScheduledDelivery>) -> Self {
+        Self { kind: DeliveryFrameKind::Schedule, message_id, device_id, deliveries }
+    }
+
+    /// Get the window of a `Window` frame
+    pub fn delivery_window(&self) -> Option<&DeliveryWindow> {
+        match self.kind {
+            DeliveryFrameKind::Window => self.deliveries.first().map(|delivery| &delivery.window),
+            _ => None,
+        }
+    }
+
+    /// Check whether a frame is a delivery frame
+    pub fn is_delivery_frame(frame: &[u8]) -> bool {
+        frame.len() >= DELIVERY_MAGIC.len() && frame[..DELIVERY_MAGIC.len()] == DELIVERY_MAGIC
+    }
+
+    /// Encode the delivery frame
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        if self.deliveries.len() > u16::MAX as usize {
+            return Err(ETPError::Validation(format!(
+                "Delivery frame can carry at most {} entries, got {}",
+                u16::MAX,
+                self.deliveries.len()
+            )));
+        }
+
+        let mut buf = BytesMut::with_capacity(DELIVERY_HEADER_SIZE + WIRE_CHECKSUM_SIZE);
+        buf.put_slice(&DELIVERY_MAGIC);
+        buf.put_u8(self.kind as u8);
+        buf.put_u64_le(self.message_id);
+        buf.put_u64_le(self.device_id);
+        buf.put_u16_le(self.deliveries.len() as u16);
+        for delivery in &self.deliveries {
+            put_delivery(delivery, &mut buf)?;
+        }
+
+        let checksum = crc32fast::hash(&buf);
+        buf.put_u32_le(checksum);
+        Ok(buf.to_vec())
+    }
+
+    /// Decode a delivery frame
+    pub fn decode(frame: &[u8]) -> Result<Self> {
+        if !Self::is_delivery_frame(frame) {
+            let magic = [frame.first().copied().unwrap_or(0), frame.get(1).copied().unwrap_or(0)];
+            return Err(ETPError::Serialization(SerializationError::InvalidMagic(magic)));
+        }
+        if frame.len() < DELIVERY_HEADER_SIZE + WIRE_CHECKSUM_SIZE {
+            return Err(ETPError::Serialization(SerializationError::InvalidMessageSize {
+                expected: DELIVERY_HEADER_SIZE + WIRE_CHECKSUM_SIZE,
+                actual: frame.len(),
+            }));
+        }
+
+        let (content, mut trailer) = frame.split_at(frame.len() - WIRE_CHECKSUM_SIZE);
+        let expected = trailer.get_u32_le();
+        let actual = crc32fast::hash(content);
+        if expected != actual {
+            return Err(ETPError::Serialization(SerializationError::ChecksumMismatch { expected, actual }));
+        }
+
+        let mut body = &content[DELIVERY_MAGIC.len()..];
+        let kind = DeliveryFrameKind::try_from(body.get_u8())?;
+        let message_id = body.get_u64_le();
+        let device_id = body.get_u64_le();
+        let count = body.get_u16_le() as usize;
+
+        let mut deliveries = Vec::with_capacity(count.min(body.len() / ENTRY_FIXED_SIZE));
+        for _ in 0..count {
+            deliveries.push(get_delivery(&mut body)?);
+        }
+        if !body.is_empty() {
+            return Err(ETPError::Serialization(SerializationError::InvalidDeliveryFrame(
+                "trailing bytes after the last entry",
+            )));
+        }
+
+        Ok(Self { kind, message_id, device_id, deliveries })
+    }
+}
+
+/// Write one delivery entry
+fn put_delivery(delivery: &ScheduledDelivery, buf: &mut BytesMut) -> Result<()> {
+    let window = &delivery.window;
+    let start_ms = window
+        .start
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| ETPError::Validation("Delivery window starts before the Unix epoch".to_string()))?
+        .as_millis();
+    let interval_ms = window.interval.as_millis();
+    if start_ms > u64::MAX as u128 || interval_ms > u32::MAX as u128 || window.profile.len() > u16::MAX as usize {
+        return Err(ETPError::Validation("Delivery window does not fit a delivery frame".to_string()));
+    }
+
+    buf.reserve(ENTRY_FIXED_SIZE + 8 * window.profile.len());
+    buf.put_u64_le(delivery.trade_id);
+    buf.put_u64_le(delivery.aggregator_id);
+    buf.put_u64_le(start_ms as u64);
+    buf.put_u32_le(interval_ms as u32);
+    buf.put_f64_le(delivery.delivered);
+    buf.put_u16_le(window.profile.len() as u16);
+    for power in &window.profile {
+        buf.put_f64_le(*power);
+    }
+    Ok(())
+}
+
+/// Read one delivery entry
+fn get_delivery(body: &mut &[u8]) -> Result<ScheduledDelivery> {
+    if body.len() < ENTRY_FIXED_SIZE {
+        return Err(ETPError::Serialization(SerializationError::InvalidDeliveryFrame("truncated entry")));
+    }
+    let trade_id = body.get_u64_le();
+    let aggregator_id = body.get_u64_le();
+    let start = UNIX_EPOCH + Duration::from_millis(body.get_u64_le());
+    let interval = Duration::from_millis(body.get_u32_le() as u64);
+    let delivered = body.get_f64_le();
+    let steps = body.get_u16_le() as usize;
+    if body.len() < 8 * steps {
+        return Err(ETPError::Serialization(SerializationError::InvalidDeliveryFrame("truncated profile")));
+    }
+    let profile = (0..steps).map(|_| body.get_f64_le()).collect();
+
+    Ok(ScheduledDelivery {
+        trade_id,
+        aggregator_id,
+        window: DeliveryWindow::new(start, interval, profile),
+        delivered,
+    })
+}
+
+/// Get the current time truncated to the millisecond precision of delivery frames
+pub fn delivery_time_now() -> SystemTime {
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    UNIX_EPOCH + Duration::from_millis(now_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delivery_frame_roundtrip_and_corruption() {
+        let start = delivery_time_now();
+        let window = DeliveryWindow::new(start, Duration::from_secs(900), vec![2.0, 4.0, 1.5]);
+        let frame = DeliveryFrame::window(7, 42, window.clone());
+        let encoded = frame.encode().unwrap();
+        assert!(DeliveryFrame::is_delivery_frame(&encoded));
+        assert_eq!(encoded.len(), DELIVERY_HEADER_SIZE + ENTRY_FIXED_SIZE + 3 * 8 + WIRE_CHECKSUM_SIZE);
+
+        let decoded = DeliveryFrame::decode(&encoded).unwrap();
+        assert_eq!(decoded, frame);
+        assert_eq!(decoded.delivery_window(), Some(&window));
+
+        let request = DeliveryFrame::schedule_request(8, 42);
+        assert_eq!(DeliveryFrame::decode(&request.encode().unwrap()).unwrap(), request);
+        assert_eq!(request.delivery_window(), None);
+
+        let mut corrupted = encoded.clone();
+        corrupted[30] ^= 0xff;
+        assert!(matches!(
+            DeliveryFrame::decode(&corrupted),
+            Err(ETPError::Serialization(SerializationError::ChecksumMismatch { .. }))
+        ));
+        assert!(DeliveryFrame::decode(&encoded[..20]).is_err());
+    }
+}
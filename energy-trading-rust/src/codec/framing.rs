@@ -0,0 +1,238 @@
+use crate::codec::legacy_go::{is_go_text_start, GO_TEXT_DETECT_SIZE};
+use crate::error::{ETPError, Result, SerializationError};
+use bytes::{Buf, BufMut, BytesMut};
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Message encoding spoken by the peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WireFormat {
+    Etp,    // Length-prefixed fixed-layout ETP frames
+    GoText, // Space-separated text of the legacy Go prototype
+}
+
+/// Size of the little-endian length prefix before every TCP frame
+pub const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// Largest frame accepted by default, compressed or once decompressed
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// Check a frame length against the limit before anything is allocated for it
+pub fn check_frame_size(size: usize, max_frame_size: usize) -> Result<()> {
+    if size > max_frame_size {
+        return Err(ETPError::Serialization(SerializationError::FrameTooLarge {
+            size,
+            max: max_frame_size,
+        }));
+    }
+    Ok(())
+}
+
+/// Split one complete length-prefixed frame off the front of `buffer`
+///
+/// Returns `None` until the whole frame has arrived. The length prefix is
+/// checked as soon as it is readable, so an oversized frame fails with
+/// `FrameTooLarge` without waiting for, or buffering, its body.
+pub fn split_frame(buffer: &mut BytesMut, max_frame_size: usize) -> Result<Option<Vec<u8>>> {
+    let Some(length_bytes) = buffer.get(..LENGTH_PREFIX_SIZE) else {
+        return Ok(None);
+    };
+    let length = u32::from_le_bytes(length_bytes.try_into().unwrap()) as usize;
+    check_frame_size(length, max_frame_size)?;
+    if buffer.len() < LENGTH_PREFIX_SIZE + length {
+        return Ok(None);
+    }
+
+    buffer.advance(LENGTH_PREFIX_SIZE);
+    Ok(Some(buffer.split_to(length).to_vec()))
+}
+
+/// Frames ETP traffic for `tokio_util::codec::Framed`, `FramedRead` and `FramedWrite`
+///
+/// ETP frames carry a 4-byte length prefix; Go text frames are whatever a
+/// single read delivered, matching how the Go prototype reads. Items are
+/// raw frames, so the codec sits below signing, compression and batching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EtpCodec {
+    wire_format: Option<WireFormat>, // None = detect from the peer's first bytes
+    max_frame_size: usize,
+}
+
+impl Default for EtpCodec {
+    fn default() -> Self {
+        Self {
+            wire_format: Some(WireFormat::Etp),
+            max_frame_size: MAX_FRAME_SIZE,
+        }
+    }
+}
+
+impl EtpCodec {
+    /// Create a codec for length-prefixed ETP frames up to `MAX_FRAME_SIZE`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Force the wire format
+    pub fn set_wire_format(&mut self, wire_format: WireFormat) {
+        self.wire_format = Some(wire_format);
+    }
+
+    /// Detect the wire format from the first bytes decoded; frames encoded before that are ETP
+    pub fn detect_wire_format(&mut self) {
+        self.wire_format = None;
+    }
+
+    /// Get the wire format in use, `None` while still detecting
+    pub fn wire_format(&self) -> Option<WireFormat> {
+        self.wire_format
+    }
+
+    /// Refuse frames longer than `max_frame_size` in either direction with `FrameTooLarge`
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.max_frame_size = max_frame_size;
+    }
+
+    /// Get the frame size limit
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+}
+
+impl Decoder for EtpCodec {
+    type Item = Vec<u8>;
+    type Error = ETPError;
+
+    fn decode(&mut self, buffer: &mut BytesMut) -> Result<Option<Vec<u8>>> {
+        let wire_format = match self.wire_format {
+            Some(wire_format) => wire_format,
+            // A length prefix can start like a digit, so look at all of it
+            None if buffer.len() < GO_TEXT_DETECT_SIZE => return Ok(None),
+            None => {
+                let wire_format = if is_go_text_start(buffer) { WireFormat::GoText } else { WireFormat::Etp };
+                self.wire_format = Some(wire_format);
+                wire_format
+            }
+        };
+        match wire_format {
+            WireFormat::GoText => Ok((!buffer.is_empty()).then(|| buffer.split().to_vec())),
+            WireFormat::Etp => split_frame(buffer, self.max_frame_size),
+        }
+    }
+
+    fn decode_eof(&mut self, buffer: &mut BytesMut) -> Result<Option<Vec<u8>>> {
+        match self.decode(buffer)? {
+            Some(frame) => Ok(Some(frame)),
+            None if buffer.is_empty() => Ok(None),
+            // The peer hung up part way through a frame
+            None => Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for EtpCodec {
+    type Error = ETPError;
+
+    fn encode(&mut self, frame: T, buffer: &mut BytesMut) -> Result<()> {
+        let frame = frame.as_ref();
+        check_frame_size(frame.len(), self.max_frame_size)?;
+        if self.wire_format != Some(WireFormat::GoText) {
+            buffer.reserve(LENGTH_PREFIX_SIZE + frame.len());
+            buffer.put_u32_le(frame.len() as u32);
+        }
+        buffer.extend_from_slice(frame);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::codec::Framed;
+
+    #[test]
+    fn test_split_frame_waits_for_whole_frame() {
+        let mut buffer = BytesMut::from(&[3, 0, 0, 0, 1, 2][..]);
+        assert_eq!(split_frame(&mut buffer, MAX_FRAME_SIZE).unwrap(), None);
+
+        buffer.extend_from_slice(&[3, 0, 0, 0, 0]);
+        assert_eq!(split_frame(&mut buffer, MAX_FRAME_SIZE).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(split_frame(&mut buffer, MAX_FRAME_SIZE).unwrap(), Some(vec![]));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_oversized_length_rejected_before_body() {
+        // A 4 GB length prefix must not wait for, or allocate, 4 GB
+        let mut buffer = BytesMut::from(&u32::MAX.to_le_bytes()[..]);
+        assert!(matches!(
+            split_frame(&mut buffer, MAX_FRAME_SIZE),
+            Err(ETPError::Serialization(SerializationError::FrameTooLarge { size, max }))
+                if size == u32::MAX as usize && max == MAX_FRAME_SIZE
+        ));
+    }
+
+    #[test]
+    fn test_codec_detects_wire_format() {
+        let mut codec = EtpCodec::new();
+        codec.detect_wire_format();
+        assert_eq!(codec.decode(&mut BytesMut::new()).unwrap(), None);
+        assert_eq!(codec.wire_format(), None);
+
+        let mut buffer = BytesMut::from(&b"1 7"[..]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert_eq!(codec.wire_format(), None);
+        buffer.extend_from_slice(b" 0 5");
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(b"1 7 0 5".to_vec()));
+        assert_eq!(codec.wire_format(), Some(WireFormat::GoText));
+
+        // Go text goes out without a length prefix
+        let mut encoded = BytesMut::new();
+        codec.encode(b"2 7 0 5", &mut encoded).unwrap();
+        assert_eq!(&encoded[..], b"2 7 0 5");
+    }
+
+    #[test]
+    fn test_codec_detects_frame_whose_length_looks_like_a_digit() {
+        let mut codec = EtpCodec::new();
+        codec.detect_wire_format();
+
+        // 0x30 is ASCII '0', and the body could be anything
+        let body = vec![b'1'; 0x30];
+        let mut buffer = BytesMut::new();
+        buffer.put_u32_le(body.len() as u32);
+        buffer.extend_from_slice(&body);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(body));
+        assert_eq!(codec.wire_format(), Some(WireFormat::Etp));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_codec_refuses_truncated_frame_at_eof() {
+        let mut codec = EtpCodec::new();
+        let mut buffer = BytesMut::from(&[3, 0, 0, 0, 1][..]);
+        assert!(matches!(codec.decode_eof(&mut buffer), Err(ETPError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof));
+        assert_eq!(codec.decode_eof(&mut BytesMut::new()).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_framed_tcp_stream_roundtrip() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        let mut client = Framed::new(client, EtpCodec::new());
+        let mut server = Framed::new(server, EtpCodec::new());
+
+        client.send(vec![1, 2, 3]).await.unwrap();
+        client.send(&[][..]).await.unwrap();
+        assert_eq!(server.next().await.unwrap().unwrap(), vec![1, 2, 3]);
+        assert_eq!(server.next().await.unwrap().unwrap(), Vec::<u8>::new());
+
+        drop(client);
+        assert!(server.next().await.is_none());
+    }
+}
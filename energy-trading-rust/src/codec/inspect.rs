@@ -0,0 +1,295 @@
+use crate::codec::batch::{MessageBatch, BATCH_MAGIC};
+use crate::codec::compact::COMPACT_MAGIC;
+use crate::codec::compression::{decompress_frame, is_compressed_frame, COMPRESSED_MAGIC};
+use crate::codec::delivery_frame::{DeliveryFrame, DELIVERY_MAGIC};
+use crate::codec::framing::{split_frame, MAX_FRAME_SIZE};
+use crate::codec::wire::ETP_MAGIC;
+use crate::envelope::{SignedEnvelope, ENVELOPE_MAGIC};
+use crate::etp_message::ETPMessage;
+use crate::error::{ETPError, Result, SerializationError};
+use crate::network::handshake::{Hello, HELLO_MAGIC};
+use bytes::BytesMut;
+use serde_json::json;
+use std::fmt;
+
+/// Magics of every frame kind, used to tell bare frames from length-prefixed streams
+const FRAME_MAGICS: [[u8; 2]; 7] =
+    [ETP_MAGIC, HELLO_MAGIC, ENVELOPE_MAGIC, BATCH_MAGIC, COMPACT_MAGIC, COMPRESSED_MAGIC, DELIVERY_MAGIC];
+
+/// A single frame decoded for inspection, without verifying signatures or replays
+#[derive(Debug, Clone, PartialEq)]
+pub enum InspectedFrame {
+    Hello(Hello),
+    Wire(ETPMessage),
+    Compact(ETPMessage),
+    GoText(ETPMessage),
+    Signed(SignedEnvelope),
+    Batch(MessageBatch),
+    Delivery(DeliveryFrame),
+    Compressed(Box<InspectedFrame>),
+}
+
+impl InspectedFrame {
+    /// Decode any frame an ETP peer may send
+    pub fn decode(frame: &[u8]) -> Result<Self> {
+        if is_compressed_frame(frame) {
+            return Ok(InspectedFrame::Compressed(Box::new(Self::decode_expanded(&decompress_frame(frame, MAX_FRAME_SIZE)?)?)));
+        }
+        Self::decode_expanded(frame)
+    }
+
+    /// Decode a frame that is not compressed
+    fn decode_expanded(frame: &[u8]) -> Result<Self> {
+        if Hello::is_hello_frame(frame) {
+            Ok(InspectedFrame::Hello(Hello::decode(frame)?))
+        } else if MessageBatch::is_batch_frame(frame) {
+            Ok(InspectedFrame::Batch(MessageBatch::decode(frame)?))
+        } else if DeliveryFrame::is_delivery_frame(frame) {
+            Ok(InspectedFrame::Delivery(DeliveryFrame::decode(frame)?))
+        } else if SignedEnvelope::is_envelope_frame(frame) {
+            Ok(InspectedFrame::Signed(SignedEnvelope::decode(frame)?))
+        } else if ETPMessage::is_compact_frame(frame) {
+            Ok(InspectedFrame::Compact(ETPMessage::decode_compact(frame)?))
+        } else if frame.first().is_some_and(u8::is_ascii_digit) {
+            Ok(InspectedFrame::GoText(ETPMessage::decode_go_bytes(frame)?))
+        } else {
+            Ok(InspectedFrame::Wire(ETPMessage::deserialize(frame)?))
+        }
+    }
+
+    /// Get the name of this frame kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            InspectedFrame::Hello(_) => "hello",
+            InspectedFrame::Wire(_) => "wire",
+            InspectedFrame::Compact(_) => "compact",
+            InspectedFrame::GoText(_) => "go-text",
+            InspectedFrame::Signed(_) => "signed",
+            InspectedFrame::Batch(_) => "batch",
+            InspectedFrame::Delivery(_) => "delivery",
+            InspectedFrame::Compressed(_) => "compressed",
+        }
+    }
+
+    /// Get every message carried by the frame
+    pub fn messages(&self) -> Vec<&ETPMessage> {
+        match self {
+            InspectedFrame::Hello(_) | InspectedFrame::Delivery(_) => Vec::new(),
+            InspectedFrame::Wire(message) | InspectedFrame::Compact(message) | InspectedFrame::GoText(message) => {
+                vec![message]
+            }
+            InspectedFrame::Signed(envelope) => vec![&envelope.message],
+            InspectedFrame::Batch(batch) => batch.messages.iter().collect(),
+            InspectedFrame::Compressed(inner) => inner.messages(),
+        }
+    }
+
+    /// Convert the frame to JSON, with messages in their canonical JSON form
+    pub fn to_json_value(&self) -> Result<serde_json::Value> {
+        let value = match self {
+            InspectedFrame::Hello(hello) => json!({
+                "frame": self.kind(),
+                "versions": hello.versions,
+                "capabilities": hello.capabilities.bits(),
+            }),
+            InspectedFrame::Wire(message) | InspectedFrame::Compact(message) | InspectedFrame::GoText(message) => json!({
+                "frame": self.kind(),
+                "message": message.to_json_value()?,
+            }),
+            InspectedFrame::Signed(envelope) => json!({
+                "frame": self.kind(),
+                "sender_public_key": to_hex(&envelope.sender_public_key),
+                "nonce": envelope.nonce,
+                "timestamp_ms": envelope.timestamp_ms,
+                "signature": to_hex(&envelope.signature),
+                "message": envelope.message.to_json_value()?,
+            }),
+            InspectedFrame::Batch(batch) => json!({
+                "frame": self.kind(),
+                "version": batch.version,
+                "delta_status": batch.delta_status,
+                "messages": batch.messages.iter().map(ETPMessage::to_json_value).collect::<Result<Vec<_>>>()?,
+            }),
+            InspectedFrame::Delivery(delivery) => json!({
+                "frame": self.kind(),
+                "delivery": serde_json::to_value(delivery)?,
+            }),
+            InspectedFrame::Compressed(inner) => json!({
+                "frame": self.kind(),
+                "inner": inner.to_json_value()?,
+            }),
+        };
+        Ok(value)
+    }
+}
+
+impl fmt::Display for InspectedFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InspectedFrame::Hello(hello) => {
+                write!(f, "hello: versions {:?}, capabilities {:#010x}", hello.versions, hello.capabilities.bits())
+            }
+            InspectedFrame::Wire(message) | InspectedFrame::Compact(message) | InspectedFrame::GoText(message) => {
+                write!(f, "{}: {}", self.kind(), message)
+            }
+            InspectedFrame::Signed(envelope) => write!(
+                f,
+                "signed by {}… (nonce {}, at {} ms): {}",
+                to_hex(&envelope.sender_public_key[..8]),
+                envelope.nonce,
+                envelope.timestamp_ms,
+                envelope.message
+            ),
+            InspectedFrame::Batch(batch) => {
+                write!(f, "batch of {} messages (version {})", batch.len(), batch.version)?;
+                batch.messages.iter().try_for_each(|message| write!(f, "\n  {}", message))
+            }
+            InspectedFrame::Delivery(delivery) => write!(
+                f,
+                "delivery {:?} #{} from device {}: {} entries",
+                delivery.kind,
+                delivery.message_id,
+                delivery.device_id,
+                delivery.deliveries.len()
+            ),
+            InspectedFrame::Compressed(inner) => write!(f, "compressed {}", inner),
+        }
+    }
+}
+
+/// Splits captured traffic into frames
+///
+/// Feed it payloads as captured, e.g. one TCP segment per call. A payload
+/// starting with a frame magic or holding a Go text message is taken as one
+/// bare frame; anything else is read as length-prefixed ETP traffic, whose
+/// frames may straddle payloads.
+#[derive(Debug)]
+pub struct CaptureSplitter {
+    buffer: BytesMut,
+    max_frame_size: usize,
+}
+
+impl Default for CaptureSplitter {
+    fn default() -> Self {
+        Self {
+            buffer: BytesMut::new(),
+            max_frame_size: MAX_FRAME_SIZE,
+        }
+    }
+}
+
+impl CaptureSplitter {
+    /// Create a splitter using the default frame size limit
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one captured payload and return the frames it completes
+    pub fn push(&mut self, payload: &[u8]) -> Result<Vec<Vec<u8>>> {
+        if self.buffer.is_empty() && is_bare_frame(payload) {
+            return Ok(vec![payload.to_vec()]);
+        }
+        self.buffer.extend_from_slice(payload);
+        let mut frames = Vec::new();
+        while let Some(frame) = split_frame(&mut self.buffer, self.max_frame_size)? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    /// Get the number of buffered bytes still waiting for the rest of a frame
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+/// Check whether a payload is a whole frame rather than length-prefixed traffic
+fn is_bare_frame(payload: &[u8]) -> bool {
+    FRAME_MAGICS.iter().any(|magic| payload.starts_with(magic)) || ETPMessage::decode_go_bytes(payload).is_ok()
+}
+
+/// Parse hex text, ignoring whitespace, `:` separators and a leading `0x`
+pub fn parse_hex(text: &str) -> Result<Vec<u8>> {
+    let trimmed = text.trim();
+    let digits: Vec<u8> = trimmed
+        .strip_prefix("0x")
+        .unwrap_or(trimmed)
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace() && *byte != b':')
+        .collect();
+    let invalid = || {
+        ETPError::Serialization(SerializationError::InvalidTextField {
+            field: "hex",
+            value: text.to_string(),
+        })
+    };
+    if !digits.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+/// Format bytes as lowercase hex
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::compression::compress_frame;
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = ETPMessage::new_query(1, 2).serialize().unwrap();
+        assert_eq!(parse_hex(&to_hex(&bytes)).unwrap(), bytes);
+        assert_eq!(parse_hex("0x45:54 01\n03").unwrap(), vec![0x45, 0x54, 0x01, 0x03]);
+        assert!(parse_hex("4554 0").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_inspect_nested_frames() {
+        let batch = MessageBatch::new(vec![ETPMessage::new_query(1, 2), ETPMessage::new_bid(3, 18.0, 10.0)]);
+        let frame = compress_frame(&batch.encode().unwrap()).unwrap();
+
+        let inspected = InspectedFrame::decode(&frame).unwrap();
+        assert_eq!(inspected.kind(), "compressed");
+        assert_eq!(inspected.messages().len(), 2);
+        let value = inspected.to_json_value().unwrap();
+        assert_eq!(value["inner"]["frame"], "batch");
+        assert_eq!(value["inner"]["messages"][1]["type"], "Bid");
+        assert!(inspected.to_string().starts_with("compressed batch of 2 messages"));
+
+        let go = InspectedFrame::decode(ETPMessage::new_query(1, 2).encode_go_text().as_bytes()).unwrap();
+        assert_eq!(go.kind(), "go-text");
+        assert!(InspectedFrame::decode(b"XY").is_err());
+    }
+
+    #[test]
+    fn test_capture_splitter_handles_bare_and_prefixed_payloads() {
+        let wire = ETPMessage::new_query(1, 2).serialize().unwrap();
+        let mut splitter = CaptureSplitter::new();
+        assert_eq!(splitter.push(&wire).unwrap(), vec![wire.clone()]);
+
+        // Two length-prefixed frames split across three segments
+        let mut stream = Vec::new();
+        for _ in 0..2 {
+            stream.extend_from_slice(&(wire.len() as u32).to_le_bytes());
+            stream.extend_from_slice(&wire);
+        }
+        assert!(splitter.push(&stream[..50]).unwrap().is_empty());
+        assert_eq!(splitter.push(&stream[50..120]).unwrap().len(), 1);
+        assert_eq!(splitter.pending(), 120 - 95);
+        assert_eq!(splitter.push(&stream[120..]).unwrap().len(), 1);
+        assert_eq!(splitter.pending(), 0);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::error::{ETPError, Result};
+use crate::termination::TerminationCode;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+
+/// Canonical JSON form of an `ETPMessage`
+///
+/// All 14 fields are always present, in research-paper order. The message
+/// type and known termination codes are written by name and every quantity
+/// carries its unit in the key:
+///
+/// ```json
+/// {
+///   "type": "Bid",
+///   "message_id": 42,
+///   "device_id": 0,
+///   "ttl": 5,
+///   "bid_price_c_per_kwh": 18.0,
+///   "sale_price_c_per_kwh": 0.0,
+///   "energy_total_kwh": 0.0,
+///   "percentage_for_sale_pct": 0.0,
+///   "required_energy_amount_kwh": 10.0,
+///   "termination_code": "Normal",
+///   "remaining_battery_energy_kwh": 0.0,
+///   "battery_health_status_code": 0,
+///   "battery_voltage_v": 0.0,
+///   "discharge_rate_kw": 0.0
+/// }
+/// ```
+///
+/// Floats are written with the shortest representation that parses back to
+/// the same value. JSON has no NaN or infinities, so those are written as the
+/// strings `"NaN"`, `"inf"` and `"-inf"`; a NaN with a non-default payload is
+/// written as its bits, e.g. `"NaN:0x7ff8000000000001"`. Every message
+/// therefore survives a round trip through JSON bit for bit.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CanonicalMessage {
+    #[serde(rename = "type")]
+    message_type: MessageType,
+    message_id: u64,
+    device_id: u64,
+    ttl: u8,
+    #[serde(with = "json_float")]
+    bid_price_c_per_kwh: f64,
+    #[serde(with = "json_float")]
+    sale_price_c_per_kwh: f64,
+    #[serde(with = "json_float")]
+    energy_total_kwh: f64,
+    #[serde(with = "json_float")]
+    percentage_for_sale_pct: f64,
+    #[serde(with = "json_float")]
+    required_energy_amount_kwh: f64,
+    termination_code: JsonTerminationCode,
+    #[serde(with = "json_float")]
+    remaining_battery_energy_kwh: f64,
+    battery_health_status_code: u8,
+    #[serde(with = "json_float")]
+    battery_voltage_v: f64,
+    #[serde(with = "json_float")]
+    discharge_rate_kw: f64,
+}
+
+/// Termination code by name, or by number for codes outside the registry
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonTerminationCode {
+    Known(TerminationCode),
+    Raw(u8),
+}
+
+impl JsonTerminationCode {
+    fn as_u8(&self) -> u8 {
+        match self {
+            JsonTerminationCode::Known(code) => code.as_u8(),
+            JsonTerminationCode::Raw(value) => *value,
+        }
+    }
+}
+
+impl From<u8> for JsonTerminationCode {
+    fn from(value: u8) -> Self {
+        match TerminationCode::try_from(value) {
+            Ok(code) => JsonTerminationCode::Known(code),
+            Err(_) => JsonTerminationCode::Raw(value),
+        }
+    }
+}
+
+impl TryFrom<&ETPMessage> for CanonicalMessage {
+    type Error = ETPError;
+
+    fn try_from(message: &ETPMessage) -> Result<Self> {
+        Ok(Self {
+            message_type: message.kind()?,
+            message_id: message.message_id,
+            device_id: message.device_id,
+            ttl: message.ttl,
+            bid_price_c_per_kwh: message.bid_price,
+            sale_price_c_per_kwh: message.sale_price,
+            energy_total_kwh: message.energy_total,
+            percentage_for_sale_pct: message.percentage_for_sale,
+            required_energy_amount_kwh: message.required_energy_amount,
+            termination_code: message.termination_code.into(),
+            remaining_battery_energy_kwh: message.remaining_battery_energy,
+            battery_health_status_code: message.battery_health_status_code,
+            battery_voltage_v: message.battery_voltage,
+            discharge_rate_kw: message.discharge_rate,
+        })
+    }
+}
+
+impl From<CanonicalMessage> for ETPMessage {
+    fn from(canonical: CanonicalMessage) -> Self {
+        Self {
+            message_type: canonical.message_type.as_u8(),
+            message_id: canonical.message_id,
+            device_id: canonical.device_id,
+            ttl: canonical.ttl,
+            bid_price: canonical.bid_price_c_per_kwh,
+            sale_price: canonical.sale_price_c_per_kwh,
+            energy_total: canonical.energy_total_kwh,
+            percentage_for_sale: canonical.percentage_for_sale_pct,
+            required_energy_amount: canonical.required_energy_amount_kwh,
+            termination_code: canonical.termination_code.as_u8(),
+            remaining_battery_energy: canonical.remaining_battery_energy_kwh,
+            battery_health_status_code: canonical.battery_health_status_code,
+            battery_voltage: canonical.battery_voltage_v,
+            discharge_rate: canonical.discharge_rate_kw,
+        }
+    }
+}
+
+impl ETPMessage {
+    /// Convert the message to its canonical JSON value
+    ///
+    /// Fails for message types outside 0-9, which have no name.
+    pub fn to_json_value(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(CanonicalMessage::try_from(self)?)?)
+    }
+
+    /// Encode the message as single-line canonical JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&CanonicalMessage::try_from(self)?)?)
+    }
+
+    /// Encode the message as indented canonical JSON
+    pub fn to_json_pretty(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&CanonicalMessage::try_from(self)?)?)
+    }
+
+    /// Decode a message from canonical JSON
+    pub fn from_json(text: &str) -> Result<Self> {
+        Ok(serde_json::from_str::<CanonicalMessage>(text)?.into())
+    }
+
+    /// Decode a message from a canonical JSON value
+    pub fn from_json_value(value: serde_json::Value) -> Result<Self> {
+        Ok(serde_json::from_value::<CanonicalMessage>(value)?.into())
+    }
+}
+
+/// Floats as JSON numbers, with non-finite values as strings
+mod json_float {
+    use super::*;
+    use serde::de::Error;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum JsonFloat {
+        Number(f64),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f64(*value)
+        } else if value.is_nan() && value.to_bits() != f64::NAN.to_bits() {
+            serializer.serialize_str(&format!("NaN:{:#018x}", value.to_bits()))
+        } else if value.is_nan() {
+            serializer.serialize_str("NaN")
+        } else if *value > 0.0 {
+            serializer.serialize_str("inf")
+        } else {
+            serializer.serialize_str("-inf")
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<f64, D::Error> {
+        let text = match JsonFloat::deserialize(deserializer)? {
+            JsonFloat::Number(value) => return Ok(value),
+            JsonFloat::Text(text) => text,
+        };
+        match text.as_str() {
+            "NaN" => Ok(f64::NAN),
+            "inf" => Ok(f64::INFINITY),
+            "-inf" => Ok(f64::NEG_INFINITY),
+            _ => text
+                .strip_prefix("NaN:0x")
+                .and_then(|bits| u64::from_str_radix(bits, 16).ok())
+                .map(f64::from_bits)
+                .filter(|value| value.is_nan())
+                .ok_or_else(|| D::Error::custom(format!("invalid float {:?}", text))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_uses_names_and_units() {
+        let message = ETPMessage::new_bid_reject(42, 7, TerminationCode::PriceBelowReserve);
+        let value = message.to_json_value().unwrap();
+        assert_eq!(value["type"], "BidReject");
+        assert_eq!(value["termination_code"], "PriceBelowReserve");
+        assert_eq!(value["bid_price_c_per_kwh"], 0.0);
+        assert_eq!(value.as_object().unwrap().len(), 14);
+        assert_eq!(ETPMessage::from_json_value(value).unwrap(), message);
+    }
+
+    #[test]
+    fn test_json_roundtrip_is_bit_exact() {
+        let mut message = ETPMessage::new_bess_status(1, 2, f64::NAN, 3, f64::INFINITY, -0.0);
+        message.bid_price = f64::from_bits(0x7ff8_0000_0000_0001);
+        message.sale_price = f64::NEG_INFINITY;
+        message.energy_total = 0.1 + 0.2;
+        message.termination_code = 200; // Outside the registry
+
+        let text = message.to_json().unwrap();
+        assert!(text.contains("\"NaN:0x7ff8000000000001\""));
+        assert!(text.contains("\"termination_code\":200"));
+        let decoded = ETPMessage::from_json(&text).unwrap();
+        assert_eq!(decoded.serialize().unwrap(), message.serialize().unwrap());
+    }
+
+    #[test]
+    fn test_json_rejects_unknown_types_and_fields() {
+        let mut message = ETPMessage::new_query(1, 2);
+        message.message_type = 42;
+        assert!(message.to_json().is_err());
+
+        let text = ETPMessage::new_query(1, 2).to_json().unwrap();
+        assert!(ETPMessage::from_json(&text.replace("\"Query\"", "\"Quote\"")).is_err());
+        assert!(ETPMessage::from_json(&text.replace("\"ttl\"", "\"hops\"")).is_err());
+        assert!(ETPMessage::from_json(&text.replace("\"ttl\":5,", "")).is_err());
+    }
+}
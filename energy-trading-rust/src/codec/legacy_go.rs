@@ -0,0 +1,177 @@
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::error::{ETPError, Result, SerializationError};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Number of space-separated fields in a Go prototype message
+pub const GO_FIELD_COUNT: usize = 14;
+
+/// Largest text message the Go prototype reads in one go
+pub const GO_MAX_MESSAGE_SIZE: usize = 1024;
+
+/// Field names in Go wire order, used in error messages
+const GO_FIELD_NAMES: [&str; GO_FIELD_COUNT] = [
+    "message_type",
+    "message_id",
+    "device_id",
+    "ttl",
+    "bid_price",
+    "sale_price",
+    "energy_total",
+    "percentage_for_sale",
+    "required_energy_amount",
+    "termination_code",
+    "remaining_battery_energy",
+    "battery_health_status_code",
+    "battery_voltage",
+    "discharge_rate",
+];
+
+/// Number of bytes needed to tell a Go prototype stream from framed ETP
+pub const GO_TEXT_DETECT_SIZE: usize = 4;
+
+/// Check whether the first bytes of a stream look like a Go prototype message
+///
+/// Go messages start with the ASCII message type digit, followed by more
+/// digits and spaces. Framed ETP traffic starts with a 4-byte little-endian
+/// length prefix, whose top byte is zero for any frame under 16 MiB.
+pub fn is_go_text_start(start: &[u8]) -> bool {
+    start.first().is_some_and(u8::is_ascii_digit)
+        && start.iter().take(GO_TEXT_DETECT_SIZE).all(|byte| byte.is_ascii_digit() || *byte == b' ')
+}
+
+/// Compatibility codec for the legacy Go prototype (`energy-trading-golang/message`)
+///
+/// The Go side writes the 14 fields space-separated in research-paper order,
+/// integers with `%d` and floats with `%0.2f`, without any terminator or
+/// length prefix. Floats therefore only survive a round trip to two decimals.
+impl ETPMessage {
+    /// Encode the message in the Go prototype text format
+    pub fn encode_go_text(&self) -> String {
+        format!(
+            "{} {} {} {} {:.2} {:.2} {:.2} {:.2} {:.2} {} {:.2} {} {:.2} {:.2}",
+            self.message_type,
+            self.message_id,
+            self.device_id,
+            self.ttl,
+            self.bid_price,
+            self.sale_price,
+            self.energy_total,
+            self.percentage_for_sale,
+            self.required_energy_amount,
+            self.termination_code,
+            self.remaining_battery_energy,
+            self.battery_health_status_code,
+            self.battery_voltage,
+            self.discharge_rate,
+        )
+    }
+
+    /// Decode a message from the Go prototype text format
+    pub fn decode_go_text(text: &str) -> Result<Self> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        if fields.len() != GO_FIELD_COUNT {
+            return Err(ETPError::Serialization(SerializationError::InvalidFieldCount {
+                expected: GO_FIELD_COUNT,
+                actual: fields.len(),
+            }));
+        }
+
+        let message_type = parse_field::<u8>(&fields, 0)?;
+        MessageType::try_from(message_type).map_err(ETPError::Serialization)?;
+
+        Ok(Self {
+            message_type,
+            message_id: parse_field(&fields, 1)?,
+            device_id: parse_field(&fields, 2)?,
+            ttl: parse_field(&fields, 3)?,
+            bid_price: parse_field(&fields, 4)?,
+            sale_price: parse_field(&fields, 5)?,
+            energy_total: parse_field(&fields, 6)?,
+            percentage_for_sale: parse_field(&fields, 7)?,
+            required_energy_amount: parse_field(&fields, 8)?,
+            termination_code: parse_field(&fields, 9)?,
+            remaining_battery_energy: parse_field(&fields, 10)?,
+            battery_health_status_code: parse_field(&fields, 11)?,
+            battery_voltage: parse_field(&fields, 12)?,
+            discharge_rate: parse_field(&fields, 13)?,
+        })
+    }
+
+    /// Decode a message from raw bytes received from a Go peer
+    pub fn decode_go_bytes(data: &[u8]) -> Result<Self> {
+        let text = std::str::from_utf8(data).map_err(|_| {
+            ETPError::Serialization(SerializationError::InvalidTextField {
+                field: "message",
+                value: String::from_utf8_lossy(data).into_owned(),
+            })
+        })?;
+        Self::decode_go_text(text)
+    }
+}
+
+/// Parse one field, reporting its name on failure
+fn parse_field<T: FromStr>(fields: &[&str], index: usize) -> Result<T> {
+    fields[index].parse().map_err(|_| {
+        ETPError::Serialization(SerializationError::InvalidTextField {
+            field: GO_FIELD_NAMES[index],
+            value: fields[index].to_string(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_matches_go_serialize() {
+        // Output of Go: message.Message{messageType: 3, messageID: 12345, deviceID: 50, ...}.Serialize()
+        let mut bid = ETPMessage::new_bid(12345, 15.5, 10.0);
+        bid.device_id = 50;
+        assert_eq!(
+            bid.encode_go_text(),
+            "3 12345 50 5 15.50 0.00 0.00 0.00 10.00 0 0.00 0 0.00 0.00"
+        );
+    }
+
+    #[test]
+    fn test_decode_go_query_response() {
+        let msg = ETPMessage::decode_go_text("2 54321 50 0 0.00 0.00 13.50 50.00 0.00 0 0.00 0 0.00 0.00").unwrap();
+        assert_eq!(msg.message_type, 2);
+        assert_eq!(msg.message_id, 54321);
+        assert_eq!(msg.device_id, 50);
+        assert_eq!(msg.energy_total, 13.5);
+        assert_eq!(msg.percentage_for_sale, 50.0);
+    }
+
+    #[test]
+    fn test_go_text_roundtrip() {
+        let status = ETPMessage::new_bess_status(42, 100, 18.5, 1, 12.6, 2.5);
+        let decoded = ETPMessage::decode_go_text(&status.encode_go_text()).unwrap();
+        assert_eq!(decoded, status);
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_text() {
+        assert!(matches!(
+            ETPMessage::decode_go_text("3 12345 50"),
+            Err(ETPError::Serialization(SerializationError::InvalidFieldCount { expected: 14, actual: 3 }))
+        ));
+        assert!(matches!(
+            ETPMessage::decode_go_text("3 12345 50 5 abc 0.00 0.00 0.00 10.00 0 0.00 0 0.00 0.00"),
+            Err(ETPError::Serialization(SerializationError::InvalidTextField { field: "bid_price", .. }))
+        ));
+        // Go's FromNetwork maps parse failures to -1, which is never a valid ETP value
+        assert!(ETPMessage::decode_go_text("3 -1 50 5 15.50 0.00 0.00 0.00 10.00 0 0.00 0 0.00 0.00").is_err());
+        assert!(ETPMessage::decode_go_text("12 1 50 5 15.50 0.00 0.00 0.00 10.00 0 0.00 0 0.00 0.00").is_err());
+    }
+
+    #[test]
+    fn test_detects_go_text_start() {
+        assert!(is_go_text_start(b"3 45"));
+        assert!(!is_go_text_start(&[91, 0, 0, 0])); // Length prefix of a wire message
+        assert!(!is_go_text_start(&[b'0', 0, 0, 0])); // 48-byte frame
+        assert!(!is_go_text_start(b" 3 4"));
+    }
+}
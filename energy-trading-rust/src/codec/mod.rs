@@ -0,0 +1,18 @@
+pub mod batch;
+pub mod compact;
+pub mod compression;
+pub mod delivery_frame;
+pub mod framing;
+pub mod inspect;
+pub mod json;
+pub mod legacy_go;
+pub mod wire;
+
+pub use batch::*;
+pub use compact::*;
+pub use compression::*;
+pub use delivery_frame::*;
+pub use framing::*;
+pub use inspect::*;
+pub use legacy_go::*;
+pub use wire::*;
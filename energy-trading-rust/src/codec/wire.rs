@@ -0,0 +1,304 @@
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::error::{ETPError, Result, SerializationError};
+use bytes::{Buf, BufMut, BytesMut};
+use std::convert::TryFrom;
+
+/// Magic bytes at the start of every ETP wire message ("ET")
+pub const ETP_MAGIC: [u8; 2] = *b"ET";
+
+/// Protocol version written by this implementation
+pub const ETP_PROTOCOL_VERSION: u8 = 1;
+
+/// Protocol versions this implementation can decode
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[u8] = &[ETP_PROTOCOL_VERSION];
+
+/// Size of the header (magic, version, message type)
+pub const WIRE_HEADER_SIZE: usize = 4;
+
+/// Size of the 14-field body following the header
+pub const WIRE_BODY_SIZE: usize = 83;
+
+/// Size of the trailing CRC-32
+pub const WIRE_CHECKSUM_SIZE: usize = 4;
+
+/// Total size of an encoded ETP message
+pub const WIRE_MESSAGE_SIZE: usize = WIRE_HEADER_SIZE + WIRE_BODY_SIZE + WIRE_CHECKSUM_SIZE;
+
+/// Encoded width of each field after device_id, ttl through discharge_rate
+pub(crate) const FIELD_WIDTHS: [usize; 11] = [1, 8, 8, 8, 8, 8, 1, 8, 1, 8, 8];
+
+/// Fixed-layout ETP wire codec
+///
+/// Every message is exactly `WIRE_MESSAGE_SIZE` (91) bytes, all integers and
+/// floats little-endian:
+///
+/// | offset | size | field                        |
+/// |--------|------|------------------------------|
+/// | 0      | 2    | magic `"ET"`                 |
+/// | 2      | 1    | protocol version             |
+/// | 3      | 1    | message_type (0-9)           |
+/// | 4      | 8    | message_id (u64)             |
+/// | 12     | 8    | device_id (u64)              |
+/// | 20     | 1    | ttl (u8)                     |
+/// | 21     | 8    | bid_price (f64)              |
+/// | 29     | 8    | sale_price (f64)             |
+/// | 37     | 8    | energy_total (f64)           |
+/// | 45     | 8    | percentage_for_sale (f64)    |
+/// | 53     | 8    | required_energy_amount (f64) |
+/// | 61     | 1    | termination_code (u8)        |
+/// | 62     | 8    | remaining_battery_energy     |
+/// | 70     | 1    | battery_health_status_code   |
+/// | 71     | 8    | battery_voltage (f64)        |
+/// | 79     | 8    | discharge_rate (f64)         |
+/// | 87     | 4    | CRC-32 (IEEE) of bytes 0..87 |
+///
+/// The layout is specified here rather than derived from a serialization
+/// library, so that non-Rust devices can implement it and dependency
+/// upgrades cannot change it.
+impl ETPMessage {
+    /// Encode the message into `buf` using the current protocol version
+    pub fn encode_into(&self, buf: &mut BytesMut) -> Result<()> {
+        self.encode_versioned(ETP_PROTOCOL_VERSION, buf)
+    }
+
+    /// Encode the message into `buf` with an explicit protocol version
+    pub fn encode_versioned(&self, version: u8, buf: &mut BytesMut) -> Result<()> {
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+            return Err(ETPError::Serialization(SerializationError::UnsupportedVersion(version)));
+        }
+        MessageType::try_from(self.message_type).map_err(ETPError::Serialization)?;
+
+        let start = buf.len();
+        buf.reserve(WIRE_MESSAGE_SIZE);
+
+        buf.put_slice(&ETP_MAGIC);
+        buf.put_u8(version);
+        self.put_body(buf);
+
+        let checksum = crc32fast::hash(&buf[start..]);
+        buf.put_u32_le(checksum);
+
+        Ok(())
+    }
+
+    /// Write message_type and the body, the part of the layout shared with batch frames
+    pub(crate) fn put_body(&self, buf: &mut BytesMut) {
+        buf.put_u8(self.message_type);
+        buf.put_u64_le(self.message_id);
+        buf.put_u64_le(self.device_id);
+        buf.put_u8(self.ttl);
+        buf.put_f64_le(self.bid_price);
+        buf.put_f64_le(self.sale_price);
+        buf.put_f64_le(self.energy_total);
+        buf.put_f64_le(self.percentage_for_sale);
+        buf.put_f64_le(self.required_energy_amount);
+        buf.put_u8(self.termination_code);
+        buf.put_f64_le(self.remaining_battery_energy);
+        buf.put_u8(self.battery_health_status_code);
+        buf.put_f64_le(self.battery_voltage);
+        buf.put_f64_le(self.discharge_rate);
+    }
+
+    /// Raw values of the fields after device_id, in wire order; floats as their bits
+    pub(crate) fn field_values(&self) -> [u64; 11] {
+        [
+            self.ttl as u64,
+            self.bid_price.to_bits(),
+            self.sale_price.to_bits(),
+            self.energy_total.to_bits(),
+            self.percentage_for_sale.to_bits(),
+            self.required_energy_amount.to_bits(),
+            self.termination_code as u64,
+            self.remaining_battery_energy.to_bits(),
+            self.battery_health_status_code as u64,
+            self.battery_voltage.to_bits(),
+            self.discharge_rate.to_bits(),
+        ]
+    }
+
+    /// Set the fields after device_id from values produced by `field_values`
+    pub(crate) fn set_field_values(&mut self, values: &[u64; 11]) {
+        self.ttl = values[0] as u8;
+        self.bid_price = f64::from_bits(values[1]);
+        self.sale_price = f64::from_bits(values[2]);
+        self.energy_total = f64::from_bits(values[3]);
+        self.percentage_for_sale = f64::from_bits(values[4]);
+        self.required_energy_amount = f64::from_bits(values[5]);
+        self.termination_code = values[6] as u8;
+        self.remaining_battery_energy = f64::from_bits(values[7]);
+        self.battery_health_status_code = values[8] as u8;
+        self.battery_voltage = f64::from_bits(values[9]);
+        self.discharge_rate = f64::from_bits(values[10]);
+    }
+
+    /// Decode a message from exactly one encoded wire message
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        Self::decode_versioned(data).map(|(message, _version)| message)
+    }
+
+    /// Decode a message and return the protocol version it was encoded with
+    pub fn decode_versioned(data: &[u8]) -> Result<(Self, u8)> {
+        if data.len() != WIRE_MESSAGE_SIZE {
+            return Err(ETPError::Serialization(SerializationError::InvalidMessageSize {
+                expected: WIRE_MESSAGE_SIZE,
+                actual: data.len(),
+            }));
+        }
+
+        let magic = [data[0], data[1]];
+        if magic != ETP_MAGIC {
+            return Err(ETPError::Serialization(SerializationError::InvalidMagic(magic)));
+        }
+
+        let (content, mut trailer) = data.split_at(WIRE_MESSAGE_SIZE - WIRE_CHECKSUM_SIZE);
+        let expected = trailer.get_u32_le();
+        let actual = crc32fast::hash(content);
+        if expected != actual {
+            return Err(ETPError::Serialization(SerializationError::ChecksumMismatch {
+                expected,
+                actual,
+            }));
+        }
+
+        let mut body = &content[ETP_MAGIC.len()..];
+        let version = body.get_u8();
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+            return Err(ETPError::Serialization(SerializationError::UnsupportedVersion(version)));
+        }
+
+        Ok((Self::get_body(&mut body)?, version))
+    }
+
+    /// Read message_type and the body; `body` must hold at least `1 + WIRE_BODY_SIZE` bytes
+    pub(crate) fn get_body(body: &mut &[u8]) -> Result<Self> {
+        let message_type = body.get_u8();
+        MessageType::try_from(message_type).map_err(ETPError::Serialization)?;
+
+        Ok(Self {
+            message_type,
+            message_id: body.get_u64_le(),
+            device_id: body.get_u64_le(),
+            ttl: body.get_u8(),
+            bid_price: body.get_f64_le(),
+            sale_price: body.get_f64_le(),
+            energy_total: body.get_f64_le(),
+            percentage_for_sale: body.get_f64_le(),
+            required_energy_amount: body.get_f64_le(),
+            termination_code: body.get_u8(),
+            remaining_battery_energy: body.get_f64_le(),
+            battery_health_status_code: body.get_u8(),
+            battery_voltage: body.get_f64_le(),
+            discharge_rate: body.get_f64_le(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bid 123: 15.5 c/kWh for 10 kWh
+    const GOLDEN_BID: [u8; WIRE_MESSAGE_SIZE] = [
+        0x45, 0x54, 0x01, 0x03, // magic, version, type
+        0x7b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // message_id
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // device_id
+        0x05, // ttl
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2f, 0x40, // bid_price
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // sale_price
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // energy_total
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // percentage_for_sale
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x40, // required_energy_amount
+        0x00, // termination_code
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // remaining_battery_energy
+        0x00, // battery_health_status_code
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // battery_voltage
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // discharge_rate
+        0x5b, 0x0c, 0xf5, 0x7a, // crc32
+    ];
+
+    /// BESSStatus 42 from device 100: 18.5 kWh, health 1, 12.6 V, 2.5 kW
+    const GOLDEN_BESS_STATUS: [u8; WIRE_MESSAGE_SIZE] = [
+        0x45, 0x54, 0x01, 0x09, // magic, version, type
+        0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // message_id
+        0x64, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // device_id
+        0x05, // ttl
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // bid_price
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // sale_price
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // energy_total
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // percentage_for_sale
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // required_energy_amount
+        0x00, // termination_code
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x32, 0x40, // remaining_battery_energy
+        0x01, // battery_health_status_code
+        0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x29, 0x40, // battery_voltage
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x40, // discharge_rate
+        0xc5, 0xcf, 0x25, 0xe4, // crc32
+    ];
+
+    #[test]
+    fn test_golden_bid_encoding() {
+        let mut buf = BytesMut::new();
+        ETPMessage::new_bid(123, 15.5, 10.0).encode_into(&mut buf).unwrap();
+        assert_eq!(&buf[..], &GOLDEN_BID[..]);
+        assert_eq!(ETPMessage::decode(&GOLDEN_BID).unwrap(), ETPMessage::new_bid(123, 15.5, 10.0));
+    }
+
+    #[test]
+    fn test_golden_bess_status_encoding() {
+        let status = ETPMessage::new_bess_status(42, 100, 18.5, 1, 12.6, 2.5);
+        let mut buf = BytesMut::new();
+        status.encode_into(&mut buf).unwrap();
+        assert_eq!(&buf[..], &GOLDEN_BESS_STATUS[..]);
+        assert_eq!(ETPMessage::decode(&GOLDEN_BESS_STATUS).unwrap(), status);
+    }
+
+    #[test]
+    fn test_encode_appends_to_buffer() {
+        let mut buf = BytesMut::from(&b"xy"[..]);
+        ETPMessage::new_bid(123, 15.5, 10.0).encode_into(&mut buf).unwrap();
+        assert_eq!(buf.len(), 2 + WIRE_MESSAGE_SIZE);
+        assert_eq!(&buf[2..], &GOLDEN_BID[..]);
+    }
+
+    #[test]
+    fn test_decode_rejects_corruption() {
+        let mut corrupted = GOLDEN_BID;
+        corrupted[30] ^= 0xff;
+        assert!(matches!(
+            ETPMessage::decode(&corrupted),
+            Err(ETPError::Serialization(SerializationError::ChecksumMismatch { .. }))
+        ));
+
+        let mut bad_magic = GOLDEN_BID;
+        bad_magic[0] = b'X';
+        assert!(matches!(
+            ETPMessage::decode(&bad_magic),
+            Err(ETPError::Serialization(SerializationError::InvalidMagic(_)))
+        ));
+
+        assert!(matches!(
+            ETPMessage::decode(&GOLDEN_BID[..90]),
+            Err(ETPError::Serialization(SerializationError::InvalidMessageSize { expected: 91, actual: 90 }))
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_version() {
+        let mut future = GOLDEN_BID;
+        future[2] = 2;
+        let checksum = crc32fast::hash(&future[..87]);
+        future[87..].copy_from_slice(&checksum.to_le_bytes());
+        assert!(matches!(
+            ETPMessage::decode(&future),
+            Err(ETPError::Serialization(SerializationError::UnsupportedVersion(2)))
+        ));
+    }
+
+    #[test]
+    fn test_encode_rejects_invalid_type() {
+        let mut buf = BytesMut::new();
+        let invalid = ETPMessage::new_with_type(10, 123, 15.5, 10.0);
+        assert!(invalid.encode_into(&mut buf).is_err());
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod peer;
+pub mod suite;
+
+pub use peer::*;
+pub use suite::*;
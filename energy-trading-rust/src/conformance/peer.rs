@@ -0,0 +1,81 @@
+use crate::etp_message::ETPMessage;
+use crate::error::{ETPError, Result};
+use crate::network::handshake::{Hello, NegotiatedProtocol};
+use crate::network::unicast_connection::UnicastConnection;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration, Instant};
+
+/// What the endpoint did within a wait window
+#[derive(Debug)]
+pub enum PeerReply {
+    Message(ETPMessage, Duration), // Reply and how long after the request it arrived
+    Silent,                        // Nothing arrived in time
+    Closed,                        // The endpoint closed the connection
+}
+
+/// Scripted aggregator the conformance suite drives an endpoint with
+///
+/// Speaks plain length-framed ETP over one TCP connection and never
+/// retries, so whatever the endpoint does is reported as it happened.
+pub struct ReferencePeer {
+    connection: UnicastConnection,
+    device_id: u64,
+}
+
+impl ReferencePeer {
+    /// Connect to the endpoint under test as aggregator `device_id`
+    pub async fn connect(endpoint: SocketAddr, device_id: u64) -> Result<Self> {
+        let stream = TcpStream::connect(endpoint).await?;
+        Ok(Self {
+            connection: UnicastConnection::new(stream),
+            device_id,
+        })
+    }
+
+    /// Get the device id stamped on outgoing messages
+    pub fn device_id(&self) -> u64 {
+        self.device_id
+    }
+
+    /// Exchange hellos with the endpoint, giving up after `window`
+    pub async fn handshake(&mut self, hello: &Hello, window: Duration) -> Result<NegotiatedProtocol> {
+        timeout(window, self.connection.handshake(hello)).await.map_err(|_| {
+            ETPError::Network(format!("No hello within {} ms", window.as_millis()))
+        })?
+    }
+
+    /// Send a message from this peer
+    pub async fn send(&mut self, mut message: ETPMessage) -> Result<()> {
+        message.device_id = self.device_id;
+        self.connection.send_message(message).await
+    }
+
+    /// Send raw frame bytes, e.g. a deliberately malformed message
+    pub async fn send_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.connection.send_frame(frame).await
+    }
+
+    /// Send a message and wait up to `window` for the reply
+    pub async fn request(&mut self, message: ETPMessage, window: Duration) -> Result<PeerReply> {
+        let sent = Instant::now();
+        self.send(message).await?;
+        self.receive_within(window, sent).await
+    }
+
+    /// Wait up to `window` for the next message, timing it from `since`
+    pub async fn receive_within(&mut self, window: Duration, since: Instant) -> Result<PeerReply> {
+        match timeout(window, self.connection.receive_message()).await {
+            Ok(Ok(message)) => Ok(PeerReply::Message(message, since.elapsed())),
+            Ok(Err(ETPError::Io(e))) if is_closed(e.kind()) => Ok(PeerReply::Closed),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Ok(PeerReply::Silent),
+        }
+    }
+}
+
+/// Check whether an IO error means the endpoint hung up
+fn is_closed(kind: ErrorKind) -> bool {
+    matches!(kind, ErrorKind::UnexpectedEof | ErrorKind::ConnectionReset | ErrorKind::BrokenPipe)
+}
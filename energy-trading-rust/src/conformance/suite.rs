@@ -0,0 +1,612 @@
+use crate::codec::wire::WIRE_CHECKSUM_SIZE;
+use crate::conformance::peer::{PeerReply, ReferencePeer};
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::error::{ETPError, Result};
+use crate::network::handshake::{Capabilities, Hello};
+use crate::replay_protection::next_message_id;
+use crate::termination::{TerminationCategory, TerminationCode};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use tokio::time::{Duration, Instant};
+
+/// Device id the suite identifies itself with by default
+pub const CONFORMANCE_DEVICE_ID: u64 = 9_000;
+
+/// Queries sent back to back by the timing scenario
+const BURST_SIZE: usize = 20;
+
+/// Protocol version no endpoint supports, used to provoke a version mismatch
+const UNSUPPORTED_VERSION: u8 = u8::MAX;
+
+/// Scripted exchange the suite drives the endpoint through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Scenario {
+    Handshake,
+    QueryResponse,
+    BidAcceptConfirm,
+    BidReject,
+    Timing,
+    MalformedFrames,
+    TtlExpiry,
+}
+
+impl Scenario {
+    /// All scenarios in the order the suite runs them
+    pub const ALL: [Scenario; 7] = [
+        Scenario::Handshake,
+        Scenario::QueryResponse,
+        Scenario::BidAcceptConfirm,
+        Scenario::BidReject,
+        Scenario::Timing,
+        Scenario::MalformedFrames,
+        Scenario::TtlExpiry,
+    ];
+
+    /// Get the name of this scenario
+    pub fn name(self) -> &'static str {
+        match self {
+            Scenario::Handshake => "handshake",
+            Scenario::QueryResponse => "query-response",
+            Scenario::BidAcceptConfirm => "bid-accept-confirm",
+            Scenario::BidReject => "bid-reject",
+            Scenario::Timing => "timing",
+            Scenario::MalformedFrames => "malformed-frames",
+            Scenario::TtlExpiry => "ttl-expiry",
+        }
+    }
+}
+
+impl fmt::Display for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// Result of checking one rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum RuleOutcome {
+    Passed,
+    Failed(String),  // What the endpoint did wrong
+    Skipped(String), // Why the rule could not be checked
+}
+
+/// Outcome of one protocol rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleResult {
+    pub scenario: Scenario,
+    pub rule: &'static str,
+    pub outcome: RuleOutcome,
+}
+
+/// Pass/fail per rule for one endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct ConformanceReport {
+    pub endpoint: SocketAddr,
+    pub results: Vec<RuleResult>,
+}
+
+impl ConformanceReport {
+    /// Check whether no rule failed; skipped rules do not count against the endpoint
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Get the rules the endpoint failed
+    pub fn failures(&self) -> impl Iterator<Item = &RuleResult> {
+        self.results.iter().filter(|result| matches!(result.outcome, RuleOutcome::Failed(_)))
+    }
+
+    /// Get the outcome of a rule by name
+    pub fn outcome(&self, rule: &str) -> Option<&RuleOutcome> {
+        self.results.iter().find(|result| result.rule == rule).map(|result| &result.outcome)
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "ETP conformance report for {}", self.endpoint)?;
+        let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+        for result in &self.results {
+            let (status, detail) = match &result.outcome {
+                RuleOutcome::Passed => {
+                    passed += 1;
+                    ("PASS", None)
+                }
+                RuleOutcome::Failed(detail) => {
+                    failed += 1;
+                    ("FAIL", Some(detail))
+                }
+                RuleOutcome::Skipped(detail) => {
+                    skipped += 1;
+                    ("SKIP", Some(detail))
+                }
+            };
+            write!(f, "{}  {:<20} {}", status, result.scenario, result.rule)?;
+            match detail {
+                Some(detail) => writeln!(f, ": {}", detail)?,
+                None => writeln!(f)?,
+            }
+        }
+        write!(f, "{} passed, {} failed, {} skipped", passed, failed, skipped)
+    }
+}
+
+/// Collects the rule outcomes of one scenario
+struct Checks {
+    scenario: Scenario,
+    results: Vec<RuleResult>,
+}
+
+impl Checks {
+    fn new(scenario: Scenario) -> Self {
+        Self { scenario, results: Vec::new() }
+    }
+
+    /// Record a rule, returning whether it passed
+    fn check(&mut self, rule: &'static str, result: std::result::Result<(), String>) -> bool {
+        let passed = result.is_ok();
+        let outcome = match result {
+            Ok(()) => RuleOutcome::Passed,
+            Err(detail) => RuleOutcome::Failed(detail),
+        };
+        self.results.push(RuleResult { scenario: self.scenario, rule, outcome });
+        passed
+    }
+
+    /// Record rules that could not be checked
+    fn skip(&mut self, rules: &[&'static str], reason: &str) {
+        for rule in rules {
+            self.results.push(RuleResult {
+                scenario: self.scenario,
+                rule,
+                outcome: RuleOutcome::Skipped(reason.to_string()),
+            });
+        }
+    }
+
+    /// Record whether a reply arrived within the reply type's max delay, returning it if it arrived at all
+    fn reply(&mut self, rule: &'static str, reply: PeerReply, reply_type: MessageType) -> Option<ETPMessage> {
+        let max_ms = reply_type.max_delay_ms();
+        match reply {
+            PeerReply::Message(message, elapsed) => {
+                let elapsed_ms = elapsed.as_millis() as u64;
+                self.check(rule, match elapsed_ms <= max_ms {
+                    true => Ok(()),
+                    false => Err(format!("replied after {} ms, max {} ms", elapsed_ms, max_ms)),
+                });
+                Some(message)
+            }
+            PeerReply::Silent => {
+                self.check(rule, Err(format!("no reply within {} ms", reply_window(reply_type).as_millis())));
+                None
+            }
+            PeerReply::Closed => {
+                self.check(rule, Err("connection closed".to_string()));
+                None
+            }
+        }
+    }
+}
+
+/// Drives any TCP endpoint through scripted ETP scenarios
+///
+/// The suite plays the aggregator. Every scenario opens its own connections
+/// through a `ReferencePeer`, so a frame that makes the endpoint hang up
+/// cannot affect the next scenario.
+pub struct ConformanceSuite {
+    endpoint: SocketAddr,
+    device_id: u64,
+    hello: Option<Hello>,   // None = talk to the endpoint without a handshake
+    relay_hops: u8,         // Relays between the suite and the endpoint
+    accept_bid: (f64, f64), // Price and energy of a bid the endpoint should accept
+    reject_price: f64,      // Price the endpoint should reject as too low
+    silence_window: Duration,
+}
+
+impl ConformanceSuite {
+    /// Create a suite testing the endpoint at `endpoint`
+    pub fn new(endpoint: SocketAddr) -> Self {
+        Self {
+            endpoint,
+            device_id: CONFORMANCE_DEVICE_ID,
+            hello: Some(Hello::default()),
+            relay_hops: 0,
+            accept_bid: (50.0, 1.0),
+            reject_price: 0.01,
+            silence_window: Duration::from_millis(300),
+        }
+    }
+
+    /// Set the device id the suite identifies itself with
+    pub fn set_device_id(&mut self, device_id: u64) {
+        self.device_id = device_id;
+    }
+
+    /// Set the hello sent on every connection, or `None` for endpoints without a handshake
+    pub fn set_hello(&mut self, hello: Option<Hello>) {
+        self.hello = hello;
+    }
+
+    /// Set how many relays sit between the suite and the endpoint; TTL expiry needs at least one
+    pub fn set_relay_hops(&mut self, relay_hops: u8) {
+        self.relay_hops = relay_hops;
+    }
+
+    /// Set the price and energy of a bid the endpoint is expected to accept
+    pub fn set_accept_bid(&mut self, bid_price: f64, energy_amount: f64) {
+        self.accept_bid = (bid_price, energy_amount);
+    }
+
+    /// Set a bid price the endpoint is expected to reject as below its reserve price
+    pub fn set_reject_price(&mut self, bid_price: f64) {
+        self.reject_price = bid_price;
+    }
+
+    /// Set how long to wait before concluding that the endpoint will not answer
+    pub fn set_silence_window(&mut self, silence_window: Duration) {
+        self.silence_window = silence_window;
+    }
+
+    /// Run every scenario
+    pub async fn run(&self) -> ConformanceReport {
+        let mut results = Vec::new();
+        for scenario in Scenario::ALL {
+            results.extend(self.run_scenario(scenario).await);
+        }
+        ConformanceReport { endpoint: self.endpoint, results }
+    }
+
+    /// Run one scenario; a transport error is reported as a failed `transport` rule
+    pub async fn run_scenario(&self, scenario: Scenario) -> Vec<RuleResult> {
+        let mut checks = Checks::new(scenario);
+        let outcome = match scenario {
+            Scenario::Handshake => self.handshake(&mut checks).await,
+            Scenario::QueryResponse => self.query_response(&mut checks).await,
+            Scenario::BidAcceptConfirm => self.bid_accept_confirm(&mut checks).await,
+            Scenario::BidReject => self.bid_reject(&mut checks).await,
+            Scenario::Timing => self.timing(&mut checks).await,
+            Scenario::MalformedFrames => self.malformed_frames(&mut checks).await,
+            Scenario::TtlExpiry => self.ttl_expiry(&mut checks).await,
+        };
+        if let Err(e) = outcome {
+            checks.check("transport", Err(e.to_string()));
+        }
+        checks.results
+    }
+
+    /// Connect a reference peer, performing the handshake if one is configured
+    async fn open(&self) -> Result<ReferencePeer> {
+        let mut peer = ReferencePeer::connect(self.endpoint, self.device_id).await?;
+        if let Some(hello) = &self.hello {
+            peer.handshake(hello, self.silence_window).await?;
+        }
+        Ok(peer)
+    }
+
+    /// Hellos are answered, and answered even when no version is shared
+    async fn handshake(&self, checks: &mut Checks) -> Result<()> {
+        let Some(hello) = &self.hello else {
+            checks.skip(&["hello.negotiated", "hello.mismatch_answered"], "handshake disabled");
+            return Ok(());
+        };
+
+        let mut peer = ReferencePeer::connect(self.endpoint, self.device_id).await?;
+        checks.check("hello.negotiated", match peer.handshake(hello, self.silence_window).await {
+            Ok(protocol) if hello.versions.contains(&protocol.version) => Ok(()),
+            Ok(protocol) => Err(format!("agreed on version {}, which was never offered", protocol.version)),
+            Err(e) => Err(e.to_string()),
+        });
+
+        let mut peer = ReferencePeer::connect(self.endpoint, self.device_id).await?;
+        let unsupported = Hello::new(vec![UNSUPPORTED_VERSION], Capabilities::empty());
+        checks.check("hello.mismatch_answered", match peer.handshake(&unsupported, self.silence_window).await {
+            Err(ETPError::VersionMismatch { .. }) => Ok(()),
+            Ok(protocol) => Err(format!("agreed on unsupported version {}", protocol.version)),
+            Err(e) => Err(e.to_string()),
+        });
+        Ok(())
+    }
+
+    /// A Query is answered with a valid QueryResponse
+    async fn query_response(&self, checks: &mut Checks) -> Result<()> {
+        let mut peer = self.open().await?;
+        let query = ETPMessage::new_query(next_message_id(), self.device_id);
+        let reply = peer.request(query.clone(), reply_window(MessageType::QueryResponse)).await?;
+
+        let Some(response) = checks.reply("query.answered", reply, MessageType::QueryResponse) else {
+            checks.skip(&["query.response_type", "query.message_id", "query.fields_valid"], "no reply");
+            return Ok(());
+        };
+        checks.check("query.response_type", expect_type(&response, MessageType::QueryResponse));
+        checks.check("query.message_id", expect_message_id(&response, query.message_id));
+        checks.check("query.fields_valid", response.validate().map_err(|e| e.to_string()));
+        Ok(())
+    }
+
+    /// A good bid is accepted on the bid's terms, and the confirm needs no answer
+    async fn bid_accept_confirm(&self, checks: &mut Checks) -> Result<()> {
+        const AFTER_ACCEPT: [&str; 3] = ["bid.terms", "confirm.silent", "confirm.still_serving"];
+
+        let mut peer = self.open().await?;
+        let (bid_price, energy_amount) = self.accept_bid;
+        let bid = ETPMessage::new_bid(next_message_id(), bid_price, energy_amount);
+        let reply = peer.request(bid.clone(), reply_window(MessageType::BidAccept)).await?;
+
+        let Some(accept) = checks.reply("bid.answered", reply, MessageType::BidAccept) else {
+            checks.skip(&["bid.accepted", "bid.message_id"], "no reply");
+            checks.skip(&AFTER_ACCEPT, "no reply");
+            return Ok(());
+        };
+        let accepted = checks.check("bid.accepted", expect_type(&accept, MessageType::BidAccept));
+        checks.check("bid.message_id", expect_message_id(&accept, bid.message_id));
+        if !accepted {
+            checks.skip(&AFTER_ACCEPT, "bid was not accepted");
+            return Ok(());
+        }
+
+        let fair = accept.sale_price > 0.0
+            && accept.sale_price <= bid_price
+            && accept.required_energy_amount > 0.0
+            && accept.required_energy_amount <= energy_amount;
+        checks.check("bid.terms", match fair {
+            true => Ok(()),
+            false => Err(format!(
+                "sold {} kWh at {} for a bid of {} kWh at {}",
+                accept.required_energy_amount, accept.sale_price, energy_amount, bid_price
+            )),
+        });
+
+        let confirm = ETPMessage::new_bid_confirm(
+            bid.message_id,
+            self.device_id,
+            accept.sale_price,
+            accept.required_energy_amount,
+        );
+        peer.send(confirm).await?;
+        checks.check("confirm.silent", match peer.receive_within(self.silence_window, Instant::now()).await? {
+            PeerReply::Silent => Ok(()),
+            PeerReply::Closed => Err("connection closed after the confirm".to_string()),
+            PeerReply::Message(message, _) => Err(format!("answered the confirm with {}", describe(&message))),
+        });
+
+        let query = ETPMessage::new_query(next_message_id(), self.device_id);
+        let reply = peer.request(query.clone(), reply_window(MessageType::QueryResponse)).await?;
+        checks.check("confirm.still_serving", expect_answer(reply, query.message_id, MessageType::QueryResponse).map(drop));
+        Ok(())
+    }
+
+    /// A bid below the reserve price is rejected with an economic termination code
+    async fn bid_reject(&self, checks: &mut Checks) -> Result<()> {
+        const AFTER_REPLY: [&str; 4] = ["reject.rejected", "reject.message_id", "reject.registered_code", "reject.economic_code"];
+
+        let mut peer = self.open().await?;
+        let bid = ETPMessage::new_bid(next_message_id(), self.reject_price, self.accept_bid.1);
+        let reply = peer.request(bid.clone(), reply_window(MessageType::BidReject)).await?;
+
+        let Some(reject) = checks.reply("reject.answered", reply, MessageType::BidReject) else {
+            checks.skip(&AFTER_REPLY, "no reply");
+            return Ok(());
+        };
+        let rejected = checks.check("reject.rejected", expect_type(&reject, MessageType::BidReject));
+        checks.check("reject.message_id", expect_message_id(&reject, bid.message_id));
+        if !rejected {
+            checks.skip(&AFTER_REPLY[2..], "bid was not rejected");
+            return Ok(());
+        }
+
+        match reject.termination() {
+            Ok(code) => {
+                checks.check("reject.registered_code", Ok(()));
+                checks.check("reject.economic_code", match code.category() {
+                    TerminationCategory::Economic => Ok(()),
+                    category => Err(format!("{} is a {} code", code.name(), category)),
+                });
+            }
+            Err(e) => {
+                checks.check("reject.registered_code", Err(e.to_string()));
+                checks.skip(&AFTER_REPLY[3..], "unknown termination code");
+            }
+        }
+        Ok(())
+    }
+
+    /// Pipelined queries are all answered within the QueryResponse max delay
+    async fn timing(&self, checks: &mut Checks) -> Result<()> {
+        let mut peer = self.open().await?;
+        let mut pending = HashMap::with_capacity(BURST_SIZE); // message_id -> sent at
+        for _ in 0..BURST_SIZE {
+            let query = ETPMessage::new_query(next_message_id(), self.device_id);
+            pending.insert(query.message_id, Instant::now());
+            peer.send(query).await?;
+        }
+
+        let deadline = Instant::now() + reply_window(MessageType::QueryResponse);
+        let mut slowest = Duration::ZERO;
+        while !pending.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            match peer.receive_within(deadline - now, now).await? {
+                PeerReply::Message(message, _) => {
+                    if let Some(sent) = pending.remove(&message.message_id) {
+                        slowest = slowest.max(sent.elapsed());
+                    }
+                }
+                PeerReply::Silent | PeerReply::Closed => break,
+            }
+        }
+
+        checks.check("timing.burst_answered", match pending.len() {
+            0 => Ok(()),
+            unanswered => Err(format!("{} of {} queries unanswered", unanswered, BURST_SIZE)),
+        });
+        let max_ms = MessageType::QueryResponse.max_delay_ms();
+        checks.check("timing.burst_deadline", match slowest.as_millis() as u64 {
+            slowest_ms if slowest_ms <= max_ms => Ok(()),
+            slowest_ms => Err(format!("slowest reply took {} ms, max {} ms", slowest_ms, max_ms)),
+        });
+        Ok(())
+    }
+
+    /// Malformed frames are never answered and do not take the endpoint down
+    async fn malformed_frames(&self, checks: &mut Checks) -> Result<()> {
+        let valid = ETPMessage::new_query(next_message_id(), self.device_id).serialize()?;
+        let mut bad_magic = valid.clone();
+        bad_magic[0] = b'X';
+        let mut bad_checksum = valid.clone();
+        bad_checksum[valid.len() - 1] ^= 0xff;
+        let mut unknown_type = valid.clone();
+        unknown_type[3] = 200;
+        reseal(&mut unknown_type);
+
+        let cases: [(&'static str, &[u8]); 4] = [
+            ("malformed.bad_magic", &bad_magic),
+            ("malformed.bad_checksum", &bad_checksum),
+            ("malformed.truncated", &valid[..valid.len() / 2]),
+            ("malformed.unknown_type", &unknown_type),
+        ];
+        for (rule, frame) in cases {
+            let mut peer = self.open().await?;
+            peer.send_frame(frame).await?;
+            checks.check(rule, match peer.receive_within(self.silence_window, Instant::now()).await? {
+                PeerReply::Silent | PeerReply::Closed => Ok(()),
+                PeerReply::Message(message, _) => Err(format!("answered with {}", describe(&message))),
+            });
+        }
+
+        let mut peer = self.open().await?;
+        let query = ETPMessage::new_query(next_message_id(), self.device_id);
+        let reply = peer.request(query.clone(), reply_window(MessageType::QueryResponse)).await?;
+        checks.check("malformed.endpoint_survives", expect_answer(reply, query.message_id, MessageType::QueryResponse).map(drop));
+        Ok(())
+    }
+
+    /// A message with hops left is delivered; one without is answered with a TtlExpired Terminate
+    async fn ttl_expiry(&self, checks: &mut Checks) -> Result<()> {
+        let mut peer = self.open().await?;
+        let mut query = ETPMessage::new_query(next_message_id(), self.device_id);
+        query.ttl = self.relay_hops.saturating_add(1);
+        let reply = peer.request(query.clone(), reply_window(MessageType::QueryResponse)).await?;
+        checks.check("ttl.last_hop_delivered", expect_answer(reply, query.message_id, MessageType::QueryResponse).map(drop));
+
+        if self.relay_hops == 0 {
+            checks.skip(&["ttl.expired_notice", "ttl.expired_code"], "no relays between the suite and the endpoint");
+            return Ok(());
+        }
+
+        let (bid_price, energy_amount) = self.accept_bid;
+        let mut bid = ETPMessage::new_bid(next_message_id(), bid_price, energy_amount);
+        bid.ttl = self.relay_hops;
+        let reply = peer.request(bid.clone(), reply_window(MessageType::Terminate)).await?;
+        let notice = match expect_answer(reply, bid.message_id, MessageType::Terminate) {
+            Ok(notice) => {
+                checks.check("ttl.expired_notice", Ok(()));
+                notice
+            }
+            Err(detail) => {
+                checks.check("ttl.expired_notice", Err(detail));
+                checks.skip(&["ttl.expired_code"], "no Terminate notice");
+                return Ok(());
+            }
+        };
+        checks.check("ttl.expired_code", match notice.termination() {
+            Ok(TerminationCode::TtlExpired) => Ok(()),
+            Ok(code) => Err(format!("expected TtlExpired, got {}", code.name())),
+            Err(e) => Err(e.to_string()),
+        });
+        Ok(())
+    }
+}
+
+/// How long to wait for a reply; twice its max delay, so late replies are reported as late rather than missing
+fn reply_window(reply_type: MessageType) -> Duration {
+    Duration::from_millis(reply_type.max_delay_ms() * 2)
+}
+
+/// Check a reply's message type
+fn expect_type(message: &ETPMessage, expected: MessageType) -> std::result::Result<(), String> {
+    match message.message_type == expected.as_u8() {
+        true => Ok(()),
+        false => Err(format!("expected {}, got {}", expected.name(), describe(message))),
+    }
+}
+
+/// Check that a reply echoes the request's message_id
+fn expect_message_id(message: &ETPMessage, message_id: u64) -> std::result::Result<(), String> {
+    match message.message_id == message_id {
+        true => Ok(()),
+        false => Err(format!("expected message_id {}, got {}", message_id, message.message_id)),
+    }
+}
+
+/// Check that a request was answered with the expected type and message_id, at any speed
+fn expect_answer(reply: PeerReply, message_id: u64, expected: MessageType) -> std::result::Result<ETPMessage, String> {
+    match reply {
+        PeerReply::Message(message, _) => {
+            expect_type(&message, expected)?;
+            expect_message_id(&message, message_id)?;
+            Ok(message)
+        }
+        PeerReply::Silent => Err(format!("no {} within {} ms", expected.name(), reply_window(expected).as_millis())),
+        PeerReply::Closed => Err("connection closed".to_string()),
+    }
+}
+
+/// Describe a message for failure details
+fn describe(message: &ETPMessage) -> String {
+    match message.kind() {
+        Ok(kind @ (MessageType::BidReject | MessageType::Terminate | MessageType::DeviceFailure)) => {
+            format!("{} (termination code {})", kind.name(), message.termination_code)
+        }
+        Ok(kind) => kind.name().to_string(),
+        Err(_) => format!("message type {}", message.message_type),
+    }
+}
+
+/// Recompute the trailing CRC-32 after tampering with a wire message
+fn reseal(frame: &mut [u8]) {
+    let (content, trailer) = frame.split_at_mut(frame.len() - WIRE_CHECKSUM_SIZE);
+    trailer.copy_from_slice(&crc32fast::hash(content).to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_counts_failures_only() {
+        let report = ConformanceReport {
+            endpoint: "127.0.0.1:7000".parse().unwrap(),
+            results: vec![
+                RuleResult { scenario: Scenario::QueryResponse, rule: "query.answered", outcome: RuleOutcome::Passed },
+                RuleResult {
+                    scenario: Scenario::TtlExpiry,
+                    rule: "ttl.expired_notice",
+                    outcome: RuleOutcome::Skipped("no relays".to_string()),
+                },
+            ],
+        };
+        assert!(report.passed());
+        assert!(report.to_string().ends_with("1 passed, 0 failed, 1 skipped"));
+
+        let mut failing = report.clone();
+        failing.results[0].outcome = RuleOutcome::Failed("no reply within 1000 ms".to_string());
+        assert!(!failing.passed());
+        assert_eq!(failing.failures().count(), 1);
+        assert!(failing.to_string().contains("FAIL  query-response       query.answered: no reply within 1000 ms"));
+    }
+
+    #[test]
+    fn test_reseal_produces_valid_checksum() {
+        let mut frame = ETPMessage::new_query(1, 2).serialize().unwrap();
+        frame[3] = 200;
+        reseal(&mut frame);
+        assert!(matches!(
+            ETPMessage::deserialize(&frame),
+            Err(ETPError::Serialization(crate::error::SerializationError::InvalidMessageType(200)))
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const HOURS_PER_YEAR: f64 = 24.0 * 365.0;
+
+/// Lowest state of health for each `battery_health_status` code
+///
+/// 0=excellent, 1=good, 2=fair; anything below the last entry is 3=poor.
+pub const HEALTH_THRESHOLDS: [f64; 3] = [0.95, 0.9, 0.8];
+
+/// How a battery pack wears out
+///
+/// Capacity fades with every full equivalent cycle, spread evenly so that the
+/// pack reaches `end_of_life_health` after `cycle_life` cycles, and with
+/// age. The wear cost of a discharged kWh is the replacement cost spread over
+/// the energy the pack discharges in its cycle life.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DegradationModel {
+    pub cycle_life: f64,             // Full equivalent cycles until end of life
+    pub end_of_life_health: f64,     // State of health at end of life
+    pub calendar_fade_per_year: f64, // State of health lost per year of age
+    pub replacement_cost: f64,       // cents per kWh of rated capacity, 0 if unknown
+}
+
+impl Default for DegradationModel {
+    fn default() -> Self {
+        Self {
+            cycle_life: 6000.0,
+            end_of_life_health: 0.8,
+            calendar_fade_per_year: 0.02,
+            replacement_cost: 0.0,
+        }
+    }
+}
+
+impl DegradationModel {
+    /// Get the state of health lost per full equivalent cycle
+    pub fn fade_per_cycle(&self) -> f64 {
+        (1.0 - self.end_of_life_health) / self.cycle_life
+    }
+
+    /// Get the wear cost of drawing one kWh from the cells (cents)
+    pub fn wear_cost_per_kwh(&self) -> f64 {
+        self.replacement_cost / self.cycle_life
+    }
+
+    /// Get the state of health lost by aging for `elapsed`
+    pub fn calendar_fade(&self, elapsed: Duration) -> f64 {
+        self.calendar_fade_per_year * elapsed.as_secs_f64() / 3600.0 / HOURS_PER_YEAR
+    }
+}
+
+/// Accumulated wear and trading result of a battery
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatteryWear {
+    pub state_of_health: f64,   // Remaining fraction of the rated capacity
+    pub equivalent_cycles: f64, // Full equivalent cycles discharged
+    pub energy_throughput: f64, // kWh drawn from the cells
+    pub wear_cost: f64,         // cents
+    pub revenue: f64,           // cents earned by sales
+}
+
+impl Default for BatteryWear {
+    fn default() -> Self {
+        Self {
+            state_of_health: 1.0,
+            equivalent_cycles: 0.0,
+            energy_throughput: 0.0,
+            wear_cost: 0.0,
+            revenue: 0.0,
+        }
+    }
+}
+
+impl BatteryWear {
+    /// Get the revenue left after wear (cents)
+    pub fn profit(&self) -> f64 {
+        self.revenue - self.wear_cost
+    }
+}
+
+/// Result of a settled sale, with battery wear counted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeOutcome {
+    pub energy_amount: f64, // kWh delivered
+    pub sale_price: f64,    // cents/kWh
+    pub revenue: f64,       // cents
+    pub wear_cost: f64,     // cents
+}
+
+impl TradeOutcome {
+    /// Get the revenue left after wear (cents)
+    pub fn profit(&self) -> f64 {
+        self.revenue - self.wear_cost
+    }
+
+    /// Check whether the sale made money once wear is counted
+    pub fn is_profitable(&self) -> bool {
+        self.profit() > 0.0
+    }
+}
+
+/// A battery's health status code changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthEvent {
+    pub device_id: u64,
+    pub previous_status: u8,
+    pub status: u8,
+    pub state_of_health: f64,
+}
+
+/// Get the `battery_health_status` code for a state of health
+pub fn health_status_code(state_of_health: f64) -> u8 {
+    HEALTH_THRESHOLDS
+        .iter()
+        .position(|threshold| state_of_health >= *threshold)
+        .unwrap_or(HEALTH_THRESHOLDS.len()) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fade_and_wear_cost() {
+        let model = DegradationModel { replacement_cost: 30000.0, ..Default::default() };
+        assert!((model.fade_per_cycle() * model.cycle_life - 0.2).abs() < 1e-9);
+        assert_eq!(model.wear_cost_per_kwh(), 5.0);
+        assert!((model.calendar_fade(Duration::from_secs(365 * 24 * 3600)) - 0.02).abs() < 1e-9);
+
+        assert_eq!(health_status_code(1.0), 0);
+        assert_eq!(health_status_code(0.9), 1);
+        assert_eq!(health_status_code(0.85), 2);
+        assert_eq!(health_status_code(0.5), 3);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::battery_model::hours;
+use crate::error::{ETPError, Result};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// Interval over which a trade's energy is delivered
+///
+/// The window is split into equal steps of `interval`, each delivering at
+/// its `profile` power. A single-step profile is a flat delivery.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeliveryWindow {
+    pub start: SystemTime,
+    pub interval: Duration, // Length of each profile step
+    pub profile: Vec<f64>,  // kW for each step
+}
+
+impl DeliveryWindow {
+    /// Create a window delivering `profile` kW, one value per `interval`
+    pub fn new(start: SystemTime, interval: Duration, profile: Vec<f64>) -> Self {
+        Self { start, interval, profile }
+    }
+
+    /// Create a window delivering a constant `power` kW for `duration`
+    pub fn flat(start: SystemTime, duration: Duration, power: f64) -> Self {
+        Self::new(start, duration, vec![power])
+    }
+
+    /// Check that the profile is usable: at least one step, a non-zero interval and finite, non-negative powers
+    pub fn validate(&self) -> Result<()> {
+        if self.profile.is_empty() || self.interval.is_zero() {
+            return Err(ETPError::Validation("Delivery window has no duration".to_string()));
+        }
+        if self.profile.iter().any(|power| !power.is_finite() || *power < 0.0) {
+            return Err(ETPError::Validation("Delivery window power must be finite and non-negative".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Get the length of the window
+    pub fn duration(&self) -> Duration {
+        self.interval.saturating_mul(self.profile.len() as u32)
+    }
+
+    /// Get the time delivery ends
+    pub fn end(&self) -> SystemTime {
+        self.start + self.duration()
+    }
+
+    /// Get the energy delivered over the whole window (kWh)
+    pub fn energy(&self) -> f64 {
+        self.profile.iter().sum::<f64>() * hours(self.interval)
+    }
+
+    /// Get the highest power of the profile (kW)
+    pub fn peak_power(&self) -> f64 {
+        self.profile.iter().copied().fold(0.0, f64::max)
+    }
+
+    /// Get the power delivered at `at` (kW), 0 outside the window
+    pub fn power_at(&self, at: SystemTime) -> f64 {
+        let Ok(offset) = at.duration_since(self.start) else {
+            return 0.0;
+        };
+        let step = (offset.as_nanos() / self.interval.as_nanos().max(1)) as usize;
+        self.profile.get(step).copied().unwrap_or(0.0)
+    }
+
+    /// Get the energy due between `from` and `to` (kWh)
+    pub fn energy_between(&self, from: SystemTime, to: SystemTime) -> f64 {
+        self.steps()
+            .map(|(step_start, step_end, power)| {
+                let overlap = step_end.min(to).duration_since(step_start.max(from)).unwrap_or_default();
+                power * hours(overlap)
+            })
+            .sum()
+    }
+
+    /// Check whether two windows share any time
+    pub fn overlaps(&self, other: &DeliveryWindow) -> bool {
+        self.start < other.end() && other.start < self.end()
+    }
+
+    /// Get the start, end and power of each step
+    pub fn steps(&self) -> impl Iterator<Item = (SystemTime, SystemTime, f64)> + '_ {
+        self.profile.iter().enumerate().map(|(i, power)| {
+            let step_start = self.start + self.interval.saturating_mul(i as u32);
+            (step_start, step_start + self.interval, *power)
+        })
+    }
+}
+
+/// A committed delivery in a BESS schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledDelivery {
+    pub trade_id: u64,      // message_id of the bid
+    pub aggregator_id: u64, // Buyer
+    pub window: DeliveryWindow,
+    pub delivered: f64,     // kWh delivered so far
+}
+
+impl ScheduledDelivery {
+    /// Get the energy still to deliver (kWh)
+    pub fn remaining(&self) -> f64 {
+        (self.window.energy() - self.delivered).max(0.0)
+    }
+}
+
+/// Dispatch a BESS has committed to, in commit order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DispatchSchedule {
+    pub deliveries: Vec<ScheduledDelivery>,
+    pub last_run: Option<SystemTime>, // Time the schedule was last discharged up to
+}
+
+impl DispatchSchedule {
+    /// Check whether nothing is scheduled
+    pub fn is_empty(&self) -> bool {
+        self.deliveries.is_empty()
+    }
+
+    /// Get the delivery for a trade
+    pub fn get(&self, trade_id: u64) -> Option<&ScheduledDelivery> {
+        self.deliveries.iter().find(|delivery| delivery.trade_id == trade_id)
+    }
+
+    /// Get the energy committed but not yet delivered (kWh)
+    pub fn committed_energy(&self) -> f64 {
+        self.deliveries.iter().map(ScheduledDelivery::remaining).sum()
+    }
+
+    /// Get the power committed at `at` (kW)
+    pub fn committed_power_at(&self, at: SystemTime) -> f64 {
+        self.deliveries.iter().map(|delivery| delivery.window.power_at(at)).sum()
+    }
+
+    /// Get the highest total power if `window` were added to the schedule (kW)
+    ///
+    /// Power only changes at step boundaries, so those are the only instants checked.
+    pub fn peak_power_with(&self, window: &DeliveryWindow) -> f64 {
+        let overlapping: Vec<_> = self.deliveries.iter().filter(|delivery| delivery.window.overlaps(window)).collect();
+        overlapping
+            .iter()
+            .flat_map(|delivery| delivery.window.steps().map(|(step_start, _, _)| step_start))
+            .chain(window.steps().map(|(step_start, _, _)| step_start))
+            .filter(|at| *at >= window.start && *at < window.end())
+            .map(|at| window.power_at(at) + overlapping.iter().map(|delivery| delivery.window.power_at(at)).sum::<f64>())
+            .fold(0.0, f64::max)
+    }
+
+    /// Add a delivery; the caller checks that the battery can make it
+    pub fn commit(&mut self, trade_id: u64, aggregator_id: u64, window: DeliveryWindow) -> Result<()> {
+        window.validate()?;
+        if self.get(trade_id).is_some() {
+            return Err(ETPError::Validation(format!("Trade {} is already scheduled", trade_id)));
+        }
+        self.deliveries.push(ScheduledDelivery { trade_id, aggregator_id, window, delivered: 0.0 });
+        Ok(())
+    }
+
+    /// Remove a delivery, returning it
+    pub fn cancel(&mut self, trade_id: u64) -> Option<ScheduledDelivery> {
+        let index = self.deliveries.iter().position(|delivery| delivery.trade_id == trade_id)?;
+        Some(self.deliveries.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn test_window_profile_energy() {
+        let start = SystemTime::UNIX_EPOCH + HOUR * 1000;
+        let window = DeliveryWindow::new(start, HOUR, vec![2.0, 4.0, 1.0]);
+        assert_eq!(window.duration(), HOUR * 3);
+        assert_eq!(window.energy(), 7.0);
+        assert_eq!(window.peak_power(), 4.0);
+        assert_eq!(window.power_at(start + HOUR + HOUR / 2), 4.0);
+        assert_eq!(window.power_at(start - HOUR), 0.0);
+        assert_eq!(window.power_at(window.end()), 0.0);
+        assert!((window.energy_between(start + HOUR / 2, start + HOUR * 2) - 5.0).abs() < 1e-9);
+        assert!(window.validate().is_ok());
+        assert!(DeliveryWindow::new(start, HOUR, vec![-1.0]).validate().is_err());
+        assert!(DeliveryWindow::new(start, HOUR, vec![]).validate().is_err());
+    }
+
+    #[test]
+    fn test_schedule_peak_power_counts_overlaps_only() {
+        let start = SystemTime::UNIX_EPOCH + HOUR * 1000;
+        let mut schedule = DispatchSchedule::default();
+        schedule.commit(1, 42, DeliveryWindow::new(start, HOUR, vec![1.0, 3.0])).unwrap();
+        schedule.commit(2, 42, DeliveryWindow::flat(start + HOUR * 5, HOUR, 5.0)).unwrap();
+        assert!(schedule.commit(1, 42, DeliveryWindow::flat(start, HOUR, 1.0)).is_err());
+        assert_eq!(schedule.committed_energy(), 9.0);
+
+        // Overlaps the second step of trade 1 but not trade 2
+        let window = DeliveryWindow::flat(start + HOUR / 2, HOUR * 2, 2.0);
+        assert_eq!(schedule.peak_power_with(&window), 5.0);
+        assert_eq!(schedule.peak_power_with(&DeliveryWindow::flat(start + HOUR * 3, HOUR, 2.0)), 2.0);
+
+        assert_eq!(schedule.cancel(1).unwrap().trade_id, 1);
+        assert!(schedule.cancel(1).is_none());
+        assert_eq!(schedule.committed_energy(), 5.0);
+    }
+}
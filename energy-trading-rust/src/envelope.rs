@@ -0,0 +1,335 @@
+use crate::codec::wire::WIRE_MESSAGE_SIZE;
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::error::{ETPError, Result, SerializationError};
+use bytes::{Buf, BufMut, BytesMut};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand::rngs::OsRng;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Magic bytes at the start of every signed envelope frame ("ES")
+pub const ENVELOPE_MAGIC: [u8; 2] = *b"ES";
+
+/// Domain separation tag prepended to the signed bytes
+const SIGNATURE_CONTEXT: &[u8] = b"ETP-SIG1";
+
+/// Size of an Ed25519 public key
+pub const PUBLIC_KEY_SIZE: usize = 32;
+
+/// Size of an Ed25519 signature
+pub const SIGNATURE_SIZE: usize = 64;
+
+/// Total size of an encoded envelope
+pub const ENVELOPE_SIZE: usize =
+    ENVELOPE_MAGIC.len() + WIRE_MESSAGE_SIZE + PUBLIC_KEY_SIZE + 8 + 8 + SIGNATURE_SIZE;
+
+/// Signed ETP message
+///
+/// Wraps an `ETPMessage` with the sender's Ed25519 public key, a random nonce
+/// and a timestamp. The signature covers the encoded message and all of
+/// these fields, so none of them can be altered in transit. The only
+/// exception is `ttl`, which relays decrement on every hop.
+///
+/// Encoded as the magic `"ES"`, the 91-byte wire message, the public key,
+/// the nonce and timestamp (u64 little-endian) and the signature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedEnvelope {
+    pub message: ETPMessage,
+    pub sender_public_key: [u8; PUBLIC_KEY_SIZE],
+    pub nonce: u64,
+    pub timestamp_ms: u64, // Milliseconds since the Unix epoch
+    pub signature: [u8; SIGNATURE_SIZE],
+}
+
+impl SignedEnvelope {
+    /// Check whether a frame is a signed envelope rather than a bare ETP message
+    pub fn is_envelope_frame(frame: &[u8]) -> bool {
+        frame.len() >= ENVELOPE_MAGIC.len() && frame[..ENVELOPE_MAGIC.len()] == ENVELOPE_MAGIC
+    }
+
+    /// Encode the envelope
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut buf = BytesMut::with_capacity(ENVELOPE_SIZE);
+        buf.put_slice(&ENVELOPE_MAGIC);
+        self.message.encode_into(&mut buf)?;
+        buf.put_slice(&self.sender_public_key);
+        buf.put_u64_le(self.nonce);
+        buf.put_u64_le(self.timestamp_ms);
+        buf.put_slice(&self.signature);
+        Ok(buf.to_vec())
+    }
+
+    /// Decode an envelope without verifying its signature
+    pub fn decode(frame: &[u8]) -> Result<Self> {
+        if frame.len() != ENVELOPE_SIZE {
+            return Err(ETPError::Serialization(SerializationError::InvalidMessageSize {
+                expected: ENVELOPE_SIZE,
+                actual: frame.len(),
+            }));
+        }
+        if !Self::is_envelope_frame(frame) {
+            return Err(ETPError::Serialization(SerializationError::InvalidMagic([frame[0], frame[1]])));
+        }
+
+        let mut body = &frame[ENVELOPE_MAGIC.len()..];
+        let message = ETPMessage::decode(&body[..WIRE_MESSAGE_SIZE])?;
+        body.advance(WIRE_MESSAGE_SIZE);
+
+        let mut sender_public_key = [0u8; PUBLIC_KEY_SIZE];
+        body.copy_to_slice(&mut sender_public_key);
+        let nonce = body.get_u64_le();
+        let timestamp_ms = body.get_u64_le();
+        let mut signature = [0u8; SIGNATURE_SIZE];
+        body.copy_to_slice(&mut signature);
+
+        Ok(Self {
+            message,
+            sender_public_key,
+            nonce,
+            timestamp_ms,
+            signature,
+        })
+    }
+
+    /// Check that the signature was made by `sender_public_key`
+    pub fn verify_signature(&self) -> Result<()> {
+        let key = VerifyingKey::from_bytes(&self.sender_public_key)
+            .map_err(|e| ETPError::Authentication(format!("Invalid public key: {}", e)))?;
+        let signature = Signature::from_bytes(&self.signature);
+        let signed_bytes = signed_bytes(&self.message, &self.sender_public_key, self.nonce, self.timestamp_ms)?;
+
+        key.verify_strict(&signed_bytes, &signature)
+            .map_err(|_| ETPError::Authentication(format!(
+                "Invalid signature on message {} from device {}",
+                self.message.message_id, self.message.device_id
+            )))
+    }
+}
+
+/// Bytes covered by the signature, with the mutable `ttl` zeroed
+fn signed_bytes(message: &ETPMessage, public_key: &[u8; PUBLIC_KEY_SIZE], nonce: u64, timestamp_ms: u64) -> Result<Vec<u8>> {
+    let mut buf = BytesMut::with_capacity(SIGNATURE_CONTEXT.len() + WIRE_MESSAGE_SIZE + PUBLIC_KEY_SIZE + 16);
+    buf.put_slice(SIGNATURE_CONTEXT);
+    ETPMessage { ttl: 0, ..message.clone() }.encode_into(&mut buf)?;
+    buf.put_slice(public_key);
+    buf.put_u64_le(nonce);
+    buf.put_u64_le(timestamp_ms);
+    Ok(buf.to_vec())
+}
+
+/// Current time in milliseconds since the Unix epoch
+pub(crate) fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Signs outgoing ETP messages with an Ed25519 key
+#[derive(Clone)]
+pub struct MessageSigner {
+    signing_key: SigningKey,
+}
+
+impl std::fmt::Debug for MessageSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the secret key
+        f.debug_struct("MessageSigner")
+            .field("public_key", &self.public_key())
+            .finish()
+    }
+}
+
+impl MessageSigner {
+    /// Generate a new random signing key
+    pub fn generate() -> Self {
+        Self {
+            signing_key: SigningKey::generate(&mut OsRng),
+        }
+    }
+
+    /// Create a signer from a 32-byte secret key
+    pub fn from_secret_key(secret_key: &[u8; 32]) -> Self {
+        Self {
+            signing_key: SigningKey::from_bytes(secret_key),
+        }
+    }
+
+    /// Get the public key peers should trust
+    pub fn public_key(&self) -> [u8; PUBLIC_KEY_SIZE] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    /// Sign a message with a fresh nonce and the current time
+    pub fn sign(&self, message: ETPMessage) -> Result<SignedEnvelope> {
+        self.sign_with(message, rand::thread_rng().gen(), unix_time_ms())
+    }
+
+    /// Sign a message with an explicit nonce and timestamp
+    pub fn sign_with(&self, message: ETPMessage, nonce: u64, timestamp_ms: u64) -> Result<SignedEnvelope> {
+        let sender_public_key = self.public_key();
+        let bytes = signed_bytes(&message, &sender_public_key, nonce, timestamp_ms)?;
+        let signature = self.signing_key.sign(&bytes).to_bytes();
+
+        Ok(SignedEnvelope {
+            message,
+            sender_public_key,
+            nonce,
+            timestamp_ms,
+            signature,
+        })
+    }
+}
+
+/// Verification hook for received ETP traffic
+///
+/// Decides which message types must arrive signed and which public keys are
+/// trusted, each for one device. Messages of other types may still arrive
+/// unsigned.
+#[derive(Debug, Clone)]
+pub struct EnvelopeVerifier {
+    trusted_keys: HashMap<[u8; PUBLIC_KEY_SIZE], u64>, // Public key -> device_id it signs for
+    required_types: HashSet<MessageType>,
+}
+
+impl Default for EnvelopeVerifier {
+    /// Require signatures on bids and their accept/confirm answers
+    fn default() -> Self {
+        Self::new([MessageType::Bid, MessageType::BidAccept, MessageType::BidConfirm])
+    }
+}
+
+impl EnvelopeVerifier {
+    /// Create a verifier requiring signatures on the given message types
+    pub fn new(required_types: impl IntoIterator<Item = MessageType>) -> Self {
+        Self {
+            trusted_keys: HashMap::new(),
+            required_types: required_types.into_iter().collect(),
+        }
+    }
+
+    /// Trust messages from `device_id` signed by this public key
+    pub fn trust_key(&mut self, public_key: [u8; PUBLIC_KEY_SIZE], device_id: u64) {
+        self.trusted_keys.insert(public_key, device_id);
+    }
+
+    /// Check whether a message type must be signed
+    pub fn requires_signature(&self, message_type: u8) -> bool {
+        MessageType::try_from(message_type)
+            .map(|t| self.required_types.contains(&t))
+            .unwrap_or(false)
+    }
+
+    /// Accept or reject a bare, unsigned message
+    pub fn verify_unsigned(&self, message: &ETPMessage) -> Result<()> {
+        if self.requires_signature(message.message_type) {
+            return Err(ETPError::Authentication(format!(
+                "Unsigned message type {} from device {}",
+                message.message_type, message.device_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Accept or reject a signed envelope
+    ///
+    /// The key must be trusted for the device the message claims to come from.
+    pub fn verify(&self, envelope: &SignedEnvelope) -> Result<()> {
+        match self.trusted_keys.get(&envelope.sender_public_key) {
+            None => {
+                return Err(ETPError::Authentication(format!(
+                    "Untrusted public key for device {}",
+                    envelope.message.device_id
+                )))
+            }
+            Some(&device_id) if device_id != envelope.message.device_id => {
+                return Err(ETPError::Authentication(format!(
+                    "Key of device {} used by device {}",
+                    device_id, envelope.message.device_id
+                )))
+            }
+            Some(_) => {}
+        }
+        envelope.verify_signature()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = MessageSigner::generate();
+        let envelope = signer.sign(ETPMessage::new_bid(123, 15.5, 10.0)).unwrap();
+
+        let mut verifier = EnvelopeVerifier::default();
+        verifier.trust_key(signer.public_key(), 0);
+        assert!(verifier.verify(&envelope).is_ok());
+    }
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let signer = MessageSigner::from_secret_key(&[7u8; 32]);
+        let envelope = signer.sign_with(ETPMessage::new_bid(123, 15.5, 10.0), 99, 1_700_000_000_000).unwrap();
+        let encoded = envelope.encode().unwrap();
+        assert_eq!(encoded.len(), ENVELOPE_SIZE);
+        assert!(SignedEnvelope::is_envelope_frame(&encoded));
+
+        let decoded = SignedEnvelope::decode(&encoded).unwrap();
+        assert_eq!(decoded, envelope);
+        assert!(decoded.verify_signature().is_ok());
+    }
+
+    #[test]
+    fn test_tampered_message_rejected() {
+        let signer = MessageSigner::generate();
+        let mut envelope = signer.sign(ETPMessage::new_bid(123, 15.5, 10.0)).unwrap();
+        envelope.message.bid_price = 99.0;
+
+        let mut verifier = EnvelopeVerifier::default();
+        verifier.trust_key(signer.public_key(), 0);
+        assert!(matches!(verifier.verify(&envelope), Err(ETPError::Authentication(_))));
+    }
+
+    #[test]
+    fn test_ttl_not_signed() {
+        let signer = MessageSigner::generate();
+        let mut envelope = signer.sign(ETPMessage::new_bid(123, 15.5, 10.0)).unwrap();
+        envelope.message.decrement_ttl();
+        assert!(envelope.verify_signature().is_ok());
+    }
+
+    #[test]
+    fn test_untrusted_key_rejected() {
+        let signer = MessageSigner::generate();
+        let impostor = MessageSigner::generate();
+        let envelope = impostor.sign(ETPMessage::new_bid(123, 15.5, 10.0)).unwrap();
+
+        let mut verifier = EnvelopeVerifier::default();
+        verifier.trust_key(signer.public_key(), 0);
+        assert!(matches!(verifier.verify(&envelope), Err(ETPError::Authentication(_))));
+    }
+
+    #[test]
+    fn test_key_bound_to_device() {
+        let signer = MessageSigner::generate();
+        let mut verifier = EnvelopeVerifier::default();
+        verifier.trust_key(signer.public_key(), 42);
+
+        let mut bid = ETPMessage::new_bid(123, 15.5, 10.0);
+        bid.device_id = 42;
+        assert!(verifier.verify(&signer.sign(bid.clone()).unwrap()).is_ok());
+        bid.device_id = 43; // Trusted key, someone else's device
+        assert!(matches!(verifier.verify(&signer.sign(bid).unwrap()), Err(ETPError::Authentication(_))));
+    }
+
+    #[test]
+    fn test_unsigned_bid_rejected() {
+        let verifier = EnvelopeVerifier::default();
+        assert!(verifier.verify_unsigned(&ETPMessage::new_bid(123, 15.5, 10.0)).is_err());
+        assert!(verifier.verify_unsigned(&ETPMessage::new_query(123, 100)).is_ok());
+    }
+}
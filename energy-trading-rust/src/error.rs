@@ -1,18 +1,47 @@
+use crate::session::SessionState;
+use crate::validation::ValidationViolation;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum SerializationError {
-    #[error("Serialization failed: {0}")]
-    Serialization(bincode::Error),
+    #[error("Invalid magic bytes: {0:02x?}")]
+    InvalidMagic([u8; 2]),
     
-    #[error("Deserialization failed: {0}")]
-    Deserialization(bincode::Error),
+    #[error("Unsupported protocol version: {0}")]
+    UnsupportedVersion(u8),
+    
+    #[error("Checksum mismatch: expected {expected:#010x}, got {actual:#010x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
     
     #[error("Invalid message type: {0}")]
     InvalidMessageType(u8),
     
+    #[error("Invalid termination code: {0}")]
+    InvalidTerminationCode(u8),
+    
     #[error("Invalid message size: expected {expected}, got {actual}")]
     InvalidMessageSize { expected: usize, actual: usize },
+    
+    #[error("Invalid field count: expected {expected}, got {actual}")]
+    InvalidFieldCount { expected: usize, actual: usize },
+    
+    #[error("Invalid value for field {field}: {value:?}")]
+    InvalidTextField { field: &'static str, value: String },
+    
+    #[error("Invalid batch entry {index}: {reason}")]
+    InvalidBatchEntry { index: usize, reason: &'static str },
+    
+    #[error("Invalid compact message {field}: {reason}")]
+    InvalidCompactField { field: &'static str, reason: &'static str },
+    
+    #[error("Invalid delivery frame: {0}")]
+    InvalidDeliveryFrame(&'static str),
+    
+    #[error("Invalid compressed frame: {0}")]
+    Decompression(String),
+    
+    #[error("Frame of {size} bytes exceeds the {max}-byte limit")]
+    FrameTooLarge { size: usize, max: usize },
 }
 
 #[derive(Error, Debug)]
@@ -23,6 +52,9 @@ pub enum ETPError {
     #[error("Message validation failed: {0}")]
     Validation(String),
     
+    #[error("Message validation failed: {}", join_violations(.0))]
+    InvalidFields(Vec<ValidationViolation>),
+    
     #[error("Timing constraint violated: {message_type} took {elapsed_ms}ms, max allowed {max_ms}ms")]
     TimingViolation {
         message_type: u8,
@@ -30,6 +62,25 @@ pub enum ETPError {
         max_ms: u64,
     },
     
+    #[error("Protocol version mismatch: local supports {local:?}, peer supports {remote:?}")]
+    VersionMismatch {
+        local: Vec<u8>,
+        remote: Vec<u8>,
+    },
+    
+    #[error("Protocol violation: message type {message_type} not allowed while {state}: {reason}")]
+    ProtocolViolation {
+        state: SessionState,
+        message_type: u8,
+        reason: &'static str,
+    },
+    
+    #[error("Authentication failed: {0}")]
+    Authentication(String),
+    
+    #[error("Replayed message rejected: {0}")]
+    Replay(String),
+    
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     
@@ -46,4 +97,9 @@ pub enum ETPError {
     JsonSerialization(#[from] serde_json::Error),
 }
 
+/// Format violations as a comma-separated list
+fn join_violations(violations: &[ValidationViolation]) -> String {
+    violations.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
 pub type Result<T> = std::result::Result<T, ETPError>;
@@ -1,7 +1,116 @@
+use crate::codec::wire::WIRE_MESSAGE_SIZE;
 use crate::error::{ETPError, Result, SerializationError};
+use crate::termination::TerminationCode;
+use crate::validation::ValidationRules;
+use bytes::BytesMut;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
 use std::time::Instant;
 
+/// Hop count given to newly created messages
+pub const DEFAULT_TTL: u8 = 5;
+
+/// ETP message types 0-9 as defined in the research paper
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum MessageType {
+    Register = 0,
+    Query = 1,
+    QueryResponse = 2,
+    Bid = 3,
+    BidAccept = 4,
+    BidConfirm = 5,
+    BidReject = 6,
+    Terminate = 7,
+    DeviceFailure = 8,
+    BESSStatus = 9,
+}
+
+impl MessageType {
+    /// All message types in wire order
+    pub const ALL: [MessageType; 10] = [
+        MessageType::Register,
+        MessageType::Query,
+        MessageType::QueryResponse,
+        MessageType::Bid,
+        MessageType::BidAccept,
+        MessageType::BidConfirm,
+        MessageType::BidReject,
+        MessageType::Terminate,
+        MessageType::DeviceFailure,
+        MessageType::BESSStatus,
+    ];
+
+    /// Get the wire value of this message type
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Get the name of this message type as used in the research paper
+    pub fn name(self) -> &'static str {
+        match self {
+            MessageType::Register => "Register",
+            MessageType::Query => "Query",
+            MessageType::QueryResponse => "QueryResponse",
+            MessageType::Bid => "Bid",
+            MessageType::BidAccept => "BidAccept",
+            MessageType::BidConfirm => "BidConfirm",
+            MessageType::BidReject => "BidReject",
+            MessageType::Terminate => "Terminate",
+            MessageType::DeviceFailure => "DeviceFailure",
+            MessageType::BESSStatus => "BESSStatus",
+        }
+    }
+
+    /// Get the maximum allowed delay in milliseconds for this message type
+    pub fn max_delay_ms(self) -> u64 {
+        match self {
+            MessageType::DeviceFailure => 200, // Highest priority
+            MessageType::BidAccept | MessageType::BidConfirm | MessageType::BidReject => 500, // High priority
+            MessageType::QueryResponse => 500, // High priority
+            MessageType::BESSStatus => 2000,   // Medium priority
+            MessageType::Register => 5000,     // Low priority
+            _ => 1000,                         // Default for other message types
+        }
+    }
+
+    /// Get the priority level for this message type (lower number = higher priority)
+    pub fn priority(self) -> u8 {
+        match self {
+            MessageType::DeviceFailure => 0, // Very high priority
+            MessageType::BidAccept | MessageType::BidConfirm | MessageType::BidReject => 5, // High priority
+            MessageType::QueryResponse => 50, // Medium priority
+            MessageType::BESSStatus => 60,    // Medium priority
+            MessageType::Register => 80,      // Low priority
+            _ => 50,                          // Default for other message types
+        }
+    }
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = SerializationError;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        MessageType::ALL
+            .get(value as usize)
+            .copied()
+            .ok_or(SerializationError::InvalidMessageType(value))
+    }
+}
+
+impl From<MessageType> for u8 {
+    fn from(message_type: MessageType) -> Self {
+        message_type.as_u8()
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 /// Energy Trading Protocol (ETP) Message
 /// 
 /// Based on the research paper "Communication requirements for enabling real-time energy trading
@@ -47,7 +156,7 @@ impl ETPMessage {
             message_type,
             message_id,
             device_id: 0, // Will be set by sender
-            ttl: 5, // Default TTL
+            ttl: DEFAULT_TTL,
             bid_price,
             sale_price: 0.0,
             energy_total: 0.0,
@@ -72,7 +181,7 @@ impl ETPMessage {
             message_type: 0,
             message_id,
             device_id,
-            ttl: 5,
+            ttl: DEFAULT_TTL,
             bid_price: 0.0,
             sale_price: 0.0,
             energy_total: 0.0,
@@ -92,7 +201,7 @@ impl ETPMessage {
             message_type: 1,
             message_id,
             device_id,
-            ttl: 5,
+            ttl: DEFAULT_TTL,
             bid_price: 0.0,
             sale_price: 0.0,
             energy_total: 0.0,
@@ -112,7 +221,7 @@ impl ETPMessage {
             message_type: 2,
             message_id,
             device_id,
-            ttl: 5,
+            ttl: DEFAULT_TTL,
             bid_price: 0.0,
             sale_price: 0.0,
             energy_total,
@@ -132,7 +241,7 @@ impl ETPMessage {
             message_type: 4,
             message_id,
             device_id,
-            ttl: 5,
+            ttl: DEFAULT_TTL,
             bid_price: 0.0,
             sale_price,
             energy_total: 0.0,
@@ -152,7 +261,7 @@ impl ETPMessage {
             message_type: 5,
             message_id,
             device_id,
-            ttl: 5,
+            ttl: DEFAULT_TTL,
             bid_price: 0.0,
             sale_price,
             energy_total: 0.0,
@@ -167,18 +276,18 @@ impl ETPMessage {
     }
 
     /// Create a new BidReject message (type 6)
-    pub fn new_bid_reject(message_id: u64, device_id: u64, termination_code: u8) -> Self {
+    pub fn new_bid_reject(message_id: u64, device_id: u64, termination_code: TerminationCode) -> Self {
         Self {
             message_type: 6,
             message_id,
             device_id,
-            ttl: 5,
+            ttl: DEFAULT_TTL,
             bid_price: 0.0,
             sale_price: 0.0,
             energy_total: 0.0,
             percentage_for_sale: 0.0,
             required_energy_amount: 0.0,
-            termination_code,
+            termination_code: termination_code.as_u8(),
             remaining_battery_energy: 0.0,
             battery_health_status_code: 0,
             battery_voltage: 0.0,
@@ -187,18 +296,18 @@ impl ETPMessage {
     }
 
     /// Create a new Terminate message (type 7)
-    pub fn new_terminate(message_id: u64, device_id: u64, termination_code: u8) -> Self {
+    pub fn new_terminate(message_id: u64, device_id: u64, termination_code: TerminationCode) -> Self {
         Self {
             message_type: 7,
             message_id,
             device_id,
-            ttl: 5,
+            ttl: DEFAULT_TTL,
             bid_price: 0.0,
             sale_price: 0.0,
             energy_total: 0.0,
             percentage_for_sale: 0.0,
             required_energy_amount: 0.0,
-            termination_code,
+            termination_code: termination_code.as_u8(),
             remaining_battery_energy: 0.0,
             battery_health_status_code: 0,
             battery_voltage: 0.0,
@@ -207,18 +316,18 @@ impl ETPMessage {
     }
 
     /// Create a new DeviceFailure message (type 8)
-    pub fn new_device_failure(message_id: u64, device_id: u64, termination_code: u8) -> Self {
+    pub fn new_device_failure(message_id: u64, device_id: u64, termination_code: TerminationCode) -> Self {
         Self {
             message_type: 8,
             message_id,
             device_id,
-            ttl: 5,
+            ttl: DEFAULT_TTL,
             bid_price: 0.0,
             sale_price: 0.0,
             energy_total: 0.0,
             percentage_for_sale: 0.0,
             required_energy_amount: 0.0,
-            termination_code,
+            termination_code: termination_code.as_u8(),
             remaining_battery_energy: 0.0,
             battery_health_status_code: 0,
             battery_voltage: 0.0,
@@ -232,7 +341,7 @@ impl ETPMessage {
             message_type: 9,
             message_id,
             device_id,
-            ttl: 5,
+            ttl: DEFAULT_TTL,
             bid_price: 0.0,
             sale_price: 0.0,
             energy_total: 0.0,
@@ -246,65 +355,48 @@ impl ETPMessage {
         }
     }
 
-    /// Serialize the message to binary format
+    /// Serialize the message to the fixed-layout ETP wire format
     pub fn serialize(&self) -> Result<Vec<u8>> {
-        bincode::serialize(self)
-            .map_err(|e| ETPError::Serialization(SerializationError::Serialization(e)))
+        let mut buf = BytesMut::with_capacity(WIRE_MESSAGE_SIZE);
+        self.encode_into(&mut buf)?;
+        Ok(buf.to_vec())
     }
 
-    /// Deserialize the message from binary format
+    /// Deserialize the message from the fixed-layout ETP wire format
     pub fn deserialize(data: &[u8]) -> Result<Self> {
-        bincode::deserialize(data)
-            .map_err(|e| ETPError::Serialization(SerializationError::Deserialization(e)))
+        Self::decode(data)
     }
 
-    /// Validate the message structure
-    pub fn validate(&self) -> Result<()> {
-        // Validate message type (0-9)
-        if self.message_type > 9 {
-            return Err(ETPError::Validation(format!("Invalid message type: {}", self.message_type)));
-        }
-
-        // Validate TTL
-        if self.ttl == 0 {
-            return Err(ETPError::Validation("Message TTL is 0".to_string()));
-        }
+    /// Get the typed message type, failing for values outside 0-9
+    pub fn kind(&self) -> Result<MessageType> {
+        MessageType::try_from(self.message_type).map_err(ETPError::Serialization)
+    }
 
-        // Validate energy values are non-negative
-        if self.energy_total < 0.0 || self.percentage_for_sale < 0.0 || self.required_energy_amount < 0.0 {
-            return Err(ETPError::Validation("Energy values cannot be negative".to_string()));
-        }
+    /// Get the typed termination code, failing for values outside the registry
+    pub fn termination(&self) -> Result<TerminationCode> {
+        TerminationCode::try_from(self.termination_code).map_err(ETPError::Serialization)
+    }
 
-        // Validate percentage is within 0-100 range
-        if self.percentage_for_sale > 100.0 {
-            return Err(ETPError::Validation("Percentage for sale cannot exceed 100%".to_string()));
-        }
+    /// Validate the message against the default rules
+    pub fn validate(&self) -> Result<()> {
+        self.validate_with(&ValidationRules::default())
+    }
 
-        Ok(())
+    /// Validate the message against deployment-specific rules
+    ///
+    /// Fails with `ETPError::InvalidFields` listing every broken rule.
+    pub fn validate_with(&self, rules: &ValidationRules) -> Result<()> {
+        rules.validate(self)
     }
 
     /// Get the maximum allowed delay in milliseconds for this message type
     pub fn get_max_delay_ms(&self) -> u64 {
-        match self.message_type {
-            8 => 200,  // DeviceFailure - highest priority
-            4 | 5 | 6 => 500,  // BidAccept, BidConfirm, BidReject - high priority
-            2 => 500,  // QueryResponse - high priority
-            9 => 2000, // BESSStatus - medium priority
-            0 => 5000, // Register - low priority
-            _ => 1000, // Default for other message types
-        }
+        self.kind().map(MessageType::max_delay_ms).unwrap_or(1000) // Default for unknown types
     }
 
     /// Get the priority level for this message type (lower number = higher priority)
     pub fn get_priority(&self) -> u8 {
-        match self.message_type {
-            8 => 0,  // DeviceFailure - very high priority
-            4 | 5 | 6 => 5,  // BidAccept, BidConfirm, BidReject - high priority
-            2 => 50, // QueryResponse - medium priority
-            9 => 60, // BESSStatus - medium priority
-            0 => 80, // Register - low priority
-            _ => 50, // Default for other message types
-        }
+        self.kind().map(MessageType::priority).unwrap_or(50) // Default for unknown types
     }
 
     /// Decrement the TTL
@@ -341,6 +433,35 @@ impl ETPMessage {
     }
 }
 
+/// One-line summary showing only the fields meaningful for the message type
+impl fmt::Display for ETPMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Ok(kind) = self.kind() else {
+            return write!(f, "message type {} #{} from device {}", self.message_type, self.message_id, self.device_id);
+        };
+        write!(f, "{} #{} from device {} (ttl {})", kind, self.message_id, self.device_id, self.ttl)?;
+        match kind {
+            MessageType::Register | MessageType::Query => Ok(()),
+            MessageType::QueryResponse => {
+                write!(f, ": {:.2} kWh total, {:.2}% for sale", self.energy_total, self.percentage_for_sale)
+            }
+            MessageType::Bid => write!(f, ": {:.2} c/kWh for {:.2} kWh", self.bid_price, self.required_energy_amount),
+            MessageType::BidAccept | MessageType::BidConfirm => {
+                write!(f, ": {:.2} kWh at {:.2} c/kWh", self.required_energy_amount, self.sale_price)
+            }
+            MessageType::BidReject | MessageType::Terminate | MessageType::DeviceFailure => match self.termination() {
+                Ok(code) => write!(f, ": {} ({})", code.name(), code.as_u8()),
+                Err(_) => write!(f, ": unknown code {}", self.termination_code),
+            },
+            MessageType::BESSStatus => write!(
+                f,
+                ": {:.2} kWh remaining, health {}, {:.2} V, {:.2} kW",
+                self.remaining_battery_energy, self.battery_health_status_code, self.battery_voltage, self.discharge_rate
+            ),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -373,7 +494,7 @@ mod tests {
 
     #[test]
     fn test_timing_constraints() {
-        let device_failure = ETPMessage::new_device_failure(123, 100, 1);
+        let device_failure = ETPMessage::new_device_failure(123, 100, TerminationCode::BatteryFault);
         assert_eq!(device_failure.get_max_delay_ms(), 200);
 
         let bid_accept = ETPMessage::new_bid_accept(123, 100, 18.0, 5.0);
@@ -397,4 +518,17 @@ mod tests {
         }
         assert!(msg.is_expired());
     }
+
+    #[test]
+    fn test_display_summary() {
+        let bid = ETPMessage::new_bid(42, 18.0, 10.0);
+        assert_eq!(bid.to_string(), "Bid #42 from device 0 (ttl 5): 18.00 c/kWh for 10.00 kWh");
+
+        let reject = ETPMessage::new_bid_reject(42, 7, TerminationCode::PriceBelowReserve);
+        assert_eq!(reject.to_string(), "BidReject #42 from device 7 (ttl 5): PriceBelowReserve (1)");
+
+        let mut unknown = ETPMessage::new_query(1, 2);
+        unknown.message_type = 42;
+        assert_eq!(unknown.to_string(), "message type 42 #1 from device 2");
+    }
 }
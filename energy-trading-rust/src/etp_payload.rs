@@ -0,0 +1,300 @@
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::error::{ETPError, Result};
+use crate::termination::TerminationCode;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// QueryResponse payload (type 2)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryResponsePayload {
+    pub energy_total: f64,        // kWh available
+    pub percentage_for_sale: f64, // % available for trading
+}
+
+/// Bid payload (type 3)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BidPayload {
+    pub bid_price: f64,              // cents/kWh
+    pub required_energy_amount: f64, // kWh required
+}
+
+/// BidAccept and BidConfirm payload (types 4 and 5)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SalePayload {
+    pub sale_price: f64,    // cents/kWh
+    pub energy_amount: f64, // kWh sold
+}
+
+/// BidReject, Terminate and DeviceFailure payload (types 6, 7 and 8)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TerminationPayload {
+    pub termination_code: u8, // Raw, so codes added by newer peers still decode
+}
+
+impl TerminationPayload {
+    /// Get the typed termination code, failing for values outside the registry
+    pub fn code(&self) -> Result<TerminationCode> {
+        TerminationCode::try_from(self.termination_code).map_err(ETPError::Serialization)
+    }
+}
+
+/// BESSStatus payload (type 9)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BESSStatusPayload {
+    pub remaining_battery_energy: f64,  // kWh remaining
+    pub battery_health_status_code: u8, // 0=excellent, 1=good, 2=fair, 3=poor
+    pub battery_voltage: f64,           // V
+    pub discharge_rate: f64,            // kW
+}
+
+/// Strongly typed ETP payload
+///
+/// Each variant carries only the fields that are meaningful for its message type,
+/// so reading e.g. a bid price from a BidReject is a compile error rather than a
+/// silent zero.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EtpPayload {
+    Register,
+    Query,
+    QueryResponse(QueryResponsePayload),
+    Bid(BidPayload),
+    BidAccept(SalePayload),
+    BidConfirm(SalePayload),
+    BidReject(TerminationPayload),
+    Terminate(TerminationPayload),
+    DeviceFailure(TerminationPayload),
+    BESSStatus(BESSStatusPayload),
+}
+
+impl EtpPayload {
+    /// Get the message type carried by this payload
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            EtpPayload::Register => MessageType::Register,
+            EtpPayload::Query => MessageType::Query,
+            EtpPayload::QueryResponse(_) => MessageType::QueryResponse,
+            EtpPayload::Bid(_) => MessageType::Bid,
+            EtpPayload::BidAccept(_) => MessageType::BidAccept,
+            EtpPayload::BidConfirm(_) => MessageType::BidConfirm,
+            EtpPayload::BidReject(_) => MessageType::BidReject,
+            EtpPayload::Terminate(_) => MessageType::Terminate,
+            EtpPayload::DeviceFailure(_) => MessageType::DeviceFailure,
+            EtpPayload::BESSStatus(_) => MessageType::BESSStatus,
+        }
+    }
+
+    /// Extract the typed payload from a flat message
+    ///
+    /// Fields that are not used by the message type are dropped, e.g. the
+    /// `bid_price` of a BidReject or the `termination_code` of a Bid.
+    pub fn from_flat(message: &ETPMessage) -> Result<Self> {
+        let payload = match message.kind()? {
+            MessageType::Register => EtpPayload::Register,
+            MessageType::Query => EtpPayload::Query,
+            MessageType::QueryResponse => EtpPayload::QueryResponse(QueryResponsePayload {
+                energy_total: message.energy_total,
+                percentage_for_sale: message.percentage_for_sale,
+            }),
+            MessageType::Bid => EtpPayload::Bid(BidPayload {
+                bid_price: message.bid_price,
+                required_energy_amount: message.required_energy_amount,
+            }),
+            MessageType::BidAccept => EtpPayload::BidAccept(SalePayload {
+                sale_price: message.sale_price,
+                energy_amount: message.required_energy_amount,
+            }),
+            MessageType::BidConfirm => EtpPayload::BidConfirm(SalePayload {
+                sale_price: message.sale_price,
+                energy_amount: message.required_energy_amount,
+            }),
+            MessageType::BidReject => EtpPayload::BidReject(TerminationPayload {
+                termination_code: message.termination_code,
+            }),
+            MessageType::Terminate => EtpPayload::Terminate(TerminationPayload {
+                termination_code: message.termination_code,
+            }),
+            MessageType::DeviceFailure => EtpPayload::DeviceFailure(TerminationPayload {
+                termination_code: message.termination_code,
+            }),
+            MessageType::BESSStatus => EtpPayload::BESSStatus(BESSStatusPayload {
+                remaining_battery_energy: message.remaining_battery_energy,
+                battery_health_status_code: message.battery_health_status_code,
+                battery_voltage: message.battery_voltage,
+                discharge_rate: message.discharge_rate,
+            }),
+        };
+        Ok(payload)
+    }
+
+    /// Write this payload into the matching fields of a flat message
+    ///
+    /// Sets `message_type` and the payload fields; all other payload fields are zeroed.
+    fn write_flat(&self, message: &mut ETPMessage) {
+        message.message_type = self.message_type().as_u8();
+        message.bid_price = 0.0;
+        message.sale_price = 0.0;
+        message.energy_total = 0.0;
+        message.percentage_for_sale = 0.0;
+        message.required_energy_amount = 0.0;
+        message.termination_code = 0;
+        message.remaining_battery_energy = 0.0;
+        message.battery_health_status_code = 0;
+        message.battery_voltage = 0.0;
+        message.discharge_rate = 0.0;
+
+        match self {
+            EtpPayload::Register | EtpPayload::Query => {}
+            EtpPayload::QueryResponse(p) => {
+                message.energy_total = p.energy_total;
+                message.percentage_for_sale = p.percentage_for_sale;
+            }
+            EtpPayload::Bid(p) => {
+                message.bid_price = p.bid_price;
+                message.required_energy_amount = p.required_energy_amount;
+            }
+            EtpPayload::BidAccept(p) | EtpPayload::BidConfirm(p) => {
+                message.sale_price = p.sale_price;
+                message.required_energy_amount = p.energy_amount;
+            }
+            EtpPayload::BidReject(p) | EtpPayload::Terminate(p) | EtpPayload::DeviceFailure(p) => {
+                message.termination_code = p.termination_code;
+            }
+            EtpPayload::BESSStatus(p) => {
+                message.remaining_battery_energy = p.remaining_battery_energy;
+                message.battery_health_status_code = p.battery_health_status_code;
+                message.battery_voltage = p.battery_voltage;
+                message.discharge_rate = p.discharge_rate;
+            }
+        }
+    }
+}
+
+/// Typed ETP message
+///
+/// Header fields shared by every message type plus a typed payload. Converts
+/// to and from the flat 14-field `ETPMessage` used on the wire; a round trip
+/// keeps the header and the payload fields of the message type, and zeroes
+/// every other field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypedETPMessage {
+    pub message_id: u64,
+    pub device_id: u64,
+    pub ttl: u8,
+    pub payload: EtpPayload,
+}
+
+impl TypedETPMessage {
+    /// Create a new typed message with the default TTL
+    pub fn new(message_id: u64, device_id: u64, payload: EtpPayload) -> Self {
+        Self {
+            message_id,
+            device_id,
+            ttl: 5, // Default TTL
+            payload,
+        }
+    }
+
+    /// Get the message type of this message
+    pub fn message_type(&self) -> MessageType {
+        self.payload.message_type()
+    }
+
+    /// Convert to the flat research-paper layout
+    pub fn to_flat(&self) -> ETPMessage {
+        let mut message = ETPMessage::new_with_type(0, self.message_id, 0.0, 0.0);
+        message.device_id = self.device_id;
+        message.ttl = self.ttl;
+        self.payload.write_flat(&mut message);
+        message
+    }
+}
+
+impl TryFrom<ETPMessage> for TypedETPMessage {
+    type Error = ETPError;
+
+    fn try_from(message: ETPMessage) -> Result<Self> {
+        TypedETPMessage::try_from(&message)
+    }
+}
+
+impl TryFrom<&ETPMessage> for TypedETPMessage {
+    type Error = ETPError;
+
+    fn try_from(message: &ETPMessage) -> Result<Self> {
+        Ok(Self {
+            message_id: message.message_id,
+            device_id: message.device_id,
+            ttl: message.ttl,
+            payload: EtpPayload::from_flat(message)?,
+        })
+    }
+}
+
+impl From<TypedETPMessage> for ETPMessage {
+    fn from(message: TypedETPMessage) -> Self {
+        message.to_flat()
+    }
+}
+
+impl From<&TypedETPMessage> for ETPMessage {
+    fn from(message: &TypedETPMessage) -> Self {
+        message.to_flat()
+    }
+}
+
+impl ETPMessage {
+    /// Get the typed payload of this message
+    pub fn payload(&self) -> Result<EtpPayload> {
+        EtpPayload::from_flat(self)
+    }
+
+    /// Convert to a typed message
+    pub fn to_typed(&self) -> Result<TypedETPMessage> {
+        TypedETPMessage::try_from(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_matches_message_type() {
+        for message_type in MessageType::ALL {
+            let msg = ETPMessage::new_with_type(message_type.as_u8(), 1, 15.5, 10.0);
+            assert_eq!(msg.payload().unwrap().message_type(), message_type);
+        }
+    }
+
+    #[test]
+    fn test_typed_to_flat_roundtrip() {
+        let typed = TypedETPMessage::new(
+            42,
+            100,
+            EtpPayload::BESSStatus(BESSStatusPayload {
+                remaining_battery_energy: 18.5,
+                battery_health_status_code: 1,
+                battery_voltage: 12.6,
+                discharge_rate: 2.5,
+            }),
+        );
+        let flat = ETPMessage::from(&typed);
+        assert_eq!(flat, ETPMessage::new_bess_status(42, 100, 18.5, 1, 12.6, 2.5));
+        assert_eq!(TypedETPMessage::try_from(flat).unwrap(), typed);
+    }
+
+    #[test]
+    fn test_invalid_type_rejected() {
+        let msg = ETPMessage::new_with_type(10, 1, 15.5, 10.0);
+        assert!(msg.to_typed().is_err());
+    }
+
+    #[test]
+    fn test_unused_fields_are_dropped() {
+        let mut reject = ETPMessage::new_bid_reject(7, 100, TerminationCode::PriceBelowReserve);
+        reject.bid_price = 99.0;
+        let typed = reject.to_typed().unwrap();
+        assert_eq!(typed.payload, EtpPayload::BidReject(TerminationPayload { termination_code: 1 }));
+        assert_eq!(typed.to_flat().bid_price, 0.0);
+    }
+}
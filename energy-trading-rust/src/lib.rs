@@ -1,15 +1,41 @@
 pub mod etp_message;
+pub mod etp_payload;
+pub mod termination;
+pub mod codec;
+pub mod envelope;
+pub mod replay_protection;
+pub mod validation;
 pub mod error;
+pub mod battery_model;
+pub mod degradation;
+pub mod delivery;
+pub mod reservation;
+pub mod pricing;
 pub mod bess_node;
 pub mod aggregator_node;
 pub mod network;
 pub mod bess_tcp_server;
+pub mod session;
+pub mod conformance;
 // pub mod database; // Temporarily disabled - complex SQLx integration
 
 pub use etp_message::*;
+pub use etp_payload::*;
+pub use termination::*;
+pub use codec::*;
+pub use envelope::*;
+pub use replay_protection::*;
+pub use validation::*;
 pub use error::*;
+pub use battery_model::*;
+pub use degradation::*;
+pub use delivery::*;
+pub use reservation::*;
+pub use pricing::*;
 pub use bess_node::*;
 pub use aggregator_node::*;
 pub use network::*;
 pub use bess_tcp_server::*;
+pub use session::*;
+pub use conformance::*;
 // pub use database::*; // Temporarily disabled
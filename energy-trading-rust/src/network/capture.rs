@@ -0,0 +1,262 @@
+use crate::codec::inspect::{parse_hex, to_hex};
+use crate::error::{ETPError, Result, SerializationError};
+use crate::codec::framing::WireFormat;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Capture file format version written by this implementation
+pub const CAPTURE_VERSION: u8 = 1;
+
+/// Direction of a captured frame, seen from the recording endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureDirection {
+    Sent,
+    Received,
+}
+
+/// First line of a capture file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureHeader {
+    pub etp_capture: u8, // Format version
+    pub started_at_ms: u64, // Milliseconds since the Unix epoch
+    pub local_addr: Option<String>,
+    pub peer_addr: Option<String>,
+}
+
+impl CaptureHeader {
+    /// Create a header for a capture starting now
+    pub fn new(local_addr: Option<String>, peer_addr: Option<String>) -> Self {
+        let started_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            etp_capture: CAPTURE_VERSION,
+            started_at_ms,
+            local_addr,
+            peer_addr,
+        }
+    }
+}
+
+/// One frame as it crossed the wire, without the length prefix
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    pub at_us: u64, // Microseconds since the capture started
+    pub direction: CaptureDirection,
+    pub format: WireFormat,
+    #[serde(serialize_with = "serialize_hex", deserialize_with = "deserialize_hex")]
+    pub frame: Vec<u8>,
+}
+
+/// A recorded ETP session
+///
+/// Stored as JSON lines: the `CaptureHeader`, then one `CaptureRecord` per
+/// frame in the order the recording endpoint saw them. Frames are hex, so
+/// `etp-decode --hex` can read them once extracted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    pub header: CaptureHeader,
+    pub records: Vec<CaptureRecord>,
+}
+
+impl Capture {
+    /// Read a capture file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a capture from JSON lines
+    pub fn read_from(reader: impl BufRead) -> Result<Self> {
+        let mut lines = reader.lines().filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()));
+        let header: CaptureHeader = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => return Err(invalid_capture("empty file")),
+        };
+        if header.etp_capture != CAPTURE_VERSION {
+            return Err(invalid_capture(&format!("unsupported version {}", header.etp_capture)));
+        }
+        let records = lines
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect::<Result<Vec<CaptureRecord>>>()?;
+        Ok(Self { header, records })
+    }
+
+    /// Write the capture as JSON lines
+    pub fn write_to(&self, mut writer: impl Write) -> Result<()> {
+        writeln!(writer, "{}", serde_json::to_string(&self.header)?)?;
+        for record in &self.records {
+            writeln!(writer, "{}", serde_json::to_string(record)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Work for a capture's writer thread
+enum WriterCommand {
+    Line(String),
+    Sync(mpsc::Sender<()>), // Answered once everything before it is written
+}
+
+/// Writes every frame of a connection to a capture
+///
+/// Writes happen on a dedicated thread fed by a channel, so recording never
+/// blocks the async connection. Each record is flushed as soon as it is
+/// written, so a capture survives the process crashing mid-session. Clones
+/// append to the same capture, so both halves of a split connection record
+/// into one file.
+#[derive(Clone)]
+pub struct CaptureRecorder {
+    lines: mpsc::Sender<WriterCommand>,
+    started: Instant,
+}
+
+impl CaptureRecorder {
+    /// Start a capture on `writer`, with the header as its first line
+    ///
+    /// The writer thread stops once every clone of the recorder is dropped.
+    pub fn new(writer: impl Write + Send + 'static, header: CaptureHeader) -> Result<Self> {
+        let (lines, commands) = mpsc::channel();
+        lines
+            .send(WriterCommand::Line(serde_json::to_string(&header)?))
+            .map_err(|_| writer_stopped())?;
+        std::thread::Builder::new()
+            .name("etp-capture".to_string())
+            .spawn(move || write_lines(writer, commands))?;
+        Ok(Self {
+            lines,
+            started: Instant::now(),
+        })
+    }
+
+    /// Start a capture in a new file, replacing any existing one
+    pub fn create(path: impl AsRef<Path>, header: CaptureHeader) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), header)
+    }
+
+    /// Append one frame
+    ///
+    /// Fails once the writer thread stopped after a write error.
+    pub fn record(&mut self, direction: CaptureDirection, format: WireFormat, frame: &[u8]) -> Result<()> {
+        let record = CaptureRecord {
+            at_us: self.started.elapsed().as_micros() as u64,
+            direction,
+            format,
+            frame: frame.to_vec(),
+        };
+        let line = serde_json::to_string(&record)?;
+        self.lines.send(WriterCommand::Line(line)).map_err(|_| writer_stopped())
+    }
+
+    /// Wait until every frame recorded so far is written and flushed
+    ///
+    /// Blocks the calling thread, so keep it out of async code.
+    pub fn sync(&self) -> Result<()> {
+        let (done, written) = mpsc::channel();
+        self.lines.send(WriterCommand::Sync(done)).map_err(|_| writer_stopped())?;
+        written.recv().map_err(|_| writer_stopped())
+    }
+}
+
+/// Write lines until every recorder is dropped or a write fails
+fn write_lines(mut writer: impl Write, commands: mpsc::Receiver<WriterCommand>) {
+    for command in commands {
+        match command {
+            WriterCommand::Line(line) => {
+                if let Err(e) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+                    warn!("Capture stopped: {}", e);
+                    return;
+                }
+            }
+            WriterCommand::Sync(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+fn writer_stopped() -> ETPError {
+    std::io::Error::new(std::io::ErrorKind::BrokenPipe, "capture writer stopped").into()
+}
+
+impl std::fmt::Debug for CaptureRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CaptureRecorder").field("started", &self.started).finish_non_exhaustive()
+    }
+}
+
+fn invalid_capture(reason: &str) -> ETPError {
+    ETPError::Serialization(SerializationError::InvalidTextField {
+        field: "capture",
+        value: reason.to_string(),
+    })
+}
+
+pub(crate) fn serialize_hex<S: Serializer>(frame: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&to_hex(frame))
+}
+
+fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse_hex(&text).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::etp_message::ETPMessage;
+    use std::sync::{Arc, Mutex};
+
+    /// Writer whose contents stay readable after the recorder takes it
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_recorded_capture_reads_back() {
+        let buffer = SharedBuffer::default();
+        let header = CaptureHeader::new(Some("127.0.0.1:8080".to_string()), None);
+        let mut recorder = CaptureRecorder::new(buffer.clone(), header.clone()).unwrap();
+        let query = ETPMessage::new_query(1, 2).serialize().unwrap();
+        let response = ETPMessage::new_query_response(1, 3, 50.0, 20.0).serialize().unwrap();
+        recorder.record(CaptureDirection::Received, WireFormat::Etp, &query).unwrap();
+        recorder.record(CaptureDirection::Sent, WireFormat::Etp, &response).unwrap();
+        recorder.sync().unwrap();
+
+        let bytes = buffer.0.lock().unwrap().clone();
+        let capture = Capture::read_from(&bytes[..]).unwrap();
+        assert_eq!(capture.header, header);
+        assert_eq!(capture.records.len(), 2);
+        assert_eq!(capture.records[0].direction, CaptureDirection::Received);
+        assert_eq!(capture.records[1].frame, response);
+        assert!(capture.records[0].at_us <= capture.records[1].at_us);
+
+        let mut rewritten = Vec::new();
+        capture.write_to(&mut rewritten).unwrap();
+        assert_eq!(Capture::read_from(&rewritten[..]).unwrap(), capture);
+    }
+
+    #[test]
+    fn test_invalid_captures_rejected() {
+        assert!(Capture::read_from(&b""[..]).is_err());
+        assert!(Capture::read_from(&b"{\"etp_capture\":9,\"started_at_ms\":0,\"local_addr\":null,\"peer_addr\":null}"[..]).is_err());
+        let header = serde_json::to_string(&CaptureHeader::new(None, None)).unwrap();
+        let bad_frame = format!("{}\n{{\"at_us\":0,\"direction\":\"sent\",\"format\":\"etp\",\"frame\":\"zz\"}}", header);
+        assert!(Capture::read_from(bad_frame.as_bytes()).is_err());
+    }
+}
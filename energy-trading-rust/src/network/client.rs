@@ -0,0 +1,395 @@
+use crate::codec::delivery_frame::{DeliveryFrame, DeliveryFrameKind};
+use crate::codec::wire::SUPPORTED_PROTOCOL_VERSIONS;
+use crate::delivery::{DeliveryWindow, ScheduledDelivery};
+use crate::error::{ETPError, Result};
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::network::handshake::{Capabilities, Hello};
+use crate::network::unicast_connection::{ConnectionReader, ConnectionWriter, Received, UnicastConnection};
+use futures::Stream;
+use std::future::Future;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Unsolicited messages a `Subscription` holds before further ones are dropped
+pub const SUBSCRIPTION_CAPACITY: usize = 256;
+
+/// What a pending request takes as its answer
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Awaited {
+    Answer(MessageType), // An answer valid for a request of this type
+    Schedule,            // A schedule frame
+}
+
+impl Awaited {
+    /// Check whether a received message or frame answers the request
+    fn accepts(self, received: &Received) -> bool {
+        match (self, received) {
+            (Awaited::Answer(request), Received::Message(message)) => {
+                message.kind().is_ok_and(|kind| answers(request).contains(&kind))
+            }
+            (Awaited::Schedule, Received::Delivery(frame)) => frame.kind == DeliveryFrameKind::Schedule,
+            _ => false,
+        }
+    }
+}
+
+/// Requests waiting for their answer, by message id
+type WaiterMap = HashMap<u64, (Awaited, oneshot::Sender<Result<Received>>)>;
+
+/// Waiting requests, `None` once the connection is gone
+type Waiters = Mutex<Option<WaiterMap>>;
+
+/// Client side of an ETP connection with request/response correlation
+///
+/// Responses are matched to requests by `message_id`, so several requests
+/// can be in flight at once and unsolicited messages, such as BESSStatus
+/// pushes, cannot be mistaken for answers. Anything that does not answer a
+/// pending request goes to the client's `Subscription`.
+pub struct EtpClient {
+    writer: tokio::sync::Mutex<ConnectionWriter>,
+    waiters: Arc<Waiters>,
+    deadlines: HashMap<MessageType, Duration>,
+    peer_addr: Option<SocketAddr>,
+    reader_task: JoinHandle<()>,
+}
+
+/// Messages received by an `EtpClient` that answer none of its requests
+///
+/// Holds up to `SUBSCRIPTION_CAPACITY` messages. While it is full, further
+/// unsolicited messages are dropped with a warning, so a subscription that is
+/// not read never holds up answers to requests. Ends when the connection
+/// closes.
+#[derive(Debug)]
+pub struct Subscription {
+    receiver: mpsc::Receiver<ETPMessage>,
+}
+
+impl EtpClient {
+    /// Take over a connection, after its handshake
+    pub fn new(connection: UnicastConnection) -> (Self, Subscription) {
+        let peer_addr = connection.peer_addr().ok();
+        let (reader, writer) = connection.split();
+        let waiters = Arc::new(Mutex::new(Some(HashMap::new())));
+        let (sender, receiver) = mpsc::channel(SUBSCRIPTION_CAPACITY);
+        let reader_task = tokio::spawn(Self::dispatch(reader, waiters.clone(), sender));
+
+        let client = Self {
+            writer: tokio::sync::Mutex::new(writer),
+            waiters,
+            deadlines: HashMap::new(),
+            peer_addr,
+            reader_task,
+        };
+        (client, Subscription { receiver })
+    }
+
+    /// Connect to an ETP server and perform the handshake, offering delivery windows
+    pub async fn connect(addr: SocketAddr) -> Result<(Self, Subscription)> {
+        let mut connection = UnicastConnection::new(TcpStream::connect(addr).await?);
+        connection.handshake(&Hello::new(SUPPORTED_PROTOCOL_VERSIONS.to_vec(), Capabilities::DELIVERY_WINDOWS)).await?;
+        Ok(Self::new(connection))
+    }
+
+    /// Wait `deadline` instead of the answer's max delay for answers to requests of this type
+    pub fn set_deadline(&mut self, message_type: MessageType, deadline: Duration) {
+        self.deadlines.insert(message_type, deadline);
+    }
+
+    /// Get how long a request of this type may wait for its answer
+    ///
+    /// Defaults to the longest max delay of the answers valid for the request,
+    /// e.g. 500ms for a Bid answered by a BidAccept or BidReject.
+    pub fn deadline(&self, message_type: MessageType) -> Duration {
+        self.deadlines.get(&message_type).copied().unwrap_or_else(|| {
+            let max_delay_ms = answers(message_type).iter().map(|answer| answer.max_delay_ms()).max();
+            Duration::from_millis(max_delay_ms.unwrap_or_else(|| message_type.max_delay_ms()))
+        })
+    }
+
+    /// Get peer address
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Check whether the connection is still open
+    pub fn is_connected(&self) -> bool {
+        self.lock_waiters().is_some()
+    }
+
+    /// Send a message that expects no answer, such as a BidConfirm
+    pub async fn send(&self, message: ETPMessage) -> Result<()> {
+        self.writer.lock().await.send_message(message).await
+    }
+
+    /// Send a Query or Bid and wait for the answer with the same `message_id`
+    ///
+    /// Fails with `ETPError::TimingViolation` when no answer arrives within
+    /// the request type's deadline; a late answer goes to the subscription.
+    pub async fn request(&self, message: ETPMessage) -> Result<ETPMessage> {
+        let message_type = message.kind()?;
+        if answers(message_type).is_empty() {
+            return Err(ETPError::Validation(format!("{} messages are not answered", message_type.name())));
+        }
+        let message_id = message.message_id;
+        let deadline = self.deadline(message_type);
+        match self.await_answer(message_id, Awaited::Answer(message_type), deadline, self.send(message)).await? {
+            Received::Message(answer) => Ok(answer),
+            Received::Delivery(_) => Err(ETPError::Validation(format!("message {} was answered with a delivery frame", message_id))),
+        }
+    }
+
+    /// Send a Bid for delivery over `window` and wait for the answer
+    ///
+    /// The window goes out first in a delivery frame; the bid's
+    /// `required_energy_amount` must be the window's energy. Needs a server
+    /// that negotiated `Capabilities::DELIVERY_WINDOWS`.
+    pub async fn request_with_window(&self, bid: ETPMessage, window: &DeliveryWindow) -> Result<ETPMessage> {
+        let frame = DeliveryFrame::window(bid.message_id, bid.device_id, window.clone());
+        self.writer.lock().await.send_delivery(&frame).await?;
+        self.request(bid).await
+    }
+
+    /// Ask the server for its committed deliveries
+    ///
+    /// Waits as long as a Query would.
+    pub async fn request_schedule(&self, message_id: u64, device_id: u64) -> Result<Vec<ScheduledDelivery>> {
+        let deadline = self.deadline(MessageType::Query);
+        let frame = DeliveryFrame::schedule_request(message_id, device_id);
+        let sent = async { self.writer.lock().await.send_delivery(&frame).await };
+        match self.await_answer(message_id, Awaited::Schedule, deadline, sent).await? {
+            Received::Delivery(frame) => Ok(frame.deliveries),
+            _ => Err(ETPError::Validation(format!("schedule request {} was not answered with a schedule", message_id))),
+        }
+    }
+
+    /// Register for the answer to `message_id`, send the request and wait up to `deadline`
+    async fn await_answer(
+        &self,
+        message_id: u64,
+        awaited: Awaited,
+        deadline: Duration,
+        send: impl Future<Output = Result<()>>,
+    ) -> Result<Received> {
+        let (sender, receiver) = oneshot::channel();
+        match self.lock_waiters().as_mut() {
+            None => return Err(connection_closed("connection closed")),
+            Some(waiters) if waiters.contains_key(&message_id) => {
+                return Err(ETPError::Validation(format!("message {} is already awaiting an answer", message_id)));
+            }
+            Some(waiters) => {
+                waiters.insert(message_id, (awaited, sender));
+            }
+        }
+
+        let started = Instant::now();
+        let answered = tokio::time::timeout(deadline, async {
+            send.await?;
+            receiver.await.unwrap_or_else(|_| Err(connection_closed("connection closed")))
+        })
+        .await;
+        match answered {
+            Ok(answer) => {
+                self.forget(message_id);
+                answer
+            }
+            Err(_) => {
+                self.forget(message_id);
+                let message_type = match awaited {
+                    Awaited::Answer(request) => request,
+                    Awaited::Schedule => MessageType::Query, // Waits as long as a Query
+                };
+                Err(ETPError::TimingViolation {
+                    message_type: message_type.as_u8(),
+                    elapsed_ms: started.elapsed().as_millis() as u64,
+                    max_ms: deadline.as_millis() as u64,
+                })
+            }
+        }
+    }
+
+    /// Stop waiting for an answer
+    fn forget(&self, message_id: u64) {
+        if let Some(waiters) = self.lock_waiters().as_mut() {
+            waiters.remove(&message_id);
+        }
+    }
+
+    fn lock_waiters(&self) -> MutexGuard<'_, Option<WaiterMap>> {
+        self.waiters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Route received messages to their requests or the subscription
+    async fn dispatch(mut reader: ConnectionReader, waiters: Arc<Waiters>, subscription: mpsc::Sender<ETPMessage>) {
+        let reason = loop {
+            let received = match reader.receive().await {
+                Ok(received) => received,
+                Err(ETPError::Replay(reason)) => {
+                    warn!("Dropping message from {:?}: {}", reader.peer_addr().ok(), reason);
+                    continue;
+                }
+                Err(ETPError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                    info!("Connection closed by peer: {:?}", reader.peer_addr().ok());
+                    break "connection closed by peer".to_string();
+                }
+                Err(e) => {
+                    warn!("Closing connection to {:?}: {}", reader.peer_addr().ok(), e);
+                    break e.to_string();
+                }
+            };
+
+            // Only an answer valid for the waiting request's type is taken as its answer
+            let message_id = match &received {
+                Received::Message(message) => message.message_id,
+                Received::Delivery(frame) => frame.message_id,
+            };
+            let waiter = match waiters.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+                Some(waiters) if waiters.get(&message_id).is_some_and(|(awaited, _)| awaited.accepts(&received)) => {
+                    waiters.remove(&message_id).map(|(_, waiter)| waiter)
+                }
+                _ => None,
+            };
+            let unanswered = match waiter {
+                Some(waiter) => waiter.send(Ok(received)).err().and_then(Result::ok), // Requester gave up
+                None => Some(received),
+            };
+            match unanswered {
+                Some(Received::Message(message)) => {
+                    if let Err(mpsc::error::TrySendError::Full(message)) = subscription.try_send(message) {
+                        warn!("Subscription full, dropping message {} from {:?}", message.message_id, reader.peer_addr().ok());
+                    }
+                }
+                Some(Received::Delivery(frame)) => {
+                    warn!("Dropping unrequested delivery frame {} from {:?}", frame.message_id, reader.peer_addr().ok())
+                }
+                None => {}
+            }
+        };
+
+        // Fail everything still waiting; the subscription ends as the sender drops
+        let pending = waiters.lock().unwrap_or_else(PoisonError::into_inner).take();
+        for (_, waiter) in pending.into_iter().flat_map(HashMap::into_values) {
+            let _ = waiter.send(Err(connection_closed(&reason)));
+        }
+    }
+}
+
+impl Drop for EtpClient {
+    fn drop(&mut self) {
+        self.reader_task.abort();
+    }
+}
+
+impl Subscription {
+    /// Wait for the next unsolicited message, `None` once the connection closed
+    pub async fn recv(&mut self) -> Option<ETPMessage> {
+        self.receiver.recv().await
+    }
+
+    /// Take an unsolicited message if one has arrived
+    pub fn try_recv(&mut self) -> Option<ETPMessage> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Stream for Subscription {
+    type Item = ETPMessage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ETPMessage>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Get the message types that answer a request of this type
+fn answers(request: MessageType) -> &'static [MessageType] {
+    match request {
+        MessageType::Query => &[MessageType::QueryResponse],
+        MessageType::Bid => &[MessageType::BidAccept, MessageType::BidReject],
+        _ => &[],
+    }
+}
+
+fn connection_closed(reason: &str) -> ETPError {
+    ETPError::Network(format!("ETP connection lost: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_answers_matched_by_message_id() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // A peer that pushes status and answers the two requests in reverse order
+        let peer = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut connection = UnicastConnection::new(stream);
+            let query = connection.receive_message().await.unwrap();
+            let bid = connection.receive_message().await.unwrap();
+            connection.send_message(ETPMessage::new_bess_status(77, 123, 50.0, 1, 12.6, 2.5)).await.unwrap();
+            connection.send_message(ETPMessage::new_bid_accept(bid.message_id, 123, 18.0, 10.0)).await.unwrap();
+            // Right id, wrong type: not an answer to the query
+            connection.send_message(ETPMessage::new_bid_accept(query.message_id, 123, 18.0, 10.0)).await.unwrap();
+            connection.send_message(ETPMessage::new_query_response(query.message_id, 123, 50.0, 20.0)).await.unwrap();
+            connection
+        });
+
+        let connection = UnicastConnection::new(TcpStream::connect(addr).await.unwrap());
+        let (client, mut subscription) = EtpClient::new(connection);
+        let (response, accept) = tokio::join!(
+            client.request(ETPMessage::new_query(1, 42)),
+            client.request(ETPMessage::new_bid(2, 18.0, 10.0)),
+        );
+        let response = response.unwrap();
+        assert_eq!((response.message_type, response.message_id), (2, 1)); // QueryResponse
+        assert_eq!(accept.unwrap().message_id, 2);
+        assert_eq!(subscription.recv().await.unwrap().message_id, 77);
+        let stray = subscription.recv().await.unwrap();
+        assert_eq!((stray.message_type, stray.message_id), (4, 1)); // BidAccept
+
+        drop(peer.await.unwrap());
+        assert!(subscription.recv().await.is_none());
+        assert!(!client.is_connected());
+        assert!(matches!(client.request(ETPMessage::new_query(3, 42)).await, Err(ETPError::Network(_))));
+    }
+
+    #[tokio::test]
+    async fn test_full_subscription_does_not_hold_up_answers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // A peer that floods status pushes before answering
+        let peer = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut connection = UnicastConnection::new(stream);
+            let query = connection.receive_message().await.unwrap();
+            for status_id in 0..SUBSCRIPTION_CAPACITY as u64 + 10 {
+                connection.send_message(ETPMessage::new_bess_status(status_id, 123, 50.0, 1, 12.6, 2.5)).await.unwrap();
+            }
+            connection.send_message(ETPMessage::new_query_response(query.message_id, 123, 50.0, 20.0)).await.unwrap();
+            connection
+        });
+
+        let connection = UnicastConnection::new(TcpStream::connect(addr).await.unwrap());
+        let (client, mut subscription) = EtpClient::new(connection);
+        assert_eq!(client.request(ETPMessage::new_query(1, 42)).await.unwrap().message_type, 2); // QueryResponse
+
+        let mut held = 0;
+        while subscription.try_recv().is_some() {
+            held += 1;
+        }
+        assert_eq!(held, SUBSCRIPTION_CAPACITY); // The overflow was dropped
+        drop(peer.await.unwrap());
+    }
+}
@@ -0,0 +1,345 @@
+use crate::error::{ETPError, Result};
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::network::client::{EtpClient, Subscription};
+use crate::replay_protection::next_message_id;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::sync::OwnedMutexGuard;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep, timeout, Instant, MissedTickBehavior};
+use tracing::{debug, info, warn};
+
+/// How a `BessConnectionPool` looks after its connections
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolSettings {
+    pub heartbeat_interval: Duration, // Query idle connections this often
+    pub connect_timeout: Duration,    // Give up on a connection attempt after this long
+    pub initial_backoff: Duration,    // Wait before retrying a failed connection
+    pub max_backoff: Duration,        // Cap on the wait, which doubles after each failure
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(2),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Health of one pooled BESS connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolMemberStatus {
+    pub device_id: u64,
+    pub addr: SocketAddr,
+    pub connected: bool,
+    pub reconnects: u32,             // Connections made after the first
+    pub consecutive_failures: u32,   // Failed attempts or heartbeats since the last success
+    pub last_query_response: Option<ETPMessage>, // Latest availability from a heartbeat
+    pub last_status: Option<ETPMessage>,         // Latest BESSStatus pushed by the BESS
+}
+
+/// Connection shared by the maintainer task and checkouts, `None` while disconnected
+type SharedClient = Arc<tokio::sync::Mutex<Option<EtpClient>>>;
+
+struct PoolMember {
+    client: SharedClient,
+    status: Arc<Mutex<PoolMemberStatus>>,
+    maintainer: JoinHandle<()>,
+}
+
+impl Drop for PoolMember {
+    fn drop(&mut self) {
+        self.maintainer.abort();
+    }
+}
+
+/// Aggregator-side connections to many `BESSTCPServer`s, keyed by BESS device id
+///
+/// Each member has a task that connects, sends a Query heartbeat whenever
+/// the connection sat idle for a heartbeat interval, and reconnects with
+/// exponential backoff when the connection fails. Callers `checkout` a
+/// connection for exclusive use, so a heartbeat never lands between a Bid
+/// and its BidConfirm.
+pub struct BessConnectionPool {
+    device_id: u64, // Sender of heartbeat queries
+    settings: PoolSettings,
+    members: Mutex<HashMap<u64, PoolMember>>,
+}
+
+/// A pooled connection checked out for exclusive use
+///
+/// Returns to the pool when dropped.
+pub struct PooledConnection {
+    client: OwnedMutexGuard<Option<EtpClient>>,
+    status: Arc<Mutex<PoolMemberStatus>>,
+}
+
+impl BessConnectionPool {
+    /// Create an empty pool for the aggregator with this device id
+    pub fn new(device_id: u64) -> Self {
+        Self::with_settings(device_id, PoolSettings::default())
+    }
+
+    /// Create an empty pool with custom heartbeat and reconnect timing
+    pub fn with_settings(device_id: u64, settings: PoolSettings) -> Self {
+        Self {
+            device_id,
+            settings,
+            members: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start keeping a connection to the BESS at `addr`, replacing any previous one
+    pub fn add(&self, device_id: u64, addr: SocketAddr) {
+        let client: SharedClient = Arc::new(tokio::sync::Mutex::new(None));
+        let status = Arc::new(Mutex::new(PoolMemberStatus {
+            device_id,
+            addr,
+            connected: false,
+            reconnects: 0,
+            consecutive_failures: 0,
+            last_query_response: None,
+            last_status: None,
+        }));
+        let maintainer = tokio::spawn(maintain(self.device_id, self.settings.clone(), client.clone(), status.clone()));
+        self.lock_members().insert(device_id, PoolMember { client, status, maintainer });
+        info!("Pooling connection to BESS {} at {}", device_id, addr);
+    }
+
+    /// Close and forget the connection to a BESS
+    pub fn remove(&self, device_id: u64) -> bool {
+        self.lock_members().remove(&device_id).is_some()
+    }
+
+    /// Get the device ids of every pooled BESS, connected or not
+    pub fn device_ids(&self) -> Vec<u64> {
+        let mut device_ids: Vec<u64> = self.lock_members().keys().copied().collect();
+        device_ids.sort_unstable();
+        device_ids
+    }
+
+    /// Get the number of pooled BESS
+    pub fn len(&self) -> usize {
+        self.lock_members().len()
+    }
+
+    /// Check whether the pool has no BESS
+    pub fn is_empty(&self) -> bool {
+        self.lock_members().is_empty()
+    }
+
+    /// Get the health of a pooled connection
+    pub fn status(&self, device_id: u64) -> Option<PoolMemberStatus> {
+        self.lock_members().get(&device_id).map(|member| lock_status(&member.status).clone())
+    }
+
+    /// Check whether a pooled BESS is currently connected
+    pub fn is_connected(&self, device_id: u64) -> bool {
+        self.status(device_id).is_some_and(|status| status.connected)
+    }
+
+    /// Wait up to `within` for a pooled BESS to be connected
+    pub async fn wait_connected(&self, device_id: u64, within: Duration) -> bool {
+        let deadline = Instant::now() + within;
+        while !self.is_connected(device_id) {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        true
+    }
+
+    /// Take a connection for exclusive use, waiting while another caller holds it
+    ///
+    /// Fails with `ETPError::Network` when the BESS is unknown or not connected.
+    pub async fn checkout(&self, device_id: u64) -> Result<PooledConnection> {
+        let (client, status) = match self.lock_members().get(&device_id) {
+            Some(member) => (member.client.clone(), member.status.clone()),
+            None => return Err(ETPError::Network(format!("BESS {} is not in the connection pool", device_id))),
+        };
+        let mut client = client.lock_owned().await;
+        if !client.as_ref().is_some_and(EtpClient::is_connected) {
+            *client = None;
+            return Err(ETPError::Network(format!("BESS {} is not connected", device_id)));
+        }
+        Ok(PooledConnection { client, status })
+    }
+
+    fn lock_members(&self) -> MutexGuard<'_, HashMap<u64, PoolMember>> {
+        self.members.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for BessConnectionPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BessConnectionPool")
+            .field("device_id", &self.device_id)
+            .field("settings", &self.settings)
+            .field("members", &self.device_ids())
+            .finish()
+    }
+}
+
+impl PooledConnection {
+    /// Drop the connection, e.g. after the peer broke the protocol; the pool reconnects
+    pub fn discard(mut self) {
+        *self.client = None;
+        lock_status(&self.status).connected = false;
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = EtpClient;
+
+    fn deref(&self) -> &EtpClient {
+        self.client.as_ref().expect("checked out connections are connected")
+    }
+}
+
+fn lock_status(status: &Mutex<PoolMemberStatus>) -> MutexGuard<'_, PoolMemberStatus> {
+    status.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Keep one pooled connection up for as long as it stays in the pool
+async fn maintain(aggregator_id: u64, settings: PoolSettings, client: SharedClient, status: Arc<Mutex<PoolMemberStatus>>) {
+    let addr = lock_status(&status).addr;
+    let mut backoff = settings.initial_backoff;
+    let mut connected_before = false;
+    loop {
+        let connected = match timeout(settings.connect_timeout, EtpClient::connect(addr)).await {
+            Ok(connected) => connected,
+            Err(_) => Err(ETPError::Network(format!("connecting to {} timed out", addr))),
+        };
+        let subscription = match connected {
+            Ok((connection, subscription)) => {
+                *client.lock().await = Some(connection);
+                let mut status = lock_status(&status);
+                status.connected = true;
+                status.consecutive_failures = 0;
+                if connected_before {
+                    status.reconnects += 1;
+                }
+                info!("Connected to BESS {} at {}", status.device_id, addr);
+                subscription
+            }
+            Err(e) => {
+                let failures = {
+                    let mut status = lock_status(&status);
+                    status.consecutive_failures += 1;
+                    status.consecutive_failures
+                };
+                warn!("Connecting to BESS at {} failed ({} in a row), retrying in {:?}: {}", addr, failures, backoff, e);
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(settings.max_backoff);
+                continue;
+            }
+        };
+        connected_before = true;
+        backoff = settings.initial_backoff;
+
+        watch(aggregator_id, &settings, &client, &status, subscription).await;
+        *client.lock().await = None;
+        lock_status(&status).connected = false;
+    }
+}
+
+/// Heartbeat a connection and collect its pushes until it fails
+async fn watch(
+    aggregator_id: u64,
+    settings: &PoolSettings,
+    client: &SharedClient,
+    status: &Mutex<PoolMemberStatus>,
+    mut subscription: Subscription,
+) {
+    let mut heartbeat = interval(settings.heartbeat_interval);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    heartbeat.tick().await; // The first tick is immediate
+    loop {
+        tokio::select! {
+            _ = heartbeat.tick() => {
+                // A connection checked out right now is evidently in use
+                let Ok(mut checked) = client.clone().try_lock_owned() else {
+                    continue;
+                };
+                let Some(connection) = checked.as_ref() else {
+                    return; // Discarded
+                };
+                match connection.request(ETPMessage::new_query(next_message_id(), aggregator_id)).await {
+                    Ok(response) => {
+                        let mut status = lock_status(status);
+                        status.consecutive_failures = 0;
+                        status.last_query_response = Some(response);
+                    }
+                    Err(e) => {
+                        let mut status = lock_status(status);
+                        status.consecutive_failures += 1;
+                        warn!("Heartbeat to BESS {} failed, reconnecting: {}", status.device_id, e);
+                        *checked = None;
+                        return;
+                    }
+                }
+            }
+            pushed = subscription.recv() => match pushed {
+                Some(message) if message.kind().ok() == Some(MessageType::BESSStatus) => {
+                    lock_status(status).last_status = Some(message);
+                }
+                Some(message) => debug!("Ignoring unsolicited {}", message),
+                None => return, // Closed by the peer, or discarded
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::unicast_connection::UnicastConnection;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_pool_reconnects_with_backoff() {
+        // Reserve an address, then leave it closed so the first attempts fail
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let settings = PoolSettings {
+            heartbeat_interval: Duration::from_secs(60),
+            initial_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(40),
+            ..PoolSettings::default()
+        };
+        let pool = BessConnectionPool::with_settings(1, settings);
+        pool.add(7, addr);
+        assert!(!pool.wait_connected(7, Duration::from_millis(150)).await);
+        assert!(pool.status(7).unwrap().consecutive_failures >= 2);
+        assert!(matches!(pool.checkout(7).await, Err(ETPError::Network(_))));
+
+        // A peer that answers the handshake and then hangs up
+        let listener = TcpListener::bind(addr).await.unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut connection = UnicastConnection::new(stream);
+                connection.accept_handshake(&Default::default()).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        assert!(pool.wait_connected(7, Duration::from_secs(1)).await);
+        assert_eq!(pool.status(7).unwrap().consecutive_failures, 0);
+
+        // The pool notices the hang-up and connects again
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(pool.status(7).unwrap().reconnects >= 1);
+        assert!(pool.remove(7));
+        assert!(pool.is_empty());
+    }
+}
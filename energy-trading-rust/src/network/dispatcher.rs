@@ -0,0 +1,302 @@
+use crate::etp_message::{ETPMessage, MessageType};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// What to do with a message whose deadline passed while it was queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LatePolicy {
+    Drop, // Discard late status and failure traffic; session messages are flagged, as their sender awaits an answer
+    Flag, // Hand it out with `late` set
+}
+
+/// Queue metrics, shared by every dispatcher created with the same handle
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DispatcherMetrics {
+    pub queue_depths: BTreeMap<u8, usize>, // Queued messages per priority level
+    pub dispatched: u64,
+    pub dropped_late: u64,
+    pub flagged_late: u64,
+}
+
+impl DispatcherMetrics {
+    /// Total number of queued messages
+    pub fn total_queued(&self) -> usize {
+        self.queue_depths.values().sum()
+    }
+
+    fn adjust_depth(&mut self, priority: u8, queued: bool) {
+        let depth = self.queue_depths.entry(priority).or_insert(0);
+        if queued {
+            *depth += 1;
+        } else {
+            *depth = depth.saturating_sub(1);
+        }
+    }
+}
+
+/// Message handed out by the dispatcher
+#[derive(Debug, Clone, PartialEq)]
+pub struct DispatchedMessage {
+    pub message: ETPMessage,
+    pub received_at: Instant,
+    pub deadline: Instant, // received_at + the message type's max delay
+    pub late: bool,        // Deadline had passed when dispatched (LatePolicy::Flag only)
+}
+
+/// Priority the session lane is queued at, that of a Query or Bid
+pub const SESSION_PRIORITY: u8 = 50;
+
+/// Check whether a message moves the session on, as opposed to status and failure traffic
+fn is_session_message(message: &ETPMessage) -> bool {
+    !matches!(message.kind(), Ok(MessageType::BESSStatus | MessageType::DeviceFailure))
+}
+
+/// Queue entry; the heap pops the greatest, so the ordering is reversed
+#[derive(Debug)]
+struct QueuedMessage {
+    priority: u8, // The message type's own priority, for the metrics
+    deadline: Instant,
+    sequence: u64,
+    received_at: Instant,
+    message: ETPMessage,
+}
+
+impl QueuedMessage {
+    fn key(&self) -> (u8, Instant, u64) {
+        if is_session_message(&self.message) {
+            (SESSION_PRIORITY, self.received_at, self.sequence)
+        } else {
+            (self.priority, self.deadline, self.sequence)
+        }
+    }
+}
+
+impl PartialEq for QueuedMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for QueuedMessage {}
+
+impl PartialOrd for QueuedMessage {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedMessage {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+/// Deadline-aware priority queue for received ETP messages
+///
+/// Messages that move the session on keep their arrival order, so a
+/// pipelined Register is handled before the Query behind it. They share one
+/// lane at `SESSION_PRIORITY`. Status and failure traffic is handed out by
+/// priority (`ETPMessage::get_priority`, lower first), then by absolute
+/// deadline (receipt plus `get_max_delay_ms`), then in arrival order. A
+/// DeviceFailure therefore overtakes everything queued, while BESSStatus
+/// waits behind the session lane.
+#[derive(Debug)]
+pub struct MessageDispatcher {
+    queue: BinaryHeap<QueuedMessage>,
+    late_policy: LatePolicy,
+    metrics: Arc<Mutex<DispatcherMetrics>>,
+    next_sequence: u64,
+}
+
+impl MessageDispatcher {
+    /// Create a dispatcher with its own metrics
+    pub fn new(late_policy: LatePolicy) -> Self {
+        Self::with_metrics(late_policy, Arc::default())
+    }
+
+    /// Create a dispatcher reporting into shared metrics, e.g. one per connection of a server
+    pub fn with_metrics(late_policy: LatePolicy, metrics: Arc<Mutex<DispatcherMetrics>>) -> Self {
+        Self {
+            queue: BinaryHeap::new(),
+            late_policy,
+            metrics,
+            next_sequence: 0,
+        }
+    }
+
+    /// Queue a message received just now
+    pub fn push(&mut self, message: ETPMessage) {
+        self.push_received(message, Instant::now());
+    }
+
+    /// Queue a message received at `received_at`
+    pub fn push_received(&mut self, message: ETPMessage, received_at: Instant) {
+        let priority = message.get_priority();
+        let deadline = received_at + Duration::from_millis(message.get_max_delay_ms());
+        self.lock_metrics().adjust_depth(priority, true);
+
+        self.queue.push(QueuedMessage {
+            priority,
+            deadline,
+            sequence: self.next_sequence,
+            received_at,
+            message,
+        });
+        self.next_sequence += 1;
+    }
+
+    /// Take the most urgent message
+    pub fn pop(&mut self) -> Option<DispatchedMessage> {
+        self.pop_at(Instant::now())
+    }
+
+    /// Take the most urgent message, judging lateness at `now`
+    pub fn pop_at(&mut self, now: Instant) -> Option<DispatchedMessage> {
+        while let Some(queued) = self.queue.pop() {
+            let late = now > queued.deadline;
+            let mut metrics = self.lock_metrics();
+            metrics.adjust_depth(queued.priority, false);
+
+            if late && self.late_policy == LatePolicy::Drop && !is_session_message(&queued.message) {
+                metrics.dropped_late += 1;
+                warn!("Dropping message {} type {}: missed its deadline by {}ms",
+                      queued.message.message_id, queued.message.message_type,
+                      (now - queued.deadline).as_millis());
+                continue;
+            }
+
+            if late {
+                metrics.flagged_late += 1;
+            }
+            metrics.dispatched += 1;
+
+            return Some(DispatchedMessage {
+                message: queued.message,
+                received_at: queued.received_at,
+                deadline: queued.deadline,
+                late,
+            });
+        }
+        None
+    }
+
+    /// Number of queued messages
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Check whether nothing is queued
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Get a snapshot of the metrics
+    pub fn metrics(&self) -> DispatcherMetrics {
+        self.lock_metrics().clone()
+    }
+
+    fn lock_metrics(&self) -> std::sync::MutexGuard<'_, DispatcherMetrics> {
+        self.metrics.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for MessageDispatcher {
+    /// Remove still-queued messages from the shared queue depths
+    fn drop(&mut self) {
+        let mut metrics = self.metrics.lock().unwrap_or_else(PoisonError::into_inner);
+        for queued in self.queue.drain() {
+            metrics.adjust_depth(queued.priority, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::termination::TerminationCode;
+
+    #[test]
+    fn test_device_failure_preempts_status() {
+        let mut dispatcher = MessageDispatcher::new(LatePolicy::Flag);
+        dispatcher.push(ETPMessage::new_bess_status(1, 100, 18.5, 1, 12.6, 2.5));
+        dispatcher.push(ETPMessage::new_register(2, 100));
+        dispatcher.push(ETPMessage::new_device_failure(3, 100, TerminationCode::BatteryFault));
+
+        let order: Vec<u64> = std::iter::from_fn(|| dispatcher.pop()).map(|d| d.message.message_id).collect();
+        assert_eq!(order, vec![3, 2, 1]); // The Register is in the session lane, ahead of status traffic
+    }
+
+    #[test]
+    fn test_session_messages_keep_arrival_order() {
+        let mut dispatcher = MessageDispatcher::new(LatePolicy::Flag);
+        dispatcher.push(ETPMessage::new_register(1, 100));
+        dispatcher.push(ETPMessage::new_query(2, 100));
+        dispatcher.push(ETPMessage::new_bess_status(3, 100, 18.5, 1, 12.6, 2.5));
+        dispatcher.push(ETPMessage::new_bid_confirm(4, 100, 18.0, 10.0));
+        dispatcher.push(ETPMessage::new_device_failure(5, 100, TerminationCode::BatteryFault));
+
+        let order: Vec<u64> = std::iter::from_fn(|| dispatcher.pop()).map(|d| d.message.message_id).collect();
+        assert_eq!(order, vec![5, 1, 2, 4, 3]);
+    }
+
+    #[test]
+    fn test_equal_priority_orders_by_deadline() {
+        let mut dispatcher = MessageDispatcher::new(LatePolicy::Flag);
+        let now = Instant::now();
+        dispatcher.push_received(ETPMessage::new_bess_status(1, 100, 18.5, 1, 12.6, 2.5), now);
+        dispatcher.push_received(ETPMessage::new_bess_status(2, 100, 18.5, 1, 12.6, 2.5), now - Duration::from_millis(10));
+        dispatcher.push_received(ETPMessage::new_bess_status(3, 100, 18.5, 1, 12.6, 2.5), now);
+
+        let order: Vec<u64> = std::iter::from_fn(|| dispatcher.pop_at(now)).map(|d| d.message.message_id).collect();
+        assert_eq!(order, vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn test_late_messages_dropped_or_flagged() {
+        let now = Instant::now();
+        let received = now - Duration::from_millis(300);
+
+        let mut dropping = MessageDispatcher::new(LatePolicy::Drop);
+        dropping.push_received(ETPMessage::new_device_failure(1, 100, TerminationCode::BatteryFault), received); // 200ms deadline
+        dropping.push_received(ETPMessage::new_bess_status(2, 100, 18.5, 1, 12.6, 2.5), received); // 2000ms
+        assert_eq!(dropping.pop_at(now).unwrap().message.message_id, 2);
+        assert_eq!(dropping.metrics().dropped_late, 1);
+
+        // A late bid still reaches the receiver, which owes the bidder an answer
+        dropping.push_received(ETPMessage::new_bid(3, 18.0, 10.0), now - Duration::from_millis(1500));
+        let bid = dropping.pop_at(now).unwrap();
+        assert_eq!((bid.message.message_id, bid.late), (3, true));
+        assert_eq!(dropping.metrics().dropped_late, 1);
+
+        let mut flagging = MessageDispatcher::new(LatePolicy::Flag);
+        flagging.push_received(ETPMessage::new_device_failure(1, 100, TerminationCode::BatteryFault), received);
+        assert!(flagging.pop_at(now).unwrap().late);
+        assert_eq!(flagging.metrics().flagged_late, 1);
+    }
+
+    #[test]
+    fn test_queue_depth_metrics() {
+        let metrics = Arc::new(Mutex::new(DispatcherMetrics::default()));
+        let mut first = MessageDispatcher::with_metrics(LatePolicy::Flag, metrics.clone());
+        let mut second = MessageDispatcher::with_metrics(LatePolicy::Flag, metrics.clone());
+        first.push(ETPMessage::new_device_failure(1, 100, TerminationCode::BatteryFault));
+        first.push(ETPMessage::new_bess_status(2, 100, 18.5, 1, 12.6, 2.5));
+        second.push(ETPMessage::new_bess_status(3, 100, 18.5, 1, 12.6, 2.5));
+
+        let snapshot = first.metrics();
+        assert_eq!(snapshot.queue_depths[&0], 1);
+        assert_eq!(snapshot.queue_depths[&60], 2);
+        assert_eq!(snapshot.total_queued(), 3);
+
+        first.pop();
+        drop(second);
+        let snapshot = first.metrics();
+        assert_eq!(snapshot.queue_depths[&0], 0);
+        assert_eq!(snapshot.queue_depths[&60], 1);
+        assert_eq!(snapshot.dispatched, 1);
+    }
+}
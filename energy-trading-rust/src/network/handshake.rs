@@ -0,0 +1,212 @@
+use crate::codec::wire::SUPPORTED_PROTOCOL_VERSIONS;
+use crate::error::{ETPError, Result, SerializationError};
+use bytes::{Buf, BufMut, BytesMut};
+use serde::{Deserialize, Serialize};
+
+/// Magic bytes at the start of every handshake frame ("EH")
+pub const HELLO_MAGIC: [u8; 2] = *b"EH";
+
+/// Optional protocol features a peer can advertise during the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// Payload compression
+    pub const COMPRESSION: Capabilities = Capabilities(1 << 0);
+    /// Signed message envelopes
+    pub const AUTHENTICATION: Capabilities = Capabilities(1 << 1);
+    /// Bids that may be partially filled
+    pub const PARTIAL_FILLS: Capabilities = Capabilities(1 << 2);
+    /// Batch frames carrying several messages
+    pub const BATCHING: Capabilities = Capabilities(1 << 3);
+    /// Compact message encoding
+    pub const COMPACT_ENCODING: Capabilities = Capabilities(1 << 4);
+    /// Delivery frames carrying delivery windows and schedules
+    pub const DELIVERY_WINDOWS: Capabilities = Capabilities(1 << 5);
+
+    /// No capabilities
+    pub const fn empty() -> Self {
+        Capabilities(0)
+    }
+
+    /// Create capabilities from raw bits, keeping unknown bits so they survive a round trip
+    pub const fn from_bits(bits: u32) -> Self {
+        Capabilities(bits)
+    }
+
+    /// Get the raw bits
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Check whether every capability in `other` is present
+    pub const fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Capabilities present in both sets
+    pub const fn intersection(self, other: Capabilities) -> Self {
+        Capabilities(self.0 & other.0)
+    }
+
+    /// Capabilities present in either set
+    pub const fn union(self, other: Capabilities) -> Self {
+        Capabilities(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOr for Capabilities {
+    type Output = Capabilities;
+
+    fn bitor(self, rhs: Capabilities) -> Capabilities {
+        self.union(rhs)
+    }
+}
+
+/// Hello frame exchanged when an ETP connection opens
+///
+/// Encoded as the magic `"EH"`, a version count, the supported versions
+/// and a little-endian u32 capability mask.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    pub versions: Vec<u8>,
+    pub capabilities: Capabilities,
+}
+
+/// Outcome of a successful handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NegotiatedProtocol {
+    pub version: u8,
+    pub capabilities: Capabilities,
+}
+
+impl Default for NegotiatedProtocol {
+    /// Protocol assumed for peers that never send a hello
+    fn default() -> Self {
+        Self {
+            version: crate::codec::wire::ETP_PROTOCOL_VERSION,
+            capabilities: Capabilities::empty(),
+        }
+    }
+}
+
+impl Default for Hello {
+    fn default() -> Self {
+        Self::new(SUPPORTED_PROTOCOL_VERSIONS.to_vec(), Capabilities::empty())
+    }
+}
+
+impl Hello {
+    /// Create a new hello advertising the given versions and capabilities
+    pub fn new(versions: Vec<u8>, capabilities: Capabilities) -> Self {
+        Self { versions, capabilities }
+    }
+
+    /// Check whether a frame is a hello rather than an ETP message
+    pub fn is_hello_frame(frame: &[u8]) -> bool {
+        frame.len() >= HELLO_MAGIC.len() && frame[..HELLO_MAGIC.len()] == HELLO_MAGIC
+    }
+
+    /// Encode the hello frame
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        if self.versions.is_empty() || self.versions.len() > u8::MAX as usize {
+            return Err(ETPError::Validation(format!(
+                "Hello must advertise 1-255 versions, got {}",
+                self.versions.len()
+            )));
+        }
+
+        let mut buf = BytesMut::with_capacity(HELLO_MAGIC.len() + 1 + self.versions.len() + 4);
+        buf.put_slice(&HELLO_MAGIC);
+        buf.put_u8(self.versions.len() as u8);
+        buf.put_slice(&self.versions);
+        buf.put_u32_le(self.capabilities.bits());
+        Ok(buf.to_vec())
+    }
+
+    /// Decode a hello frame
+    pub fn decode(frame: &[u8]) -> Result<Self> {
+        if !Self::is_hello_frame(frame) {
+            let magic = [frame.first().copied().unwrap_or(0), frame.get(1).copied().unwrap_or(0)];
+            return Err(ETPError::Serialization(SerializationError::InvalidMagic(magic)));
+        }
+
+        let mut body = &frame[HELLO_MAGIC.len()..];
+        let count = body.first().copied().unwrap_or(0) as usize;
+        let expected = HELLO_MAGIC.len() + 1 + count + 4;
+        if count == 0 || frame.len() != expected {
+            return Err(ETPError::Serialization(SerializationError::InvalidMessageSize {
+                expected,
+                actual: frame.len(),
+            }));
+        }
+
+        body.advance(1);
+        let versions = body[..count].to_vec();
+        body.advance(count);
+        let capabilities = Capabilities::from_bits(body.get_u32_le());
+
+        Ok(Self { versions, capabilities })
+    }
+
+    /// Agree on the highest common version and the shared capabilities
+    pub fn negotiate(&self, peer: &Hello) -> Result<NegotiatedProtocol> {
+        let version = self
+            .versions
+            .iter()
+            .filter(|version| peer.versions.contains(version))
+            .max()
+            .copied()
+            .ok_or_else(|| ETPError::VersionMismatch {
+                local: self.versions.clone(),
+                remote: peer.versions.clone(),
+            })?;
+
+        Ok(NegotiatedProtocol {
+            version,
+            capabilities: self.capabilities.intersection(peer.capabilities),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hello_roundtrip() {
+        let hello = Hello::new(vec![1, 2], Capabilities::COMPRESSION | Capabilities::PARTIAL_FILLS);
+        let encoded = hello.encode().unwrap();
+        assert!(Hello::is_hello_frame(&encoded));
+        assert_eq!(Hello::decode(&encoded).unwrap(), hello);
+    }
+
+    #[test]
+    fn test_negotiate_highest_common_version() {
+        let local = Hello::new(vec![1, 2, 3], Capabilities::COMPRESSION | Capabilities::AUTHENTICATION);
+        let peer = Hello::new(vec![1, 2], Capabilities::AUTHENTICATION | Capabilities::PARTIAL_FILLS);
+        let negotiated = local.negotiate(&peer).unwrap();
+        assert_eq!(negotiated.version, 2);
+        assert_eq!(negotiated.capabilities, Capabilities::AUTHENTICATION);
+    }
+
+    #[test]
+    fn test_negotiate_no_common_version() {
+        let local = Hello::new(vec![1], Capabilities::empty());
+        let peer = Hello::new(vec![2, 3], Capabilities::empty());
+        match local.negotiate(&peer) {
+            Err(ETPError::VersionMismatch { local, remote }) => {
+                assert_eq!(local, vec![1]);
+                assert_eq!(remote, vec![2, 3]);
+            }
+            other => panic!("Expected VersionMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_truncated_hello() {
+        let encoded = Hello::default().encode().unwrap();
+        assert!(Hello::decode(&encoded[..encoded.len() - 1]).is_err());
+        assert!(Hello::decode(b"ET").is_err());
+    }
+}
@@ -1,7 +1,21 @@
+pub mod capture;
+pub mod client;
+pub mod connection_pool;
+pub mod dispatcher;
+pub mod handshake;
 pub mod multicast_discovery;
+pub mod relay;
+pub mod replay;
 pub mod unicast_connection;
 pub mod websocket_gateway;
 
+pub use capture::*;
+pub use client::*;
+pub use connection_pool::*;
+pub use dispatcher::*;
+pub use handshake::*;
 pub use multicast_discovery::*;
+pub use relay::*;
+pub use replay::*;
 pub use unicast_connection::*;
 pub use websocket_gateway::*;
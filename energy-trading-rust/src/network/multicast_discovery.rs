@@ -1,7 +1,7 @@
 use crate::etp_message::ETPMessage;
 use crate::bess_node::BESSNode;
 use crate::error::Result;
-use rand::Rng;
+use crate::replay_protection::next_message_id;
 use std::collections::HashMap;
 use std::net::{Ipv4Addr, SocketAddr};
 use std::sync::Arc;
@@ -68,7 +68,7 @@ impl MulticastDiscovery {
         for (_device_id, bess_node) in nodes.iter() {
             if bess_node.is_online && bess_node.get_available_energy() > 0.0 {
                 let response = bess_node.generate_query_response(
-                    rand::thread_rng().gen_range(1000..=9999),
+                    next_message_id(),
                     query.device_id,
                 );
                 responses.push(response);
@@ -114,7 +114,7 @@ impl MulticastDiscovery {
     /// Broadcast registration event
     async fn broadcast_registration_event(&self, device_id: u64) -> Result<()> {
         let register_message = ETPMessage::new_register(
-            rand::thread_rng().gen_range(1000..=9999),
+            next_message_id(),
             device_id,
         );
         
@@ -0,0 +1,404 @@
+use crate::codec::batch::MessageBatch;
+use crate::codec::delivery_frame::DeliveryFrame;
+use crate::codec::compression::{compress_frame, decompress_frame, is_compressed_frame};
+use crate::codec::framing::{check_frame_size, LENGTH_PREFIX_SIZE, MAX_FRAME_SIZE};
+use crate::envelope::SignedEnvelope;
+use crate::etp_message::ETPMessage;
+use crate::error::{ETPError, Result};
+use crate::network::handshake::Hello;
+use crate::replay_protection::ReplayCache;
+use crate::termination::TerminationCode;
+use bytes::BytesMut;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tracing::{info, warn, error};
+
+/// What a relay does with a received message
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelayDecision {
+    Forward(ETPMessage), // Pass on to the next hop, TTL already decremented
+    Expired(ETPMessage), // Out of hops; Terminate notice to send back to the sender
+    Loop,                // Already relayed once, drop it
+}
+
+/// Hop-by-hop forwarding rules shared by every connection of a relay
+///
+/// Each hop decrements the TTL like an IP router: a message arriving with a
+/// TTL of 1 or less is not forwarded and the sender gets a Terminate with
+/// `TerminationCode::TtlExpired`. Messages whose
+/// `(device_id, message_type, message_id)` has already passed through the
+/// relay are dropped, which breaks routing loops.
+#[derive(Debug, Clone)]
+pub struct RelayRouter {
+    relay_id: u64,
+    seen: Arc<ReplayCache>,
+}
+
+impl RelayRouter {
+    /// Create a router identifying itself as `relay_id` in Terminate notices
+    pub fn new(relay_id: u64) -> Self {
+        Self {
+            relay_id,
+            seen: Arc::new(ReplayCache::default()),
+        }
+    }
+
+    /// Decide what to do with a received message
+    pub fn route(&self, mut message: ETPMessage) -> RelayDecision {
+        if self.seen.check_message(&message).is_err() {
+            warn!("Relay {} dropping looped message {} from device {}",
+                  self.relay_id, message.message_id, message.device_id);
+            return RelayDecision::Loop;
+        }
+
+        message.decrement_ttl();
+        if message.is_expired() {
+            warn!("Relay {} dropping message {} from device {}: TTL expired",
+                  self.relay_id, message.message_id, message.device_id);
+            return RelayDecision::Expired(ETPMessage::new_terminate(
+                message.message_id,
+                self.relay_id,
+                TerminationCode::TtlExpired,
+            ));
+        }
+
+        RelayDecision::Forward(message)
+    }
+
+    /// Apply `route` to a raw frame, returning the frame to forward and the frames to send back
+    ///
+    /// Hello frames pass through untouched so the end points negotiate with
+    /// each other, and so do delivery frames, which carry no TTL. Signed envelopes stay valid because the TTL is not signed.
+    /// Batches are routed message by message and forwarded without the
+    /// messages that expired or looped. Forwarded frames keep their encoding
+    /// and compression.
+    fn route_frame(&self, frame: &[u8]) -> Result<RoutedFrame> {
+        let mut routed = RoutedFrame::default();
+        if Hello::is_hello_frame(frame) || DeliveryFrame::is_delivery_frame(frame) {
+            routed.forward = Some(frame.to_vec());
+            return Ok(routed);
+        }
+
+        if is_compressed_frame(frame) {
+            let mut routed = self.route_frame(&decompress_frame(frame, MAX_FRAME_SIZE)?)?;
+            routed.forward = routed.forward.map(|inner| compress_frame(&inner)).transpose()?;
+            return Ok(routed);
+        }
+
+        if MessageBatch::is_batch_frame(frame) {
+            let mut batch = MessageBatch::decode(frame)?;
+            for message in std::mem::take(&mut batch.messages) {
+                match self.route(message) {
+                    RelayDecision::Forward(message) => batch.push(message),
+                    RelayDecision::Expired(notice) => routed.replies.push(notice.serialize()?),
+                    RelayDecision::Loop => {}
+                }
+            }
+            if !batch.is_empty() {
+                routed.forward = Some(batch.encode()?);
+            }
+            return Ok(routed);
+        }
+
+        if SignedEnvelope::is_envelope_frame(frame) {
+            let mut envelope = SignedEnvelope::decode(frame)?;
+            match self.route(envelope.message.clone()) {
+                RelayDecision::Forward(message) => {
+                    envelope.message = message;
+                    routed.forward = Some(envelope.encode()?);
+                }
+                RelayDecision::Expired(notice) => routed.replies.push(notice.serialize()?),
+                RelayDecision::Loop => {}
+            }
+            return Ok(routed);
+        }
+
+        let compact = ETPMessage::is_compact_frame(frame);
+        let (message, version) = match compact {
+            true => ETPMessage::decode_compact_versioned(frame)?,
+            false => ETPMessage::decode_versioned(frame)?,
+        };
+        match self.route(message) {
+            RelayDecision::Forward(message) => {
+                let mut buf = BytesMut::with_capacity(frame.len());
+                match compact {
+                    true => message.encode_compact(version, &mut buf)?,
+                    false => message.encode_versioned(version, &mut buf)?,
+                }
+                routed.forward = Some(buf.to_vec());
+            }
+            RelayDecision::Expired(notice) => routed.replies.push(notice.serialize()?),
+            RelayDecision::Loop => {}
+        }
+        Ok(routed)
+    }
+
+    /// Route a raw frame, dropping it if it cannot be routed
+    ///
+    /// A bad frame from one peer must not end the connection for both sides.
+    fn route_or_drop(&self, frame: &[u8]) -> RoutedFrame {
+        self.route_frame(frame).unwrap_or_else(|e| {
+            warn!("Relay {} dropping frame it cannot route: {}", self.relay_id, e);
+            RoutedFrame::default()
+        })
+    }
+}
+
+/// Outcome of routing one frame
+#[derive(Debug, Default)]
+struct RoutedFrame {
+    forward: Option<Vec<u8>>, // Frame for the other side, if anything is left to forward
+    replies: Vec<Vec<u8>>,    // Terminate notices for the sender
+}
+
+/// ETP relay
+///
+/// Forwards ETP traffic between network segments, e.g. from aggregators to a
+/// BESS only reachable through a site controller. Every accepted connection
+/// is paired with a new connection to the next hop, which may itself be
+/// another relay. Only length-framed ETP traffic is relayed, not Go text.
+pub struct EtpRelay {
+    router: RelayRouter,
+    next_hop: SocketAddr,
+    listener: Option<TcpListener>,
+    is_running: Arc<AtomicBool>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl EtpRelay {
+    /// Create a relay listening on `bind_addr` and forwarding to `next_hop`
+    pub async fn new(relay_id: u64, bind_addr: SocketAddr, next_hop: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(bind_addr).await?;
+        let local_addr = listener.local_addr()?;
+
+        info!("ETP relay {} created on {}, forwarding to {}", relay_id, local_addr, next_hop);
+
+        Ok(Self {
+            router: RelayRouter::new(relay_id),
+            next_hop,
+            listener: Some(listener),
+            is_running: Arc::new(AtomicBool::new(false)),
+            local_addr: Some(local_addr),
+        })
+    }
+
+    /// Change where accepted connections are forwarded to
+    pub fn set_next_hop(&mut self, next_hop: SocketAddr) {
+        self.next_hop = next_hop;
+    }
+
+    /// Get the forwarding rules
+    pub fn router(&self) -> &RelayRouter {
+        &self.router
+    }
+
+    /// Start relaying
+    pub async fn start(&mut self) -> Result<()> {
+        let listener = self.listener.take()
+            .ok_or_else(|| ETPError::Network("Relay not initialized".to_string()))?;
+
+        self.is_running.store(true, Ordering::Relaxed);
+        info!("ETP relay started on {}", self.local_addr.unwrap());
+
+        while self.is_running.load(Ordering::Relaxed) {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    info!("Relay connection from {}", addr);
+
+                    let router = self.router.clone();
+                    let next_hop = self.next_hop;
+                    tokio::spawn(async move {
+                        if let Err(e) = Self::handle_connection(stream, next_hop, router).await {
+                            error!("Error relaying connection from {}: {}", addr, e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    if self.is_running.load(Ordering::Relaxed) {
+                        error!("Error accepting relay connection: {}", e);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if the relay is running
+    pub async fn is_running(&self) -> bool {
+        self.is_running.load(Ordering::Relaxed)
+    }
+
+    /// Get local address
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.local_addr.ok_or_else(||
+            ETPError::Network("Relay not bound to address".to_string())
+        )
+    }
+
+    /// Shutdown the relay
+    pub async fn shutdown(&self) -> Result<()> {
+        self.is_running.store(false, Ordering::Relaxed);
+        info!("ETP relay shutdown requested");
+        Ok(())
+    }
+
+    /// Relay one connection in both directions until either side closes
+    async fn handle_connection(inbound: TcpStream, next_hop: SocketAddr, router: RelayRouter) -> Result<()> {
+        let (mut inbound_read, inbound_write) = inbound.into_split();
+        let inbound_write = Mutex::new(inbound_write);
+
+        // Connect to the next hop only once there is something to forward, so a
+        // misconfigured loop of relays does not keep opening connections
+        let first_frame = loop {
+            let Some(frame) = next_frame(&mut inbound_read).await? else {
+                return Ok(());
+            };
+            let routed = router.route_or_drop(&frame);
+            for reply in &routed.replies {
+                write_frame(&inbound_write, reply).await?;
+            }
+            if let Some(frame) = routed.forward {
+                break frame;
+            }
+        };
+
+        let (outbound_read, outbound_write) = TcpStream::connect(next_hop).await?.into_split();
+        let outbound_write = Mutex::new(outbound_write);
+        write_frame(&outbound_write, &first_frame).await?;
+
+        tokio::select! {
+            result = Self::pump(&router, inbound_read, &outbound_write, &inbound_write) => result,
+            result = Self::pump(&router, outbound_read, &inbound_write, &outbound_write) => result,
+        }
+    }
+
+    /// Route frames read from one side to the other
+    async fn pump(
+        router: &RelayRouter,
+        mut reader: OwnedReadHalf,
+        forward_to: &Mutex<OwnedWriteHalf>,
+        reply_to: &Mutex<OwnedWriteHalf>,
+    ) -> Result<()> {
+        while let Some(frame) = next_frame(&mut reader).await? {
+            let routed = router.route_or_drop(&frame);
+            for reply in &routed.replies {
+                write_frame(reply_to, reply).await?;
+            }
+            if let Some(frame) = routed.forward {
+                write_frame(forward_to, &frame).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read one length-prefixed frame, `None` once the peer has closed the connection
+///
+/// Lengths above `MAX_FRAME_SIZE` fail with `FrameTooLarge` before the frame is allocated.
+async fn next_frame(reader: &mut OwnedReadHalf) -> Result<Option<Vec<u8>>> {
+    let mut length_bytes = [0u8; LENGTH_PREFIX_SIZE];
+    match reader.read_exact(&mut length_bytes).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let length = u32::from_le_bytes(length_bytes) as usize;
+    check_frame_size(length, MAX_FRAME_SIZE)?;
+    let mut frame = vec![0u8; length];
+    reader.read_exact(&mut frame).await?;
+    Ok(Some(frame))
+}
+
+/// Write one length-prefixed frame
+async fn write_frame(writer: &Mutex<OwnedWriteHalf>, frame: &[u8]) -> Result<()> {
+    let mut framed_message = Vec::with_capacity(LENGTH_PREFIX_SIZE + frame.len());
+    framed_message.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+    framed_message.extend_from_slice(frame);
+
+    let mut writer = writer.lock().await;
+    writer.write_all(&framed_message).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_decrements_ttl() {
+        let router = RelayRouter::new(900);
+        match router.route(ETPMessage::new_bid(1, 15.5, 10.0)) {
+            RelayDecision::Forward(message) => assert_eq!(message.ttl, 4),
+            other => panic!("Expected Forward, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_route_expires_last_hop() {
+        let router = RelayRouter::new(900);
+        let mut bid = ETPMessage::new_bid(2, 15.5, 10.0);
+        bid.ttl = 1;
+        match router.route(bid) {
+            RelayDecision::Expired(notice) => {
+                assert_eq!(notice.message_type, 7); // Terminate
+                assert_eq!(notice.message_id, 2);
+                assert_eq!(notice.device_id, 900);
+                assert_eq!(notice.termination().unwrap(), TerminationCode::TtlExpired);
+            }
+            other => panic!("Expected Expired, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_route_drops_loops() {
+        let router = RelayRouter::new(900);
+        let bid = ETPMessage::new_bid(3, 15.5, 10.0);
+        assert!(matches!(router.route(bid.clone()), RelayDecision::Forward(_)));
+        assert_eq!(router.route(bid), RelayDecision::Loop);
+
+        // A response reusing the request's message_id is a different message
+        let response = ETPMessage::new_bid_accept(3, 123, 15.5, 10.0);
+        assert!(matches!(router.route(response), RelayDecision::Forward(_)));
+    }
+
+    #[test]
+    fn test_route_batch_frame() {
+        let router = RelayRouter::new(900);
+        let mut last_hop = ETPMessage::new_bess_status(5, 100, 18.5, 1, 12.6, 2.5);
+        last_hop.ttl = 1;
+        let batch = MessageBatch::new(vec![ETPMessage::new_bess_status(4, 100, 18.6, 1, 12.6, 2.5), last_hop]);
+
+        let routed = router.route_frame(&batch.encode().unwrap()).unwrap();
+        let forwarded = MessageBatch::decode(&routed.forward.unwrap()).unwrap();
+        assert_eq!(forwarded.len(), 1);
+        assert_eq!(forwarded.messages[0].message_id, 4);
+        assert_eq!(forwarded.messages[0].ttl, 4);
+
+        assert_eq!(routed.replies.len(), 1);
+        let notice = ETPMessage::deserialize(&routed.replies[0]).unwrap();
+        assert_eq!(notice.message_id, 5);
+    }
+
+    #[test]
+    fn test_route_keeps_compact_and_compressed_frames() {
+        let router = RelayRouter::new(900);
+        let mut buf = BytesMut::new();
+        ETPMessage::new_bid(6, 15.5, 10.0).encode_compact(1, &mut buf).unwrap();
+        let compressed = compress_frame(&MessageBatch::new(vec![ETPMessage::new_bid(7, 15.5, 10.0)]).encode().unwrap()).unwrap();
+
+        let forwarded = router.route_frame(&buf).unwrap().forward.unwrap();
+        assert_eq!(ETPMessage::decode_compact(&forwarded).unwrap().ttl, 4);
+
+        let forwarded = router.route_frame(&compressed).unwrap().forward.unwrap();
+        let batch = MessageBatch::decode(&decompress_frame(&forwarded, MAX_FRAME_SIZE).unwrap()).unwrap();
+        assert_eq!(batch.messages[0].ttl, 4);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::codec::framing::WireFormat;
+use crate::codec::inspect::{to_hex, InspectedFrame};
+use crate::etp_message::ETPMessage;
+use crate::network::capture::{serialize_hex, Capture, CaptureDirection, CaptureRecord};
+use crate::network::unicast_connection::UnicastConnection;
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+use tokio::time::{sleep_until, timeout, timeout_at, Instant};
+
+/// How long to wait for frames the target sends after the capture ends
+const TRAILING_WINDOW: Duration = Duration::from_millis(200);
+
+/// How the replayer paces the frames it sends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ReplayTiming {
+    Original, // Send each frame at its recorded offset
+    Fast,     // Send each frame as soon as the expected answers before it arrived
+}
+
+/// Which side of the recorded conversation the replayer plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ReplaySide {
+    Peer,     // Send what the recording endpoint received; drive a fresh instance of it
+    Recorder, // Send what the recording endpoint sent; drive a fresh instance of its peer
+}
+
+impl ReplaySide {
+    /// Direction of the records this side sends
+    fn sends(self) -> CaptureDirection {
+        match self {
+            ReplaySide::Peer => CaptureDirection::Received,
+            ReplaySide::Recorder => CaptureDirection::Sent,
+        }
+    }
+}
+
+/// Point where the target behaved differently from the capture
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Divergence {
+    /// The target sent a different frame than recorded
+    Mismatch {
+        record: usize,
+        #[serde(serialize_with = "serialize_hex")]
+        expected: Vec<u8>,
+        #[serde(serialize_with = "serialize_hex")]
+        actual: Vec<u8>,
+    },
+    /// The target sent nothing where the capture has a frame
+    Missing {
+        record: usize,
+        #[serde(serialize_with = "serialize_hex")]
+        expected: Vec<u8>,
+        reason: String,
+    },
+    /// The target sent a frame after the capture ended
+    Unexpected {
+        #[serde(serialize_with = "serialize_hex")]
+        actual: Vec<u8>,
+    },
+    /// A recorded frame could not be sent
+    SendFailed { record: usize, reason: String },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Mismatch { record, expected, actual } => write!(
+                f,
+                "record {}: expected {}\n           got      {}",
+                record,
+                describe(expected),
+                describe(actual)
+            ),
+            Divergence::Missing { record, expected, reason } => {
+                write!(f, "record {}: expected {}, got nothing ({})", record, describe(expected), reason)
+            }
+            Divergence::Unexpected { actual } => write!(f, "after the capture: unexpected {}", describe(actual)),
+            Divergence::SendFailed { record, reason } => write!(f, "record {}: send failed ({})", record, reason),
+        }
+    }
+}
+
+/// Outcome of replaying a capture
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    pub sent: usize,     // Frames sent to the target
+    pub expected: usize, // Frames the capture expects from the target
+    pub matched: usize,  // Expected frames the target reproduced
+    pub divergences: Vec<Divergence>,
+}
+
+impl ReplayReport {
+    /// Check whether the target reproduced the capture
+    pub fn passed(&self) -> bool {
+        self.divergences.is_empty()
+    }
+
+    /// Get the earliest divergence, usually the one worth investigating
+    pub fn first_divergence(&self) -> Option<&Divergence> {
+        self.divergences.first()
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Replay: sent {} frames, {}/{} expected frames matched",
+            self.sent, self.matched, self.expected
+        )?;
+        for divergence in &self.divergences {
+            writeln!(f, "DIVERGED  {}", divergence)?;
+        }
+        write!(f, "{}", if self.passed() { "Target reproduced the capture" } else { "Target diverged from the capture" })
+    }
+}
+
+/// Re-drives an ETP endpoint from a capture and reports where it diverges
+///
+/// Records sent by the played side are written to the connection; the other
+/// side's records are what the target is expected to answer. A frame matches
+/// when it has the same bytes, or carries the same messages (or hello) once
+/// decoded, so fresh signatures, nonces and compression do not count as
+/// divergences. Signed captures only replay within the target's replay
+/// window, as their timestamps are kept.
+pub struct CaptureReplayer {
+    capture: Capture,
+    side: ReplaySide,
+    timing: ReplayTiming,
+    response_window: Duration,
+}
+
+impl CaptureReplayer {
+    /// Create a replayer playing the peer of the recording endpoint with the original timing
+    pub fn new(capture: Capture) -> Self {
+        Self {
+            capture,
+            side: ReplaySide::Peer,
+            timing: ReplayTiming::Original,
+            response_window: Duration::from_secs(2),
+        }
+    }
+
+    /// Choose which recorded side to play
+    pub fn set_side(&mut self, side: ReplaySide) {
+        self.side = side;
+    }
+
+    /// Choose between the recorded pacing and replaying as fast as possible
+    pub fn set_timing(&mut self, timing: ReplayTiming) {
+        self.timing = timing;
+    }
+
+    /// Set how long to wait for each expected frame beyond its recorded offset
+    pub fn set_response_window(&mut self, response_window: Duration) {
+        self.response_window = response_window;
+    }
+
+    /// Replay the capture over a connection to the target
+    pub async fn run(&self, connection: &mut UnicastConnection) -> ReplayReport {
+        if self.capture.records.iter().any(|record| record.format == WireFormat::GoText) {
+            connection.set_wire_format(WireFormat::GoText);
+        }
+
+        let mut report = ReplayReport {
+            sent: 0,
+            expected: 0,
+            matched: 0,
+            divergences: Vec::new(),
+        };
+        let started = Instant::now();
+        let mut records = self.capture.records.iter().enumerate();
+        while let Some((index, record)) = records.next() {
+            let recorded_at = started + Duration::from_micros(record.at_us);
+            if record.direction == self.side.sends() {
+                if self.timing == ReplayTiming::Original {
+                    sleep_until(recorded_at).await;
+                }
+                match connection.send_frame(&record.frame).await {
+                    Ok(()) => report.sent += 1,
+                    Err(e) => {
+                        report.divergences.push(Divergence::SendFailed { record: index, reason: e.to_string() });
+                        return report;
+                    }
+                }
+                continue;
+            }
+
+            report.expected += 1;
+            let deadline = match self.timing {
+                ReplayTiming::Original => recorded_at.max(Instant::now()) + self.response_window,
+                ReplayTiming::Fast => Instant::now() + self.response_window,
+            };
+            match timeout_at(deadline, connection.receive_frame()).await {
+                Ok(Ok(actual)) if frames_match(&record.frame, &actual) => report.matched += 1,
+                Ok(Ok(actual)) => report.divergences.push(Divergence::Mismatch {
+                    record: index,
+                    expected: record.frame.clone(),
+                    actual,
+                }),
+                Ok(Err(e)) => {
+                    // The target hung up; nothing else it was expected to send will come
+                    let reason = e.to_string();
+                    report.divergences.push(missing(index, record, &reason));
+                    for (index, record) in records.by_ref().filter(|(_, record)| record.direction != self.side.sends()) {
+                        report.expected += 1;
+                        report.divergences.push(missing(index, record, &reason));
+                    }
+                    return report;
+                }
+                Err(_) => report.divergences.push(missing(index, record, "timed out")),
+            }
+        }
+
+        while let Ok(Ok(actual)) = timeout(TRAILING_WINDOW, connection.receive_frame()).await {
+            report.divergences.push(Divergence::Unexpected { actual });
+        }
+        report
+    }
+}
+
+fn missing(index: usize, record: &CaptureRecord, reason: &str) -> Divergence {
+    Divergence::Missing {
+        record: index,
+        expected: record.frame.clone(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Check whether the target's frame reproduces the recorded one
+fn frames_match(expected: &[u8], actual: &[u8]) -> bool {
+    if expected == actual {
+        return true;
+    }
+    match (InspectedFrame::decode(expected), InspectedFrame::decode(actual)) {
+        (Ok(InspectedFrame::Hello(expected)), Ok(InspectedFrame::Hello(actual))) => expected == actual,
+        (Ok(expected), Ok(actual)) => {
+            // Compare encoded bytes so NaN fields still match themselves
+            let encoded = |frame: &InspectedFrame| {
+                frame.messages().into_iter().map(|message| ETPMessage::serialize(message).ok()).collect::<Vec<_>>()
+            };
+            !expected.messages().is_empty() && encoded(&expected) == encoded(&actual)
+        }
+        _ => false,
+    }
+}
+
+/// Summarise a frame for the report
+fn describe(frame: &[u8]) -> String {
+    match InspectedFrame::decode(frame) {
+        Ok(inspected) => inspected.to_string().replace('\n', "; "),
+        Err(_) => format!("undecodable frame {}", to_hex(frame)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::MessageSigner;
+    use crate::codec::compression::compress_frame;
+
+    #[test]
+    fn test_frames_match_ignores_signatures_and_compression() {
+        let message = ETPMessage::new_bid_accept(7, 123, 18.0, 10.0);
+        let signer = MessageSigner::generate();
+        let first = signer.sign(message.clone()).unwrap().encode().unwrap();
+        let second = signer.sign(message.clone()).unwrap().encode().unwrap();
+        assert_ne!(first, second); // Fresh nonce
+        assert!(frames_match(&first, &second));
+        assert!(frames_match(&message.serialize().unwrap(), &compress_frame(&first).unwrap()));
+
+        let other = ETPMessage::new_bid_accept(7, 123, 17.0, 10.0).serialize().unwrap();
+        assert!(!frames_match(&message.serialize().unwrap(), &other));
+        assert!(!frames_match(b"XY", b"XZ"));
+    }
+}
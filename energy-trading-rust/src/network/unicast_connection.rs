@@ -1,62 +1,280 @@
 use crate::etp_message::ETPMessage;
-use crate::error::Result;
+use crate::etp_payload::EtpPayload;
+use crate::codec::batch::MessageBatch;
+use crate::codec::compression::{compress_frame, decompress_frame, is_compressed_frame};
+use crate::codec::delivery_frame::DeliveryFrame;
+use crate::codec::framing::{check_frame_size, EtpCodec, WireFormat};
+use crate::codec::wire::WIRE_MESSAGE_SIZE;
+use crate::envelope::{EnvelopeVerifier, MessageSigner, SignedEnvelope};
+use crate::error::{ETPError, Result};
+use crate::replay_protection::ReplayCache;
+use crate::network::capture::{CaptureDirection, CaptureHeader, CaptureRecorder};
+use crate::network::handshake::{Capabilities, Hello, NegotiatedProtocol};
+use bytes::BytesMut;
+use futures::{ready, FutureExt, Sink, SinkExt, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::future::poll_fn;
 use std::io::ErrorKind;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::TcpStream;
+use tokio_util::codec::{FramedRead, FramedWrite};
 use tracing::{info, warn, error};
 
 /// Unicast TCP Connection
 /// 
 /// Handles reliable message delivery between aggregators and BESS nodes.
 /// Implements message framing for TCP streams as per ETP specifications.
+///
+/// Frames go through an `EtpCodec` on each half of the stream. The
+/// connection is a `Stream` of received messages and a `Sink` of messages
+/// to send, and `split` hands the halves to separate tasks.
 pub struct UnicastConnection {
-    stream: TcpStream,
-    buffer: Vec<u8>,
+    reader: ConnectionReader,
+    writer: ConnectionWriter,
+}
+
+/// A message or delivery frame received from the peer
+#[derive(Debug, Clone, PartialEq)]
+pub enum Received {
+    Message(ETPMessage),
+    Delivery(DeliveryFrame),
+}
+
+/// Receiving half of a `UnicastConnection`
+///
+/// Ends as a `Stream` when the peer closes the connection.
+pub struct ConnectionReader {
+    frames: FramedRead<OwnedReadHalf, EtpCodec>,
+    pending_frame: Option<Result<Vec<u8>>>, // Frame read ahead by a handshake or liveness check
+    pending_messages: VecDeque<Result<ETPMessage>>, // Rest of a received batch
+    verifier: Option<Arc<EnvelopeVerifier>>,
+    replay_cache: Option<Arc<ReplayCache>>,
+    recorder: Option<CaptureRecorder>,
+}
+
+/// Sending half of a `UnicastConnection`
+pub struct ConnectionWriter {
+    frames: FramedWrite<OwnedWriteHalf, EtpCodec>,
+    protocol: NegotiatedProtocol,
+    compression: bool, // Compress outgoing frames if the peer negotiated it
+    signer: Option<MessageSigner>,
+    recorder: Option<CaptureRecorder>,
 }
 
 impl UnicastConnection {
     /// Create a new unicast connection
     pub fn new(stream: TcpStream) -> Self {
+        let (read_half, write_half) = stream.into_split();
         Self {
-            stream,
-            buffer: Vec::new(),
+            reader: ConnectionReader {
+                frames: FramedRead::new(read_half, EtpCodec::new()),
+                pending_frame: None,
+                pending_messages: VecDeque::new(),
+                verifier: None,
+                replay_cache: None,
+                recorder: None,
+            },
+            writer: ConnectionWriter {
+                frames: FramedWrite::new(write_half, EtpCodec::new()),
+                protocol: NegotiatedProtocol::default(),
+                compression: false,
+                signer: None,
+                recorder: None,
+            },
+        }
+    }
+
+    /// Split into halves that can send and receive from separate tasks
+    ///
+    /// Split after the handshake: the halves keep the negotiated protocol,
+    /// and a writer split off before bridge mode detected the peer's wire
+    /// format keeps sending ETP frames.
+    pub fn split(self) -> (ConnectionReader, ConnectionWriter) {
+        (self.reader, self.writer)
+    }
+
+    /// Sign every outgoing ETP message with this key
+    ///
+    /// Go text peers cannot carry envelopes and still receive unsigned messages.
+    pub fn set_signer(&mut self, signer: MessageSigner) {
+        self.writer.signer = Some(signer);
+    }
+
+    /// Verify incoming messages, rejecting unsigned or forged ones with `ETPError::Authentication`
+    pub fn set_verifier(&mut self, verifier: Arc<EnvelopeVerifier>) {
+        self.reader.verifier = Some(verifier);
+    }
+
+    /// Refuse duplicate or stale messages with `ETPError::Replay`
+    ///
+    /// Share one cache between connections to catch replays across them. Go
+    /// text peers are not checked: they send `device_id` 0 and reuse message ids.
+    pub fn set_replay_cache(&mut self, replay_cache: Arc<ReplayCache>) {
+        self.reader.replay_cache = Some(replay_cache);
+    }
+
+    /// Compress outgoing frames on links where bandwidth matters more than CPU
+    ///
+    /// Only takes effect once the handshake negotiated `Capabilities::COMPRESSION`,
+    /// and frames that would not shrink are sent as they are.
+    pub fn set_compression(&mut self, enabled: bool) {
+        self.writer.compression = enabled;
+    }
+
+    /// Refuse frames longer than `max_frame_size` in either direction with `FrameTooLarge`
+    ///
+    /// Incoming length prefixes are checked before any buffer is grown for the frame.
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.reader.frames.decoder_mut().set_max_frame_size(max_frame_size);
+        self.writer.frames.encoder_mut().set_max_frame_size(max_frame_size);
+    }
+
+    /// Record every frame sent or received from now on
+    ///
+    /// Recording is best effort: if writing fails the capture stops and the
+    /// connection carries on.
+    pub fn set_recorder(&mut self, recorder: CaptureRecorder) {
+        self.reader.recorder = Some(recorder.clone());
+        self.writer.recorder = Some(recorder);
+    }
+
+    /// Record every frame sent or received from now on to a new capture file
+    pub fn record_to(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let stream = self.reader.frames.get_ref();
+        let local_addr = stream.local_addr().ok().map(|addr| addr.to_string());
+        let peer_addr = stream.peer_addr().ok().map(|addr| addr.to_string());
+        self.set_recorder(CaptureRecorder::create(path, CaptureHeader::new(local_addr, peer_addr))?);
+        Ok(())
+    }
+
+    /// Enable bridge mode
+    ///
+    /// The wire format is detected from the first bytes the peer sends, so the
+    /// same listener can serve Rust ETP peers and legacy Go prototype peers.
+    /// Messages sent before detection use the ETP format.
+    pub fn enable_bridge_mode(&mut self) {
+        self.reader.frames.decoder_mut().detect_wire_format();
+        self.writer.frames.encoder_mut().detect_wire_format();
+    }
+
+    /// Force the wire format, e.g. when connecting out to a known Go battery
+    pub fn set_wire_format(&mut self, wire_format: WireFormat) {
+        self.reader.frames.decoder_mut().set_wire_format(wire_format);
+        self.writer.frames.encoder_mut().set_wire_format(wire_format);
+    }
+
+    /// Get the wire format in use, `None` while bridge mode is still detecting
+    pub fn wire_format(&self) -> Option<WireFormat> {
+        self.reader.wire_format()
+    }
+
+    /// Get the protocol version and capabilities in use on this connection
+    pub fn protocol(&self) -> NegotiatedProtocol {
+        self.writer.protocol
+    }
+
+    /// Perform the client side of the handshake
+    ///
+    /// Sends our hello, waits for the peer's hello and agrees on the highest
+    /// common protocol version. Fails with `ETPError::VersionMismatch` when
+    /// the peers share no version.
+    pub async fn handshake(&mut self, hello: &Hello) -> Result<NegotiatedProtocol> {
+        self.send_frame(&hello.encode()?).await?;
+        let frame = self.receive_frame().await?;
+        let peer = Hello::decode(&frame)?;
+        self.writer.protocol = hello.negotiate(&peer)?;
+
+        info!("Negotiated ETP version {} with {:?}", self.writer.protocol.version, self.peer_addr().ok());
+        Ok(self.writer.protocol)
+    }
+
+    /// Perform the server side of the handshake
+    ///
+    /// Reads the first frame; if it is a hello, answers with our own hello and
+    /// negotiates. Legacy peers that start sending ETP messages straight away
+    /// are kept on protocol version 1 and `None` is returned; their first
+    /// message is delivered by the next `receive_message` call.
+    pub async fn accept_handshake(&mut self, hello: &Hello) -> Result<Option<NegotiatedProtocol>> {
+        let frame = self.receive_frame().await?;
+        if !Hello::is_hello_frame(&frame) {
+            self.reader.pending_frame = Some(Ok(frame));
+            return Ok(None);
+        }
+
+        let peer = Hello::decode(&frame)?;
+        // Always answer so the peer can report the mismatch too
+        self.send_frame(&hello.encode()?).await?;
+        self.writer.protocol = hello.negotiate(&peer)?;
+
+        info!("Negotiated ETP version {} with {:?}", self.writer.protocol.version, self.peer_addr().ok());
+        Ok(Some(self.writer.protocol))
+    }
+
+    /// Send a raw frame, with a 4-byte length prefix unless the peer speaks Go text
+    pub async fn send_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.writer.send_frame(frame).await
+    }
+
+    /// Receive a raw frame
+    ///
+    /// For ETP peers this is one length-prefixed frame; for Go peers it is the
+    /// text delivered by a single read, matching how the Go prototype reads.
+    ///
+    /// Partially read frames are kept in the codec's buffer, so the future
+    /// can be dropped and polled again without losing data.
+    pub async fn receive_frame(&mut self) -> Result<Vec<u8>> {
+        let received = self.reader.receive_frame().await;
+        self.follow_wire_format();
+        received
+    }
+
+    /// Send replies in the wire format bridge mode detected
+    fn follow_wire_format(&mut self) {
+        if let (None, Some(wire_format)) = (self.writer.wire_format(), self.reader.wire_format()) {
+            self.writer.frames.encoder_mut().set_wire_format(wire_format);
         }
     }
 
     /// Send an ETP message over the connection
     pub async fn send_message(&mut self, message: ETPMessage) -> Result<()> {
-        let serialized = message.serialize()?;
-        
-        // Add message length prefix (4 bytes)
-        let length = serialized.len() as u32;
-        let mut framed_message = Vec::new();
-        framed_message.extend_from_slice(&length.to_le_bytes());
-        framed_message.extend_from_slice(&serialized);
-        
-        // Send the framed message
-        self.stream.write_all(&framed_message).await?;
-        self.stream.flush().await?;
-        
-        info!("Sent ETP message type {} ({} bytes)", message.message_type, serialized.len());
-        Ok(())
+        self.writer.send_message(message).await
+    }
+
+    /// Send several messages in one batch frame
+    ///
+    /// Falls back to one frame per message when the peer did not negotiate
+    /// `Capabilities::BATCHING`, when messages are signed, or for Go text peers.
+    pub async fn send_batch(&mut self, batch: MessageBatch) -> Result<()> {
+        self.writer.send_batch(batch).await
     }
 
     /// Receive an ETP message from the connection
+    ///
+    /// Compressed frames are expanded and batch frames are unpacked, their
+    /// messages returned one per call.
     pub async fn receive_message(&mut self) -> Result<ETPMessage> {
-        // Read message length (4 bytes)
-        let mut length_bytes = [0u8; 4];
-        self.stream.read_exact(&mut length_bytes).await?;
-        let message_length = u32::from_le_bytes(length_bytes) as usize;
-        
-        // Read the actual message
-        let mut message_bytes = vec![0u8; message_length];
-        self.stream.read_exact(&mut message_bytes).await?;
-        
-        // Deserialize the message
-        let message = ETPMessage::deserialize(&message_bytes)?;
-        
-        info!("Received ETP message type {} ({} bytes)", message.message_type, message_length);
-        Ok(message)
+        let received = self.reader.receive_message().await;
+        self.follow_wire_format();
+        received
+    }
+
+    /// Receive an ETP message or a delivery frame
+    ///
+    /// `receive_message` skips delivery frames; peers that negotiated
+    /// `Capabilities::DELIVERY_WINDOWS` read with this instead.
+    pub async fn receive(&mut self) -> Result<Received> {
+        let received = self.reader.receive().await;
+        self.follow_wire_format();
+        received
+    }
+
+    /// Send a delivery window or schedule
+    pub async fn send_delivery(&mut self, frame: &DeliveryFrame) -> Result<()> {
+        self.writer.send_delivery(frame).await
     }
 
     /// Handle incoming messages (for server-side connections)
@@ -89,41 +307,46 @@ impl UnicastConnection {
 
     /// Process received message
     async fn process_message(&mut self, message: ETPMessage) -> Result<()> {
-        match message.message_type {
-            0 => { // Register
+        let payload = match message.payload() {
+            Ok(payload) => payload,
+            Err(_) => {
+                warn!("Unknown message type: {}", message.message_type);
+                return Ok(());
+            }
+        };
+
+        match payload {
+            EtpPayload::Register => {
                 info!("Processing register message from device {}", message.device_id);
             }
-            1 => { // Query
+            EtpPayload::Query => {
                 info!("Processing query message from device {}", message.device_id);
             }
-            2 => { // Query Response
+            EtpPayload::QueryResponse(_) => {
                 info!("Processing query response from device {}", message.device_id);
             }
-            3 => { // Bid
+            EtpPayload::Bid(bid) => {
                 info!("Processing bid message from device {}: ${:.2} for {:.2} kWh", 
-                      message.device_id, message.bid_price, message.required_energy_amount);
+                      message.device_id, bid.bid_price, bid.required_energy_amount);
             }
-            4 => { // Bid Accept
+            EtpPayload::BidAccept(_) => {
                 info!("Processing bid accept from device {}", message.device_id);
             }
-            5 => { // Bid Confirm
+            EtpPayload::BidConfirm(_) => {
                 info!("Processing bid confirm from device {}", message.device_id);
             }
-            6 => { // Bid Reject
+            EtpPayload::BidReject(_) => {
                 info!("Processing bid reject from device {}", message.device_id);
             }
-            7 => { // Terminate
+            EtpPayload::Terminate(_) => {
                 info!("Processing terminate message from device {}", message.device_id);
             }
-            8 => { // Device Failure
+            EtpPayload::DeviceFailure(_) => {
                 warn!("Processing device failure from device {}", message.device_id);
             }
-            9 => { // BESS Status
+            EtpPayload::BESSStatus(_) => {
                 info!("Processing BESS status from device {}", message.device_id);
             }
-            _ => {
-                warn!("Unknown message type: {}", message.message_type);
-            }
         }
         
         Ok(())
@@ -131,18 +354,361 @@ impl UnicastConnection {
 
     /// Check if connection is still alive
     pub async fn is_alive(&mut self) -> bool {
-        // Try to read 1 byte with a very short timeout
-        let mut buffer = [0u8; 1];
-        match self.stream.try_read(&mut buffer) {
-            Ok(0) => false, // Connection closed
-            Ok(_) => true,  // Data available
-            Err(_) => true, // Error reading (connection might still be alive)
+        let alive = self.reader.is_alive().await;
+        self.follow_wire_format();
+        alive
+    }
+
+    /// Get peer address
+    pub fn peer_addr(&self) -> Result<std::net::SocketAddr> {
+        self.reader.peer_addr()
+    }
+}
+
+impl Stream for UnicastConnection {
+    type Item = Result<ETPMessage>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let received = this.reader.poll_next_unpin(cx);
+        this.follow_wire_format();
+        received
+    }
+}
+
+impl Sink<ETPMessage> for UnicastConnection {
+    type Error = ETPError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().writer.poll_ready_unpin(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: ETPMessage) -> Result<()> {
+        self.get_mut().writer.start_send_unpin(message)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().writer.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().writer.poll_close_unpin(cx)
+    }
+}
+
+impl ConnectionReader {
+    /// Get the wire format in use, `None` while bridge mode is still detecting
+    pub fn wire_format(&self) -> Option<WireFormat> {
+        self.frames.decoder().wire_format()
+    }
+
+    /// Get peer address
+    pub fn peer_addr(&self) -> Result<std::net::SocketAddr> {
+        self.frames.get_ref().peer_addr().map_err(|e| e.into())
+    }
+
+    /// Receive a raw frame; see `UnicastConnection::receive_frame`
+    pub async fn receive_frame(&mut self) -> Result<Vec<u8>> {
+        poll_fn(|cx| self.poll_frame(cx)).await
+    }
+
+    /// Receive an ETP message; see `UnicastConnection::receive_message`
+    pub async fn receive_message(&mut self) -> Result<ETPMessage> {
+        poll_fn(|cx| self.poll_message(cx)).await
+    }
+
+    /// Receive an ETP message or a delivery frame; see `UnicastConnection::receive`
+    pub async fn receive(&mut self) -> Result<Received> {
+        poll_fn(|cx| self.poll_received(cx)).await
+    }
+
+    /// Check if connection is still alive
+    pub async fn is_alive(&mut self) -> bool {
+        if self.pending_frame.is_some() || !self.pending_messages.is_empty() {
+            return true;
         }
+        // Poll once without waiting; anything read is kept for the next receive
+        match self.receive_frame().now_or_never() {
+            None => true, // Nothing to read yet
+            Some(Err(ETPError::Io(e))) if e.kind() == ErrorKind::UnexpectedEof => false, // Connection closed
+            Some(received) => {
+                self.pending_frame = Some(received);
+                true
+            }
+        }
+    }
+
+    fn poll_frame(&mut self, cx: &mut Context<'_>) -> Poll<Result<Vec<u8>>> {
+        if let Some(received) = self.pending_frame.take() {
+            return Poll::Ready(received);
+        }
+
+        let detecting = self.wire_format().is_none();
+        let frame = match ready!(self.frames.poll_next_unpin(cx)) {
+            Some(received) => received?,
+            None => return Poll::Ready(Err(std::io::Error::from(ErrorKind::UnexpectedEof).into())),
+        };
+        if detecting {
+            info!("Detected {:?} wire format from {:?}", self.wire_format(), self.peer_addr().ok());
+        }
+        self.record(&frame);
+        Poll::Ready(Ok(frame))
+    }
+
+    fn poll_message(&mut self, cx: &mut Context<'_>) -> Poll<Result<ETPMessage>> {
+        loop {
+            match ready!(self.poll_received(cx))? {
+                Received::Message(message) => return Poll::Ready(Ok(message)),
+                Received::Delivery(frame) => {
+                    info!("Ignoring delivery frame {} from {:?}", frame.message_id, self.peer_addr().ok());
+                }
+            }
+        }
+    }
+
+    fn poll_received(&mut self, cx: &mut Context<'_>) -> Poll<Result<Received>> {
+        loop {
+            if let Some(message) = self.pending_messages.pop_front() {
+                return Poll::Ready(message.map(Received::Message));
+            }
+
+            let received = ready!(self.poll_frame(cx))?;
+            if let Some(received) = self.accept_frame(received)? {
+                return Poll::Ready(Ok(received));
+            }
+        }
+    }
+
+    /// Decode a received frame; batches are queued and `None` returned
+    fn accept_frame(&mut self, received: Vec<u8>) -> Result<Option<Received>> {
+        if self.wire_format() == Some(WireFormat::GoText) {
+            let message = self.accept_unsigned(ETPMessage::decode_go_bytes(&received)?)?;
+            info!("Received Go text {} ({} bytes)", message, received.len());
+            return Ok(Some(Received::Message(message)));
+        }
+
+        let message_bytes = match is_compressed_frame(&received) {
+            true => decompress_frame(&received, self.frames.decoder().max_frame_size())?,
+            false => received,
+        };
+        if MessageBatch::is_batch_frame(&message_bytes) {
+            let batch = MessageBatch::decode(&message_bytes)?;
+            info!("Received ETP batch of {} messages ({} bytes)", batch.len(), message_bytes.len());
+
+            // Check every message now so the replay cache sees them in order
+            let checked: VecDeque<_> = batch.into_iter().map(|message| self.accept_unsigned(message)).collect();
+            self.pending_messages = checked;
+            return Ok(None);
+        }
+        if DeliveryFrame::is_delivery_frame(&message_bytes) {
+            let frame = DeliveryFrame::decode(&message_bytes)?;
+            info!("Received delivery frame {:?} #{} ({} bytes)", frame.kind, frame.message_id, message_bytes.len());
+            return Ok(Some(Received::Delivery(frame)));
+        }
+
+        // Deserialize the message
+        let message = if SignedEnvelope::is_envelope_frame(&message_bytes) {
+            self.accept_envelope(SignedEnvelope::decode(&message_bytes)?)?
+        } else if ETPMessage::is_compact_frame(&message_bytes) {
+            self.accept_unsigned(ETPMessage::decode_compact(&message_bytes)?)?
+        } else {
+            self.accept_unsigned(ETPMessage::deserialize(&message_bytes)?)?
+        };
+
+        info!("Received {} ({} bytes)", message, message_bytes.len());
+        Ok(Some(Received::Message(message)))
+    }
+
+    /// Check a signed envelope and unwrap its message
+    fn accept_envelope(&self, envelope: SignedEnvelope) -> Result<ETPMessage> {
+        match &self.verifier {
+            Some(verifier) => verifier.verify(&envelope)?,
+            None => envelope.verify_signature()?,
+        }
+        if let Some(replay_cache) = self.replay_cache() {
+            replay_cache.check_envelope(&envelope)?;
+        }
+        Ok(envelope.message)
+    }
+
+    /// Check that an unsigned message is allowed on this connection
+    fn accept_unsigned(&self, message: ETPMessage) -> Result<ETPMessage> {
+        if let Some(verifier) = &self.verifier {
+            verifier.verify_unsigned(&message)?;
+        }
+        if let Some(replay_cache) = self.replay_cache() {
+            replay_cache.check_message(&message)?;
+        }
+        Ok(message)
+    }
+
+    /// Get the replay cache, unless the peer speaks Go text
+    fn replay_cache(&self) -> Option<&ReplayCache> {
+        match self.wire_format() {
+            Some(WireFormat::GoText) => None,
+            _ => self.replay_cache.as_deref(),
+        }
+    }
+
+    /// Append a received frame to the capture, if recording
+    fn record(&mut self, frame: &[u8]) {
+        let wire_format = self.wire_format().unwrap_or(WireFormat::Etp);
+        record(&mut self.recorder, CaptureDirection::Received, wire_format, frame);
+    }
+}
+
+impl Stream for ConnectionReader {
+    type Item = Result<ETPMessage>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match ready!(self.get_mut().poll_message(cx)) {
+            Err(ETPError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => Poll::Ready(None),
+            received => Poll::Ready(Some(received)),
+        }
+    }
+}
+
+impl ConnectionWriter {
+    /// Get the wire format replies are sent in, `None` until bridge mode detects it
+    pub fn wire_format(&self) -> Option<WireFormat> {
+        self.frames.encoder().wire_format()
+    }
+
+    /// Get the protocol version and capabilities in use on this connection
+    pub fn protocol(&self) -> NegotiatedProtocol {
+        self.protocol
     }
 
     /// Get peer address
     pub fn peer_addr(&self) -> Result<std::net::SocketAddr> {
-        self.stream.peer_addr().map_err(|e| e.into())
+        self.frames.get_ref().peer_addr().map_err(|e| e.into())
+    }
+
+    /// Send a raw frame; see `UnicastConnection::send_frame`
+    pub async fn send_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.check_outgoing(frame)?;
+        self.frames.send(frame).await
+    }
+
+    /// Send an ETP message; see `UnicastConnection::send_message`
+    pub async fn send_message(&mut self, message: ETPMessage) -> Result<()> {
+        SinkExt::send(self, message).await
+    }
+
+    /// Send a delivery frame; see `UnicastConnection::send_delivery`
+    ///
+    /// Fails unless the peer negotiated `Capabilities::DELIVERY_WINDOWS`.
+    pub async fn send_delivery(&mut self, frame: &DeliveryFrame) -> Result<()> {
+        if !self.protocol.capabilities.contains(Capabilities::DELIVERY_WINDOWS) {
+            return Err(ETPError::Validation("Peer did not negotiate delivery windows".to_string()));
+        }
+        let encoded = frame.encode()?;
+        self.send_frame(&encoded).await?;
+
+        info!("Sent delivery frame {:?} #{} ({} bytes)", frame.kind, frame.message_id, encoded.len());
+        Ok(())
+    }
+
+    /// Send several messages in one batch frame; see `UnicastConnection::send_batch`
+    pub async fn send_batch(&mut self, mut batch: MessageBatch) -> Result<()> {
+        let batching = self.protocol.capabilities.contains(Capabilities::BATCHING)
+            && self.signer.is_none()
+            && self.wire_format() != Some(WireFormat::GoText);
+        if !batching {
+            for message in batch {
+                self.send_message(message).await?;
+            }
+            return Ok(());
+        }
+
+        batch.version = self.protocol.version;
+        let frame = self.compress(batch.encode()?)?;
+        self.send_frame(&frame).await?;
+
+        info!("Sent ETP batch of {} messages ({} bytes)", batch.len(), frame.len());
+        Ok(())
+    }
+
+    /// Encode a message as the frame to send, signed and compressed if enabled
+    fn encode_message(&self, message: &ETPMessage) -> Result<Vec<u8>> {
+        if self.wire_format() == Some(WireFormat::GoText) {
+            return Ok(message.encode_go_text().into_bytes());
+        }
+
+        let serialized = match &self.signer {
+            Some(signer) => signer.sign(message.clone())?.encode()?,
+            None => {
+                let mut serialized = BytesMut::with_capacity(WIRE_MESSAGE_SIZE);
+                if self.protocol.capabilities.contains(Capabilities::COMPACT_ENCODING) {
+                    message.encode_compact(self.protocol.version, &mut serialized)?;
+                } else {
+                    message.encode_versioned(self.protocol.version, &mut serialized)?;
+                }
+                serialized.to_vec()
+            }
+        };
+        self.compress(serialized)
+    }
+
+    /// Compress a message, batch or envelope frame if enabled and it shrinks
+    fn compress(&self, frame: Vec<u8>) -> Result<Vec<u8>> {
+        if self.compression && self.protocol.capabilities.contains(Capabilities::COMPRESSION) {
+            let compressed = compress_frame(&frame)?;
+            if compressed.len() < frame.len() {
+                return Ok(compressed);
+            }
+        }
+        Ok(frame)
+    }
+
+    /// Check an outgoing frame against the size limit and record it
+    fn check_outgoing(&mut self, frame: &[u8]) -> Result<()> {
+        check_frame_size(frame.len(), self.frames.encoder().max_frame_size())?;
+        let wire_format = self.wire_format().unwrap_or(WireFormat::Etp);
+        record(&mut self.recorder, CaptureDirection::Sent, wire_format, frame);
+        Ok(())
+    }
+}
+
+impl Sink<ETPMessage> for ConnectionWriter {
+    type Error = ETPError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Sink::<Vec<u8>>::poll_ready(Pin::new(&mut self.get_mut().frames), cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: ETPMessage) -> Result<()> {
+        let this = self.get_mut();
+        let frame = this.encode_message(&message)?;
+        this.check_outgoing(&frame)?;
+        let sent = frame.len();
+        Pin::new(&mut this.frames).start_send(frame)?;
+
+        if this.wire_format() == Some(WireFormat::GoText) {
+            info!("Sent Go text {} ({} bytes)", message, sent);
+        } else {
+            info!("Sent {} ({} bytes)", message, sent);
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Sink::<Vec<u8>>::poll_flush(Pin::new(&mut self.get_mut().frames), cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Sink::<Vec<u8>>::poll_close(Pin::new(&mut self.get_mut().frames), cx)
+    }
+}
+
+/// Append a frame to the capture, if recording
+fn record(recorder: &mut Option<CaptureRecorder>, direction: CaptureDirection, wire_format: WireFormat, frame: &[u8]) {
+    let Some(active) = recorder else {
+        return;
+    };
+    if let Err(e) = active.record(direction, wire_format, frame) {
+        warn!("Capture stopped: {}", e);
+        *recorder = None;
     }
 }
 
@@ -190,4 +756,98 @@ mod tests {
         
         server_handle.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_signed_message_send_receive() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let signer = MessageSigner::generate();
+        let mut verifier = EnvelopeVerifier::default();
+        verifier.trust_key(signer.public_key(), 0);
+        let verifier = Arc::new(verifier);
+
+        let server_handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut connection = UnicastConnection::new(stream);
+            connection.set_verifier(verifier);
+
+            let signed = connection.receive_message().await.unwrap();
+            assert_eq!(signed.bid_price, 18.0);
+            assert!(matches!(
+                connection.receive_message().await,
+                Err(crate::error::ETPError::Authentication(_))
+            ));
+        });
+
+        let client_stream = TcpStream::connect(server_addr).await.unwrap();
+        let mut client_connection = UnicastConnection::new(client_stream);
+        client_connection.set_signer(signer);
+        client_connection.send_message(ETPMessage::new_bid(123, 18.0, 10.0)).await.unwrap();
+
+        // The same bid without a signature must be refused
+        client_connection.writer.signer = None;
+        client_connection.send_message(ETPMessage::new_bid(124, 18.0, 10.0)).await.unwrap();
+
+        server_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_batch_send_receive() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let batched = Hello::new(vec![1], Capabilities::BATCHING);
+        let server_hello = batched.clone();
+
+        let server_handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut connection = UnicastConnection::new(stream);
+            connection.accept_handshake(&server_hello).await.unwrap();
+
+            // Batched, then sent one by one to a peer without batching
+            for _ in 0..2 {
+                for message_id in 1..=3 {
+                    assert_eq!(connection.receive_message().await.unwrap().message_id, message_id);
+                }
+            }
+        });
+
+        let statuses = || MessageBatch::new((1..=3).map(|i| ETPMessage::new_bess_status(i, 100, 18.5, 1, 12.6, 2.5)).collect());
+
+        let client_stream = TcpStream::connect(server_addr).await.unwrap();
+        let mut client_connection = UnicastConnection::new(client_stream);
+        client_connection.handshake(&batched).await.unwrap();
+        client_connection.send_batch(statuses()).await.unwrap();
+        client_connection.writer.protocol = NegotiatedProtocol::default();
+        client_connection.send_batch(statuses()).await.unwrap();
+
+        server_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_split_connection_pushes_status_while_waiting_for_bid() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+
+        let bess_stream = TcpStream::connect(server_addr).await.unwrap();
+        let (aggregator_stream, _) = listener.accept().await.unwrap();
+        let mut aggregator = UnicastConnection::new(aggregator_stream);
+        let (mut reader, mut writer) = UnicastConnection::new(bess_stream).split();
+
+        // The BESS waits for a bid on one task while reporting status from another
+        let waiting = tokio::spawn(async move { reader.receive_message().await.unwrap() });
+        for message_id in 1..=3 {
+            writer.send_message(ETPMessage::new_bess_status(message_id, 100, 18.5, 1, 12.6, 2.5)).await.unwrap();
+        }
+        for message_id in 1..=3 {
+            let status = aggregator.next().await.unwrap().unwrap();
+            assert_eq!((status.message_type, status.message_id), (9, message_id));
+        }
+        assert!(!waiting.is_finished());
+
+        aggregator.send(ETPMessage::new_bid(4, 18.0, 10.0)).await.unwrap();
+        assert_eq!(waiting.await.unwrap().message_type, 3); // Bid
+
+        drop(writer);
+        assert!(aggregator.next().await.is_none());
+    }
 }
@@ -1,12 +1,16 @@
+use crate::degradation::{HealthEvent, TradeOutcome};
+use crate::delivery::ScheduledDelivery;
 use crate::error::Result;
+use crate::termination::{TerminationCode, TerminationCodeInfo};
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Path, State,
     },
+    http::StatusCode,
     response::Response,
     routing::get,
-    Router,
+    Json, Router,
 };
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
@@ -52,6 +56,7 @@ pub enum SystemEvent {
         aggregator_id: u64,
         bess_id: u64,
         reason: String,
+        code: TerminationCode,
     },
     QuerySent {
         aggregator_id: u64,
@@ -86,6 +91,19 @@ pub enum SystemEvent {
         battery_health: u8,
         is_online: bool,
     },
+    BatteryHealthChanged {
+        bess_id: u64,
+        previous_status: u8,
+        status: u8,
+        state_of_health: f64,
+    },
+    TradeSettled {
+        bess_id: u64,
+        energy_sold: f64,
+        revenue: f64,
+        wear_cost: f64,
+        profit: f64,
+    },
     AggregatorStatus {
         device_id: u64,
         strategy: String,
@@ -98,6 +116,30 @@ pub enum SystemEvent {
     },
 }
 
+impl SystemEvent {
+    /// Report a settled sale with its wear cost
+    pub fn trade_settled(bess_id: u64, outcome: &TradeOutcome) -> Self {
+        SystemEvent::TradeSettled {
+            bess_id,
+            energy_sold: outcome.energy_amount,
+            revenue: outcome.revenue,
+            wear_cost: outcome.wear_cost,
+            profit: outcome.profit(),
+        }
+    }
+}
+
+impl From<HealthEvent> for SystemEvent {
+    fn from(event: HealthEvent) -> Self {
+        SystemEvent::BatteryHealthChanged {
+            bess_id: event.device_id,
+            previous_status: event.previous_status,
+            status: event.status,
+            state_of_health: event.state_of_health,
+        }
+    }
+}
+
 /// Connection events
 #[derive(Debug, Clone)]
 pub enum ConnectionEvent {
@@ -127,11 +169,13 @@ pub struct WebSocketGateway {
     event_tx: broadcast::Sender<SystemEvent>,
     connected_clients: Arc<RwLock<HashMap<Uuid, WebSocketClient>>>,
     metrics: Arc<RwLock<SystemMetrics>>,
+    schedules: Arc<RwLock<HashMap<u64, Vec<ScheduledDelivery>>>>, // Latest schedule by BESS id
     is_running: Arc<RwLock<bool>>,
 }
 
 /// WebSocket client information
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 struct WebSocketClient {
     id: Uuid,
     last_seen: std::time::Instant,
@@ -156,6 +200,7 @@ impl WebSocketGateway {
                 active_bess_nodes: 0,
                 active_aggregators: 0,
             })),
+            schedules: Arc::new(RwLock::new(HashMap::new())),
             is_running: Arc::new(RwLock::new(false)),
         };
         
@@ -172,11 +217,15 @@ impl WebSocketGateway {
 
         let app = Router::new()
             .route("/ws", get(websocket_handler))
+            .route("/termination-codes", get(termination_codes_handler))
+            .route("/schedules", get(schedules_handler))
+            .route("/schedules/:bess_id", get(schedule_handler))
             .layer(cors)
             .with_state(Arc::new(GatewayState {
                 event_tx: self.event_tx.clone(),
                 connected_clients: self.connected_clients.clone(),
                 metrics: self.metrics.clone(),
+                schedules: self.schedules.clone(),
             }));
 
         let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", self.port)).await?;
@@ -228,6 +277,21 @@ impl WebSocketGateway {
         Ok(())
     }
 
+    /// Receive the events broadcast from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<SystemEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Publish a BESS's committed deliveries, replacing the previous ones
+    pub async fn publish_schedule(&self, bess_id: u64, deliveries: Vec<ScheduledDelivery>) {
+        self.schedules.write().await.insert(bess_id, deliveries);
+    }
+
+    /// Get the last published schedule of a BESS
+    pub async fn schedule(&self, bess_id: u64) -> Option<Vec<ScheduledDelivery>> {
+        self.schedules.read().await.get(&bess_id).cloned()
+    }
+
     /// Handle connection event
     pub async fn handle_connection_event(&self, event: ConnectionEvent) {
         match event {
@@ -269,7 +333,9 @@ impl WebSocketGateway {
 struct GatewayState {
     event_tx: broadcast::Sender<SystemEvent>,
     connected_clients: Arc<RwLock<HashMap<Uuid, WebSocketClient>>>,
+    #[allow(dead_code)]
     metrics: Arc<RwLock<SystemMetrics>>,
+    schedules: Arc<RwLock<HashMap<u64, Vec<ScheduledDelivery>>>>,
 }
 
 /// WebSocket handler for Axum
@@ -280,6 +346,24 @@ async fn websocket_handler(
     ws.on_upgrade(|socket| websocket_connection(socket, state))
 }
 
+/// Termination code registry, so the UI labels codes the same way the protocol does
+async fn termination_codes_handler() -> Json<Vec<TerminationCodeInfo>> {
+    Json(TerminationCode::ALL.iter().map(|code| code.info()).collect())
+}
+
+/// Published schedules of all BESS nodes
+async fn schedules_handler(State(state): State<Arc<GatewayState>>) -> Json<HashMap<u64, Vec<ScheduledDelivery>>> {
+    Json(state.schedules.read().await.clone())
+}
+
+/// Published schedule of one BESS node
+async fn schedule_handler(
+    Path(bess_id): Path<u64>,
+    State(state): State<Arc<GatewayState>>,
+) -> std::result::Result<Json<Vec<ScheduledDelivery>>, StatusCode> {
+    state.schedules.read().await.get(&bess_id).cloned().map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
 /// Handle WebSocket connection
 async fn websocket_connection(socket: WebSocket, state: Arc<GatewayState>) {
     let client_id = Uuid::new_v4();
@@ -350,6 +434,7 @@ impl Clone for WebSocketGateway {
             event_tx: self.event_tx.clone(),
             connected_clients: self.connected_clients.clone(),
             metrics: self.metrics.clone(),
+            schedules: self.schedules.clone(),
             is_running: self.is_running.clone(),
         }
     }
@@ -383,4 +468,14 @@ mod tests {
         let metrics = gateway.get_system_metrics().await;
         assert!(metrics.total_events_broadcast > 0);
     }
+
+    #[tokio::test]
+    async fn test_published_schedule_by_bess_id() {
+        let gateway = WebSocketGateway::new(8080).await.unwrap();
+        assert!(gateway.schedule(123).await.is_none());
+
+        gateway.publish_schedule(123, Vec::new()).await;
+        assert_eq!(gateway.schedule(123).await, Some(Vec::new()));
+        assert!(gateway.schedule(124).await.is_none());
+    }
 }
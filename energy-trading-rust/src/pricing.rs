@@ -0,0 +1,308 @@
+use crate::bess_node::EnergyStatus;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What a node knows about a sale when pricing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricingContext {
+    pub reserve_price: f64,   // cents/kWh, the owner's base price
+    pub energy_status: EnergyStatus,
+    pub state_of_charge: f64, // 0-1
+    pub energy_amount: f64,   // kWh asked for
+    pub delivery_time: SystemTime, // Start of delivery
+}
+
+/// How a BESS sets the lowest price it accepts
+///
+/// Battery wear is added by the node on top of the policy's price.
+pub trait PricingPolicy: Debug + Send + Sync {
+    /// Get the lowest acceptable price for the sale (cents/kWh)
+    fn reserve_price(&self, context: &PricingContext) -> f64;
+}
+
+/// Always ask the owner's reserve price
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StaticPricing;
+
+impl PricingPolicy for StaticPricing {
+    fn reserve_price(&self, context: &PricingContext) -> f64 {
+        context.reserve_price
+    }
+}
+
+/// Scale the reserve price by how full the battery is
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SocTieredPricing {
+    pub critical: f64, // Multiplier below 10% energy
+    pub low: f64,      // 10-25% energy
+    pub normal: f64,   // 25-75% energy
+    pub high: f64,     // Above 75% energy
+}
+
+impl Default for SocTieredPricing {
+    fn default() -> Self {
+        Self {
+            critical: 2.0, // Double price when critical
+            low: 1.5,      // 50% premium when low
+            normal: 1.0,
+            high: 0.9,     // 10% discount when high
+        }
+    }
+}
+
+impl PricingPolicy for SocTieredPricing {
+    fn reserve_price(&self, context: &PricingContext) -> f64 {
+        let multiplier = match context.energy_status {
+            EnergyStatus::Critical => self.critical,
+            EnergyStatus::Low => self.low,
+            EnergyStatus::Normal => self.normal,
+            EnergyStatus::High => self.high,
+        };
+        context.reserve_price * multiplier
+    }
+}
+
+/// A daily tariff period, from `start_hour` up to but not including `end_hour`
+///
+/// A period with `end_hour` before `start_hour` wraps past midnight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "TariffPeriodFields")]
+pub struct TariffPeriod {
+    pub start_hour: u8, // 0-23
+    pub end_hour: u8,   // 0-24
+    pub multiplier: f64,
+}
+
+/// Unchecked fields of a TariffPeriod, as read from a config
+#[derive(Deserialize)]
+struct TariffPeriodFields {
+    start_hour: u8,
+    end_hour: u8,
+    multiplier: f64,
+}
+
+impl TryFrom<TariffPeriodFields> for TariffPeriod {
+    type Error = String;
+
+    fn try_from(fields: TariffPeriodFields) -> std::result::Result<Self, Self::Error> {
+        if fields.start_hour > 23 || fields.end_hour > 24 {
+            return Err(format!("tariff hours {}-{} out of range", fields.start_hour, fields.end_hour));
+        }
+        if fields.start_hour == fields.end_hour {
+            return Err(format!("tariff period {}-{} is empty", fields.start_hour, fields.end_hour));
+        }
+        Ok(TariffPeriod {
+            start_hour: fields.start_hour,
+            end_hour: fields.end_hour,
+            multiplier: fields.multiplier,
+        })
+    }
+}
+
+impl TariffPeriod {
+    /// Check whether the period covers an hour of the day
+    pub fn contains(&self, hour: u8) -> bool {
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+/// Scale the reserve price by the tariff period delivery starts in
+///
+/// Hours outside every period use the reserve price as is; the first
+/// matching period wins.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeOfUsePricing {
+    pub periods: Vec<TariffPeriod>,
+    pub utc_offset_minutes: i32, // Local time of the tariff
+}
+
+impl Default for TimeOfUsePricing {
+    fn default() -> Self {
+        Self {
+            periods: vec![
+                TariffPeriod { start_hour: 16, end_hour: 21, multiplier: 1.5 }, // Evening peak
+                TariffPeriod { start_hour: 10, end_hour: 15, multiplier: 0.8 }, // Solar soak
+            ],
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+impl TimeOfUsePricing {
+    /// Get the local hour of the day at `at`
+    pub fn hour_of_day(&self, at: SystemTime) -> u8 {
+        let unix_secs = at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        let local_secs = unix_secs + self.utc_offset_minutes as i64 * 60;
+        (local_secs.rem_euclid(24 * 3600) / 3600) as u8
+    }
+
+    /// Get the multiplier in force at `at`
+    pub fn multiplier_at(&self, at: SystemTime) -> f64 {
+        let hour = self.hour_of_day(at);
+        self.periods.iter().find(|period| period.contains(hour)).map_or(1.0, |period| period.multiplier)
+    }
+}
+
+impl PricingPolicy for TimeOfUsePricing {
+    fn reserve_price(&self, context: &PricingContext) -> f64 {
+        context.reserve_price * self.multiplier_at(context.delivery_time)
+    }
+}
+
+/// A forecast market price from `start` until the next forecast point
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceForecast {
+    pub start: SystemTime,
+    pub price: f64, // cents/kWh
+}
+
+/// Ask at least what the energy is expected to fetch later
+///
+/// Selling now gives up selling at the best forecast price within
+/// `horizon`, discounted by `risk_discount` for forecast error.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpportunityCostPricing {
+    pub forecast: Vec<PriceForecast>,
+    pub horizon: Duration,
+    pub risk_discount: f64, // Fraction taken off the forecast price
+}
+
+impl Default for OpportunityCostPricing {
+    fn default() -> Self {
+        Self {
+            forecast: Vec::new(),
+            horizon: Duration::from_secs(24 * 3600),
+            risk_discount: 0.1,
+        }
+    }
+}
+
+impl OpportunityCostPricing {
+    /// Get the best forecast price from `from` to the end of the horizon, if any
+    ///
+    /// A horizon too long to add to `from` has no end.
+    pub fn best_price(&self, from: SystemTime) -> Option<f64> {
+        let until = from.checked_add(self.horizon);
+        let mut sorted: Vec<_> = self.forecast.iter().collect();
+        sorted.sort_by_key(|point| point.start);
+        sorted
+            .iter()
+            .enumerate()
+            .filter(|(i, point)| {
+                let ends = sorted.get(i + 1).map(|next| next.start);
+                until.is_none_or(|until| point.start < until) && ends.is_none_or(|ends| ends > from)
+            })
+            .map(|(_, point)| point.price)
+            .reduce(f64::max)
+    }
+}
+
+impl PricingPolicy for OpportunityCostPricing {
+    fn reserve_price(&self, context: &PricingContext) -> f64 {
+        let expected = self.best_price(context.delivery_time).unwrap_or(0.0) * (1.0 - self.risk_discount);
+        context.reserve_price.max(expected)
+    }
+}
+
+/// Built-in pricing policy of a node, as stored in its config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "kebab-case")]
+pub enum PricingConfig {
+    Static,
+    SocTiered(SocTieredPricing),
+    TimeOfUse(TimeOfUsePricing),
+    OpportunityCost(OpportunityCostPricing),
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        PricingConfig::SocTiered(SocTieredPricing::default())
+    }
+}
+
+impl PricingPolicy for PricingConfig {
+    fn reserve_price(&self, context: &PricingContext) -> f64 {
+        match self {
+            PricingConfig::Static => StaticPricing.reserve_price(context),
+            PricingConfig::SocTiered(policy) => policy.reserve_price(context),
+            PricingConfig::TimeOfUse(policy) => policy.reserve_price(context),
+            PricingConfig::OpportunityCost(policy) => policy.reserve_price(context),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn context(energy_status: EnergyStatus, delivery_time: SystemTime) -> PricingContext {
+        PricingContext {
+            reserve_price: 10.0,
+            energy_status,
+            state_of_charge: 0.5,
+            energy_amount: 5.0,
+            delivery_time,
+        }
+    }
+
+    #[test]
+    fn test_builtin_policies() {
+        let midnight = UNIX_EPOCH + HOUR * 24 * 20000;
+        assert_eq!(StaticPricing.reserve_price(&context(EnergyStatus::Critical, midnight)), 10.0);
+        assert_eq!(SocTieredPricing::default().reserve_price(&context(EnergyStatus::Critical, midnight)), 20.0);
+        assert_eq!(SocTieredPricing::default().reserve_price(&context(EnergyStatus::High, midnight)), 9.0);
+
+        let tariff = TimeOfUsePricing::default();
+        assert_eq!(tariff.reserve_price(&context(EnergyStatus::Normal, midnight + HOUR * 17)), 15.0);
+        assert_eq!(tariff.reserve_price(&context(EnergyStatus::Normal, midnight + HOUR * 12)), 8.0);
+        assert_eq!(tariff.reserve_price(&context(EnergyStatus::Normal, midnight + HOUR * 3)), 10.0);
+        let eastern = TimeOfUsePricing { utc_offset_minutes: 600, ..TimeOfUsePricing::default() };
+        assert_eq!(eastern.hour_of_day(midnight + HOUR * 7), 17);
+        let overnight = TariffPeriod { start_hour: 22, end_hour: 6, multiplier: 0.5 };
+        assert!(overnight.contains(23) && overnight.contains(2) && !overnight.contains(6));
+
+        let forecast = OpportunityCostPricing {
+            forecast: vec![
+                PriceForecast { start: midnight + HOUR * 18, price: 30.0 },
+                PriceForecast { start: midnight, price: 12.0 },
+                PriceForecast { start: midnight + HOUR * 20, price: 8.0 },
+            ],
+            horizon: HOUR * 12,
+            risk_discount: 0.1,
+        };
+        assert_eq!(forecast.best_price(midnight), Some(12.0)); // The 30c peak is beyond the horizon
+        assert!((forecast.reserve_price(&context(EnergyStatus::Normal, midnight)) - 10.8).abs() < 1e-9);
+        assert!((forecast.reserve_price(&context(EnergyStatus::Normal, midnight + HOUR * 10)) - 27.0).abs() < 1e-9);
+        assert_eq!(forecast.reserve_price(&context(EnergyStatus::Normal, midnight + HOUR * 48)), 10.0);
+        let endless = OpportunityCostPricing { horizon: Duration::MAX, ..forecast };
+        assert_eq!(endless.best_price(midnight), Some(30.0));
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let config: PricingConfig = serde_json::from_str(r#"{"policy":"static"}"#).unwrap();
+        assert_eq!(config, PricingConfig::Static);
+        let config: PricingConfig =
+            serde_json::from_str(r#"{"policy":"soc-tiered","critical":3.0,"low":1.5,"normal":1.0,"high":1.0}"#).unwrap();
+        assert_eq!(config.reserve_price(&context(EnergyStatus::Critical, UNIX_EPOCH)), 30.0);
+
+        let config = PricingConfig::TimeOfUse(TimeOfUsePricing::default());
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.starts_with(r#"{"policy":"time-of-use""#));
+        assert_eq!(serde_json::from_str::<PricingConfig>(&json).unwrap(), config);
+
+        for (start_hour, end_hour) in [(25, 6), (22, 25), (8, 8)] {
+            let json = format!(
+                r#"{{"policy":"time-of-use","periods":[{{"start_hour":{start_hour},"end_hour":{end_hour},"multiplier":1.0}}],"utc_offset_minutes":0}}"#
+            );
+            assert!(serde_json::from_str::<PricingConfig>(&json).is_err(), "accepted {start_hour}-{end_hour}");
+        }
+    }
+}
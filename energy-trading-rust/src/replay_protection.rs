@@ -0,0 +1,307 @@
+use crate::envelope::{unix_time_ms, SignedEnvelope, PUBLIC_KEY_SIZE};
+use crate::etp_message::ETPMessage;
+use crate::error::{ETPError, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+/// Default number of messages remembered by a replay cache
+pub const DEFAULT_REPLAY_CACHE_CAPACITY: usize = 10_000;
+
+/// Default window in which a message is considered fresh
+pub const DEFAULT_REPLAY_WINDOW: Duration = Duration::from_secs(60);
+
+/// Monotonic message_id sequence for one sender
+///
+/// Starts from the current time shifted left by 12 bits, so ids keep
+/// increasing across restarts as long as fewer than 4096 are issued per
+/// millisecond.
+#[derive(Debug)]
+pub struct MessageIdGenerator {
+    next: AtomicU64,
+}
+
+impl Default for MessageIdGenerator {
+    fn default() -> Self {
+        Self::starting_at(unix_time_ms() << 12)
+    }
+}
+
+impl MessageIdGenerator {
+    /// Create a generator seeded from the current time
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a generator that hands out `first`, `first + 1`, ...
+    pub fn starting_at(first: u64) -> Self {
+        Self {
+            next: AtomicU64::new(first),
+        }
+    }
+
+    /// Get the next message_id
+    pub fn next_id(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// Next message_id from the process-wide sequence
+pub fn next_message_id() -> u64 {
+    static GENERATOR: OnceLock<MessageIdGenerator> = OnceLock::new();
+    GENERATOR.get_or_init(MessageIdGenerator::new).next_id()
+}
+
+/// Identity of a message for duplicate detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReplayKey {
+    /// Signed envelope, identified by the signer and its random nonce
+    Signed {
+        public_key: [u8; PUBLIC_KEY_SIZE],
+        nonce: u64,
+    },
+    /// Bare message, identified by what the sender claims about itself
+    Unsigned {
+        device_id: u64,
+        message_type: u8,
+        message_id: u64,
+    },
+}
+
+/// Remembered message
+#[derive(Debug, Clone, Copy)]
+struct ReplayEntry {
+    key: ReplayKey,
+    timestamp_ms: u64,
+    expires_at_ms: u64,
+}
+
+#[derive(Debug, Default)]
+struct ReplayState {
+    seen: HashMap<ReplayKey, u64>, // Key -> expiry
+    order: VecDeque<ReplayEntry>,  // Insertion order, for expiry and eviction
+    low_water_ms: u64,             // Signed messages at or before this time are refused
+}
+
+/// Bounded cache of recently seen messages
+///
+/// Signed envelopes are refused when their timestamp lies outside the
+/// window around the local clock, or when their nonce has been seen before.
+/// Bare messages carry no timestamp, so they are refused only while the same
+/// `(device_id, message_type, message_id)` is still remembered.
+///
+/// When the cache is full the oldest entry is evicted and every signed
+/// message timestamped at or before it is refused from then on, so replays
+/// cannot slip through an eviction. Bare messages have no such guarantee.
+#[derive(Debug)]
+pub struct ReplayCache {
+    capacity: usize,
+    window_ms: u64,
+    state: Mutex<ReplayState>,
+}
+
+impl Default for ReplayCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPLAY_CACHE_CAPACITY, DEFAULT_REPLAY_WINDOW)
+    }
+}
+
+impl ReplayCache {
+    /// Create a cache remembering at most `capacity` messages for `window`
+    pub fn new(capacity: usize, window: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            window_ms: window.as_millis() as u64,
+            state: Mutex::new(ReplayState::default()),
+        }
+    }
+
+    /// Number of messages currently remembered
+    pub fn len(&self) -> usize {
+        self.lock().order.len()
+    }
+
+    /// Check whether no messages are remembered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record a signed envelope, refusing stale or duplicate ones
+    pub fn check_envelope(&self, envelope: &SignedEnvelope) -> Result<()> {
+        self.check_envelope_at(envelope, unix_time_ms())
+    }
+
+    /// Record a signed envelope against an explicit local time
+    pub fn check_envelope_at(&self, envelope: &SignedEnvelope, now_ms: u64) -> Result<()> {
+        let timestamp_ms = envelope.timestamp_ms;
+        if timestamp_ms.abs_diff(now_ms) > self.window_ms {
+            return Err(ETPError::Replay(format!(
+                "Stale message {} from device {}: timestamp {}ms is outside the {}ms window",
+                envelope.message.message_id, envelope.message.device_id, timestamp_ms, self.window_ms
+            )));
+        }
+
+        let key = ReplayKey::Signed {
+            public_key: envelope.sender_public_key,
+            nonce: envelope.nonce,
+        };
+        // Keep the nonce until the timestamp itself has gone stale
+        self.record(key, timestamp_ms, timestamp_ms + self.window_ms, now_ms)
+    }
+
+    /// Record a bare message, refusing duplicates
+    pub fn check_message(&self, message: &ETPMessage) -> Result<()> {
+        self.check_message_at(message, unix_time_ms())
+    }
+
+    /// Record a bare message against an explicit local time
+    pub fn check_message_at(&self, message: &ETPMessage, now_ms: u64) -> Result<()> {
+        let key = ReplayKey::Unsigned {
+            device_id: message.device_id,
+            message_type: message.message_type,
+            message_id: message.message_id,
+        };
+        self.record(key, now_ms, now_ms + self.window_ms, now_ms)
+    }
+
+    fn record(&self, key: ReplayKey, timestamp_ms: u64, expires_at_ms: u64, now_ms: u64) -> Result<()> {
+        let mut state = self.lock();
+
+        // Drop expired entries; entries queued behind a longer-lived one linger a little
+        while state.order.front().is_some_and(|entry| entry.expires_at_ms <= now_ms) {
+            let entry = state.order.pop_front().unwrap();
+            if state.seen.get(&entry.key) == Some(&entry.expires_at_ms) {
+                state.seen.remove(&entry.key);
+            }
+        }
+
+        let duplicate = state.seen.get(&key).is_some_and(|&expires| expires > now_ms);
+        let evicted = matches!(key, ReplayKey::Signed { .. }) && timestamp_ms <= state.low_water_ms;
+        if duplicate || evicted {
+            return Err(ETPError::Replay(format!("Duplicate message {:?}", key)));
+        }
+
+        if state.order.len() >= self.capacity {
+            if let Some(oldest) = state.order.pop_front() {
+                // A key recorded again since is remembered by its newer entry
+                if state.seen.get(&oldest.key) == Some(&oldest.expires_at_ms) {
+                    state.seen.remove(&oldest.key);
+                }
+                if matches!(oldest.key, ReplayKey::Signed { .. }) {
+                    state.low_water_ms = state.low_water_ms.max(oldest.timestamp_ms);
+                }
+            }
+        }
+
+        state.seen.insert(key, expires_at_ms);
+        state.order.push_back(ReplayEntry {
+            key,
+            timestamp_ms,
+            expires_at_ms,
+        });
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ReplayState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::MessageSigner;
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    #[test]
+    fn test_message_ids_are_monotonic() {
+        let generator = MessageIdGenerator::starting_at(10);
+        assert_eq!(generator.next_id(), 10);
+        assert_eq!(generator.next_id(), 11);
+
+        let first = next_message_id();
+        assert!(next_message_id() > first);
+    }
+
+    #[test]
+    fn test_duplicate_envelope_rejected() {
+        let cache = ReplayCache::new(16, Duration::from_secs(60));
+        let signer = MessageSigner::generate();
+        let envelope = signer.sign_with(ETPMessage::new_bid_accept(1, 100, 18.0, 5.0), 7, NOW).unwrap();
+
+        assert!(cache.check_envelope_at(&envelope, NOW).is_ok());
+        assert!(matches!(cache.check_envelope_at(&envelope, NOW + 1_000), Err(ETPError::Replay(_))));
+    }
+
+    #[test]
+    fn test_stale_envelope_rejected() {
+        let cache = ReplayCache::new(16, Duration::from_secs(60));
+        let signer = MessageSigner::generate();
+        let envelope = signer.sign_with(ETPMessage::new_bid_accept(1, 100, 18.0, 5.0), 7, NOW).unwrap();
+
+        assert!(matches!(cache.check_envelope_at(&envelope, NOW + 60_001), Err(ETPError::Replay(_))));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_unsigned_duplicate_expires() {
+        let cache = ReplayCache::new(16, Duration::from_secs(60));
+        let query = ETPMessage::new_query(1234, 200);
+
+        assert!(cache.check_message_at(&query, NOW).is_ok());
+        assert!(cache.check_message_at(&query, NOW + 59_999).is_err());
+        assert!(cache.check_message_at(&query, NOW + 60_000).is_ok());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_eviction_keeps_key_recorded_again() {
+        let cache = ReplayCache::new(3, Duration::from_secs(60));
+        let signer = MessageSigner::generate();
+        let ahead = signer.sign_with(ETPMessage::new_bid_accept(1, 100, 18.0, 5.0), 7, NOW + 50_000).unwrap();
+        let query = ETPMessage::new_query(1234, 200);
+
+        // The first query entry outlives its expiry behind the signed one, then the query comes again
+        assert!(cache.check_envelope_at(&ahead, NOW).is_ok());
+        assert!(cache.check_message_at(&query, NOW).is_ok());
+        assert!(cache.check_message_at(&query, NOW + 60_000).is_ok());
+
+        // After the clock steps back, evicting the stale entry must not forget the live one
+        assert!(cache.check_message_at(&ETPMessage::new_query(1235, 200), NOW + 30_000).is_ok());
+        assert!(cache.check_message_at(&ETPMessage::new_query(1236, 200), NOW + 30_001).is_ok());
+        assert!(matches!(cache.check_message_at(&query, NOW + 30_002), Err(ETPError::Replay(_))));
+    }
+
+    #[test]
+    fn test_message_id_collisions_across_senders() {
+        let cache = ReplayCache::new(16, Duration::from_secs(60));
+        let mut first = ETPMessage::new_bid(1234, 15.5, 10.0);
+        first.device_id = 1;
+        let mut second = first.clone();
+        second.device_id = 2;
+        let query = ETPMessage::new_query(1234, 1);
+
+        assert!(cache.check_message_at(&first, NOW).is_ok());
+        assert!(cache.check_message_at(&second, NOW).is_ok());
+        assert!(cache.check_message_at(&query, NOW).is_ok());
+    }
+
+    #[test]
+    fn test_capacity_is_bounded() {
+        let cache = ReplayCache::new(2, Duration::from_secs(60));
+        let signer = MessageSigner::generate();
+        let envelopes: Vec<_> = (0..3)
+            .map(|i| signer.sign_with(ETPMessage::new_bid(i, 15.5, 10.0), i, NOW + i).unwrap())
+            .collect();
+
+        for envelope in &envelopes {
+            assert!(cache.check_envelope_at(envelope, NOW + 10).is_ok());
+        }
+        assert_eq!(cache.len(), 2);
+
+        // The evicted envelope is still refused
+        assert!(cache.check_envelope_at(&envelopes[0], NOW + 10).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use crate::delivery::DeliveryWindow;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// How long an accepted bid holds its energy by default while waiting for the BidConfirm
+pub const DEFAULT_HOLD_DURATION: Duration = Duration::from_secs(5);
+
+/// Energy set aside for an accepted bid until it is confirmed, released or expires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnergyHold {
+    pub bid_id: u64,        // message_id of the bid
+    pub aggregator_id: u64, // Bidder
+    pub sale_price: f64,    // cents/kWh, as accepted
+    pub energy_amount: f64, // kWh
+    pub window: Option<DeliveryWindow>, // Delivery window, if the bid carried one
+    pub expires_at: SystemTime,
+}
+
+impl EnergyHold {
+    /// Check whether the hold has lapsed by `now`
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Check whether a BidConfirm's price and energy are the ones accepted
+    pub fn matches(&self, sale_price: f64, energy_amount: f64) -> bool {
+        (self.sale_price - sale_price).abs() < 1e-6 && (self.energy_amount - energy_amount).abs() < 1e-6
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hold_expiry_and_matching() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let hold = EnergyHold {
+            bid_id: 1,
+            aggregator_id: 42,
+            sale_price: 18.0,
+            energy_amount: 10.0,
+            window: None,
+            expires_at: now + DEFAULT_HOLD_DURATION,
+        };
+        assert!(!hold.is_expired(now));
+        assert!(hold.is_expired(now + DEFAULT_HOLD_DURATION));
+        assert!(hold.matches(18.0, 10.0));
+        assert!(!hold.matches(18.0, 9.0));
+        assert!(!hold.matches(17.5, 10.0));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::error::{ETPError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Protocol stage of one aggregator-BESS conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum SessionState {
+    #[default]
+    Connected,
+    Registered,
+    Queried,    // Ready for a bid; also where a rejected bid leaves the session
+    BidPending, // Bid sent, no answer yet
+    Accepted,   // Bid accepted, waiting for the aggregator's confirm
+    Confirmed,  // Trade agreed; a new round may start with a Query or Bid
+    Terminated, // Final; nothing more is accepted
+}
+
+impl SessionState {
+    /// Get the name of this state
+    pub fn name(self) -> &'static str {
+        match self {
+            SessionState::Connected => "Connected",
+            SessionState::Registered => "Registered",
+            SessionState::Queried => "Queried",
+            SessionState::BidPending => "BidPending",
+            SessionState::Accepted => "Accepted",
+            SessionState::Confirmed => "Confirmed",
+            SessionState::Terminated => "Terminated",
+        }
+    }
+}
+
+impl fmt::Display for SessionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Tracks which protocol stage a connection is in
+///
+/// Feed it every message sent or received on the connection; each message
+/// type has one sender, so the direction does not matter. Transitions:
+///
+/// | message          | allowed in                                  | next state   |
+/// |------------------|---------------------------------------------|--------------|
+/// | Register         | Connected                                   | Registered   |
+/// | Query            | any but BidPending, Accepted                | Queried      |
+/// | QueryResponse    | Queried                                     | Queried      |
+/// | Bid              | any but BidPending, Accepted                | BidPending   |
+/// | BidAccept        | BidPending, same message_id as the bid      | Accepted     |
+/// | BidReject        | BidPending, same message_id as the bid      | Queried      |
+/// | BidConfirm       | Accepted, same message_id as the bid        | Confirmed    |
+/// | Terminate        | any                                         | Terminated   |
+/// | BESSStatus, DeviceFailure | any                                | unchanged    |
+///
+/// Registering is optional because the Go prototype starts with a Query.
+/// Nothing is allowed once the session is Terminated.
+#[derive(Debug, Clone, Default)]
+pub struct EtpSession {
+    state: SessionState,
+    bid_id: Option<u64>, // message_id of the bid being negotiated
+}
+
+impl EtpSession {
+    /// Create a session for a freshly opened connection
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the current protocol stage
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// Get the message_id of the bid awaiting an answer or a confirm
+    pub fn pending_bid(&self) -> Option<u64> {
+        self.bid_id
+    }
+
+    /// Get the state `message` would move the session to, without moving it
+    ///
+    /// Fails with `ETPError::ProtocolViolation` if the message is out of order.
+    pub fn check(&self, message: &ETPMessage) -> Result<SessionState> {
+        self.next_state(message).map_err(|reason| ETPError::ProtocolViolation {
+            state: self.state,
+            message_type: message.message_type,
+            reason,
+        })
+    }
+
+    /// Record a message sent or received on the connection and return the new state
+    ///
+    /// An out-of-order message fails with `ETPError::ProtocolViolation` and
+    /// leaves the session unchanged.
+    pub fn advance(&mut self, message: &ETPMessage) -> Result<SessionState> {
+        let next = self.check(message)?;
+        match next {
+            SessionState::BidPending => self.bid_id = Some(message.message_id),
+            SessionState::Accepted => {}
+            _ => self.bid_id = None,
+        }
+        self.state = next;
+        Ok(next)
+    }
+
+    /// Return to Queried after an accepted bid fell through
+    ///
+    /// Used when the BESS released the bid's hold or refused its confirm, so
+    /// the aggregator may query or bid again. A terminated session stays terminated.
+    pub fn reopen(&mut self) {
+        if self.state != SessionState::Terminated {
+            self.state = SessionState::Queried;
+            self.bid_id = None;
+        }
+    }
+
+    fn next_state(&self, message: &ETPMessage) -> std::result::Result<SessionState, &'static str> {
+        use SessionState::*;
+
+        if self.state == Terminated {
+            return Err("session terminated");
+        }
+        let negotiating = matches!(self.state, BidPending | Accepted);
+        match message.kind().map_err(|_| "unknown message type")? {
+            MessageType::BESSStatus | MessageType::DeviceFailure => Ok(self.state),
+            MessageType::Terminate => Ok(Terminated),
+            MessageType::Register if self.state == Connected => Ok(Registered),
+            MessageType::Register => Err("already registered"),
+            MessageType::Query | MessageType::Bid if negotiating => Err("a bid is still being negotiated"),
+            MessageType::Query => Ok(Queried),
+            MessageType::Bid => Ok(BidPending),
+            MessageType::QueryResponse if self.state == Queried => Ok(Queried),
+            MessageType::QueryResponse => Err("no query to answer"),
+            MessageType::BidAccept => self.answer_bid(message, BidPending, Accepted),
+            MessageType::BidReject => self.answer_bid(message, BidPending, Queried),
+            MessageType::BidConfirm => self.answer_bid(message, Accepted, Confirmed),
+        }
+    }
+
+    /// Check that `message` answers the bid in flight while the session is `expected`
+    fn answer_bid(
+        &self,
+        message: &ETPMessage,
+        expected: SessionState,
+        next: SessionState,
+    ) -> std::result::Result<SessionState, &'static str> {
+        if self.state != expected {
+            return Err(match expected {
+                SessionState::Accepted => "no accepted bid to confirm",
+                _ => "no bid to answer",
+            });
+        }
+        match self.bid_id == Some(message.message_id) {
+            true => Ok(next),
+            false => Err("message_id does not match the bid"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::termination::TerminationCode;
+
+    #[test]
+    fn test_full_trade() {
+        let mut session = EtpSession::new();
+        assert_eq!(session.advance(&ETPMessage::new_register(1, 200)).unwrap(), SessionState::Registered);
+        assert_eq!(session.advance(&ETPMessage::new_query(2, 200)).unwrap(), SessionState::Queried);
+        assert_eq!(session.advance(&ETPMessage::new_query_response(2, 100, 50.0, 20.0)).unwrap(), SessionState::Queried);
+        assert_eq!(session.advance(&ETPMessage::new_bid(3, 18.0, 5.0)).unwrap(), SessionState::BidPending);
+        assert_eq!(session.pending_bid(), Some(3));
+        assert_eq!(session.advance(&ETPMessage::new_bid_accept(3, 100, 18.0, 5.0)).unwrap(), SessionState::Accepted);
+        assert_eq!(session.advance(&ETPMessage::new_bid_confirm(3, 200, 18.0, 5.0)).unwrap(), SessionState::Confirmed);
+        assert_eq!(session.pending_bid(), None);
+
+        // Another round on the same connection
+        assert_eq!(session.advance(&ETPMessage::new_bid(4, 18.0, 5.0)).unwrap(), SessionState::BidPending);
+        assert_eq!(
+            session.advance(&ETPMessage::new_terminate(4, 200, TerminationCode::Normal)).unwrap(),
+            SessionState::Terminated
+        );
+    }
+
+    #[test]
+    fn test_rejected_bid_can_be_retried() {
+        // Go prototype flow: no Register, bid again after a reject
+        let mut session = EtpSession::new();
+        session.advance(&ETPMessage::new_query(1, 200)).unwrap();
+        session.advance(&ETPMessage::new_bid(2, 10.0, 5.0)).unwrap();
+        assert_eq!(
+            session.advance(&ETPMessage::new_bid_reject(2, 100, TerminationCode::PriceBelowReserve)).unwrap(),
+            SessionState::Queried
+        );
+        assert_eq!(session.advance(&ETPMessage::new_bid(3, 16.0, 5.0)).unwrap(), SessionState::BidPending);
+    }
+
+    #[test]
+    fn test_out_of_order_messages_rejected() {
+        let mut session = EtpSession::new();
+        let violation = session.advance(&ETPMessage::new_bid_confirm(7, 200, 18.0, 5.0));
+        assert!(matches!(
+            violation,
+            Err(ETPError::ProtocolViolation { state: SessionState::Connected, message_type: 5, .. })
+        ));
+        assert_eq!(session.state(), SessionState::Connected);
+
+        session.advance(&ETPMessage::new_bid(8, 18.0, 5.0)).unwrap();
+        assert!(session.advance(&ETPMessage::new_bid(9, 18.0, 5.0)).is_err()); // Still negotiating
+        assert!(session.advance(&ETPMessage::new_bid_accept(9, 100, 18.0, 5.0)).is_err()); // Wrong bid
+        session.advance(&ETPMessage::new_bid_accept(8, 100, 18.0, 5.0)).unwrap();
+        assert!(session.advance(&ETPMessage::new_bid_confirm(9, 200, 18.0, 5.0)).is_err()); // Never accepted
+        assert!(session.advance(&ETPMessage::new_query(10, 200)).is_err());
+
+        // Status and failure reports never change the stage
+        session.advance(&ETPMessage::new_device_failure(11, 100, TerminationCode::BatteryFault)).unwrap();
+        assert_eq!(session.state(), SessionState::Accepted);
+
+        session.advance(&ETPMessage::new_terminate(12, 200, TerminationCode::Normal)).unwrap();
+        assert!(session.advance(&ETPMessage::new_query(13, 200)).is_err());
+        assert_eq!(session.state(), SessionState::Terminated);
+    }
+
+    #[test]
+    fn test_reopen_after_bid_falls_through() {
+        let mut session = EtpSession::new();
+        session.advance(&ETPMessage::new_bid(1, 18.0, 5.0)).unwrap();
+        session.advance(&ETPMessage::new_bid_accept(1, 100, 18.0, 5.0)).unwrap();
+        assert!(session.advance(&ETPMessage::new_query(2, 200)).is_err());
+
+        session.reopen();
+        assert_eq!((session.state(), session.pending_bid()), (SessionState::Queried, None));
+        session.advance(&ETPMessage::new_bid(3, 18.0, 5.0)).unwrap();
+
+        session.advance(&ETPMessage::new_terminate(4, 200, TerminationCode::Normal)).unwrap();
+        session.reopen();
+        assert_eq!(session.state(), SessionState::Terminated);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::error::SerializationError;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// What kind of problem ended a trade or session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TerminationCategory {
+    Economic, // The price was not right
+    Physical, // The battery could not deliver
+    Protocol, // The exchange itself ended or went wrong
+}
+
+impl TerminationCategory {
+    /// Get the name of this category
+    pub fn name(self) -> &'static str {
+        match self {
+            TerminationCategory::Economic => "Economic",
+            TerminationCategory::Physical => "Physical",
+            TerminationCategory::Protocol => "Protocol",
+        }
+    }
+}
+
+impl fmt::Display for TerminationCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Reason carried in the termination_code of BidReject, Terminate and DeviceFailure
+///
+/// Wire values are stable: new codes are only ever appended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum TerminationCode {
+    Normal = 0,
+    PriceBelowReserve = 1,
+    InsufficientEnergy = 2,
+    Offline = 3,
+    TtlExpired = 4,
+    NotCompetitive = 5,
+    BatteryFault = 6,
+    InvalidMessage = 7,
+}
+
+impl TerminationCode {
+    /// All termination codes in wire order
+    pub const ALL: [TerminationCode; 8] = [
+        TerminationCode::Normal,
+        TerminationCode::PriceBelowReserve,
+        TerminationCode::InsufficientEnergy,
+        TerminationCode::Offline,
+        TerminationCode::TtlExpired,
+        TerminationCode::NotCompetitive,
+        TerminationCode::BatteryFault,
+        TerminationCode::InvalidMessage,
+    ];
+
+    /// Get the wire value of this code
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Get the name of this code
+    pub fn name(self) -> &'static str {
+        match self {
+            TerminationCode::Normal => "Normal",
+            TerminationCode::PriceBelowReserve => "PriceBelowReserve",
+            TerminationCode::InsufficientEnergy => "InsufficientEnergy",
+            TerminationCode::Offline => "Offline",
+            TerminationCode::TtlExpired => "TtlExpired",
+            TerminationCode::NotCompetitive => "NotCompetitive",
+            TerminationCode::BatteryFault => "BatteryFault",
+            TerminationCode::InvalidMessage => "InvalidMessage",
+        }
+    }
+
+    /// Get a human-readable description
+    pub fn description(self) -> &'static str {
+        match self {
+            TerminationCode::Normal => "Completed normally",
+            TerminationCode::PriceBelowReserve => "Bid price below reserve price",
+            TerminationCode::InsufficientEnergy => "Insufficient energy available",
+            TerminationCode::Offline => "BESS is offline",
+            TerminationCode::TtlExpired => "Message ran out of hops",
+            TerminationCode::NotCompetitive => "Bid not competitive enough",
+            TerminationCode::BatteryFault => "Battery fault",
+            TerminationCode::InvalidMessage => "Message failed validation",
+        }
+    }
+
+    /// Get the category used to aggregate rejection reasons
+    pub fn category(self) -> TerminationCategory {
+        match self {
+            TerminationCode::PriceBelowReserve | TerminationCode::NotCompetitive => TerminationCategory::Economic,
+            TerminationCode::InsufficientEnergy | TerminationCode::Offline | TerminationCode::BatteryFault => {
+                TerminationCategory::Physical
+            }
+            TerminationCode::Normal | TerminationCode::TtlExpired | TerminationCode::InvalidMessage => {
+                TerminationCategory::Protocol
+            }
+        }
+    }
+
+    /// Get everything known about this code, e.g. for a UI legend
+    pub fn info(self) -> TerminationCodeInfo {
+        TerminationCodeInfo {
+            value: self.as_u8(),
+            name: self.name(),
+            description: self.description(),
+            category: self.category(),
+        }
+    }
+}
+
+impl TryFrom<u8> for TerminationCode {
+    type Error = SerializationError;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        TerminationCode::ALL
+            .get(value as usize)
+            .copied()
+            .ok_or(SerializationError::InvalidTerminationCode(value))
+    }
+}
+
+impl From<TerminationCode> for u8 {
+    fn from(code: TerminationCode) -> Self {
+        code.as_u8()
+    }
+}
+
+impl fmt::Display for TerminationCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// Registry entry for one termination code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TerminationCodeInfo {
+    pub value: u8,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub category: TerminationCategory,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wire_values_are_stable() {
+        for (value, code) in TerminationCode::ALL.iter().enumerate() {
+            assert_eq!(code.as_u8() as usize, value);
+            assert_eq!(TerminationCode::try_from(value as u8).unwrap(), *code);
+        }
+        assert_eq!(TerminationCode::PriceBelowReserve.as_u8(), 1);
+        assert_eq!(TerminationCode::InsufficientEnergy.as_u8(), 2);
+        assert_eq!(TerminationCode::Offline.as_u8(), 3);
+        assert_eq!(TerminationCode::TtlExpired.as_u8(), 4);
+        assert!(TerminationCode::try_from(200).is_err());
+    }
+
+    #[test]
+    fn test_categories() {
+        assert_eq!(TerminationCode::PriceBelowReserve.category(), TerminationCategory::Economic);
+        assert_eq!(TerminationCode::BatteryFault.category(), TerminationCategory::Physical);
+        assert_eq!(TerminationCode::TtlExpired.category(), TerminationCategory::Protocol);
+
+        let info = TerminationCode::Offline.info();
+        assert_eq!(serde_json::to_value(&info).unwrap(), serde_json::json!({
+            "value": 3,
+            "name": "Offline",
+            "description": "BESS is offline",
+            "category": "Physical",
+        }));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::etp_message::{ETPMessage, MessageType};
+use crate::error::{ETPError, Result};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Rule a message field can break
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ValidationRule {
+    KnownMessageType, // message_type is 0-9
+    NonZeroTtl,       // ttl has not run out
+    Finite,           // Not NaN or infinite
+    NonNegative,      // >= 0
+    Required,         // > 0 because the message type needs it
+    Percentage,       // <= 100
+    PriceCap,         // <= ValidationRules::max_price
+    VoltageBand,      // Within ValidationRules::voltage_band
+}
+
+impl ValidationRule {
+    /// Get a short description of the rule
+    pub fn description(self) -> &'static str {
+        match self {
+            ValidationRule::KnownMessageType => "must be a known message type (0-9)",
+            ValidationRule::NonZeroTtl => "must be greater than 0",
+            ValidationRule::Finite => "must be a finite number",
+            ValidationRule::NonNegative => "cannot be negative",
+            ValidationRule::Required => "is required for this message type",
+            ValidationRule::Percentage => "cannot exceed 100%",
+            ValidationRule::PriceCap => "exceeds the price cap",
+            ValidationRule::VoltageBand => "is outside the voltage band",
+        }
+    }
+}
+
+/// One broken rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationViolation {
+    pub field: &'static str,
+    pub rule: ValidationRule,
+    pub value: f64, // Integer fields are widened to f64
+}
+
+impl fmt::Display for ValidationViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {} {}", self.field, self.value, self.rule.description())
+    }
+}
+
+/// Deployment-specific field rules for ETP messages
+///
+/// Every message must have a known type and finite, non-negative prices and
+/// energy amounts. Bids need a price and an energy amount; accepts and
+/// confirms need an energy amount. The price cap and voltage band only
+/// apply when configured.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationRules {
+    pub require_ttl: bool,                // Reject messages whose TTL has run out
+    pub max_price: Option<f64>,           // Cap on bid and sale prices (c/kWh)
+    pub voltage_band: Option<(f64, f64)>, // Accepted BESSStatus battery voltage (V)
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        Self {
+            require_ttl: true,
+            max_price: None,
+            voltage_band: None,
+        }
+    }
+}
+
+impl ValidationRules {
+    /// Collect every rule the message breaks
+    pub fn check(&self, message: &ETPMessage) -> Vec<ValidationViolation> {
+        let mut violations = Vec::new();
+        let mut violate = |field, rule, value| violations.push(ValidationViolation { field, rule, value });
+
+        let message_type = match MessageType::try_from(message.message_type) {
+            Ok(message_type) => Some(message_type),
+            Err(_) => {
+                violate("message_type", ValidationRule::KnownMessageType, message.message_type as f64);
+                None
+            }
+        };
+
+        if self.require_ttl && message.ttl == 0 {
+            violate("ttl", ValidationRule::NonZeroTtl, 0.0);
+        }
+
+        let amounts = [
+            ("bid_price", message.bid_price),
+            ("sale_price", message.sale_price),
+            ("energy_total", message.energy_total),
+            ("percentage_for_sale", message.percentage_for_sale),
+            ("required_energy_amount", message.required_energy_amount),
+            ("remaining_battery_energy", message.remaining_battery_energy),
+            ("battery_voltage", message.battery_voltage),
+        ];
+        for (field, value) in amounts {
+            if !value.is_finite() {
+                violate(field, ValidationRule::Finite, value);
+            } else if value < 0.0 {
+                violate(field, ValidationRule::NonNegative, value);
+            }
+        }
+        if !message.discharge_rate.is_finite() {
+            violate("discharge_rate", ValidationRule::Finite, message.discharge_rate); // Negative while charging
+        }
+
+        if message.percentage_for_sale > 100.0 {
+            violate("percentage_for_sale", ValidationRule::Percentage, message.percentage_for_sale);
+        }
+
+        // Per-type rules; amounts already reported as non-finite or negative are skipped
+        let price = match message_type {
+            Some(MessageType::Bid) => Some(("bid_price", message.bid_price)),
+            Some(MessageType::BidAccept | MessageType::BidConfirm) => Some(("sale_price", message.sale_price)),
+            _ => None,
+        };
+        if message_type == Some(MessageType::Bid) && message.bid_price == 0.0 {
+            violate("bid_price", ValidationRule::Required, 0.0);
+        }
+        if matches!(message_type, Some(MessageType::Bid | MessageType::BidAccept | MessageType::BidConfirm))
+            && message.required_energy_amount == 0.0
+        {
+            violate("required_energy_amount", ValidationRule::Required, 0.0);
+        }
+        if let (Some((field, value)), Some(max_price)) = (price, self.max_price) {
+            if value > max_price {
+                violate(field, ValidationRule::PriceCap, value);
+            }
+        }
+        if let (Some(MessageType::BESSStatus), Some((min, max))) = (message_type, self.voltage_band) {
+            if message.battery_voltage.is_finite() && !(min..=max).contains(&message.battery_voltage) {
+                violate("battery_voltage", ValidationRule::VoltageBand, message.battery_voltage);
+            }
+        }
+
+        violations
+    }
+
+    /// Fail with `ETPError::InvalidFields` if the message breaks any rule
+    pub fn validate(&self, message: &ETPMessage) -> Result<()> {
+        let violations = self.check(message);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ETPError::InvalidFields(violations))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules_broken(rules: &ValidationRules, message: &ETPMessage) -> Vec<(&'static str, ValidationRule)> {
+        rules.check(message).into_iter().map(|v| (v.field, v.rule)).collect()
+    }
+
+    #[test]
+    fn test_nan_and_infinite_rejected() {
+        let rules = ValidationRules::default();
+        let bid = ETPMessage::new_bid(1, f64::NAN, f64::INFINITY);
+        assert_eq!(
+            rules_broken(&rules, &bid),
+            vec![("bid_price", ValidationRule::Finite), ("required_energy_amount", ValidationRule::Finite)]
+        );
+    }
+
+    #[test]
+    fn test_required_fields_per_type() {
+        let rules = ValidationRules::default();
+        assert_eq!(
+            rules_broken(&rules, &ETPMessage::new_bid(1, 15.5, 0.0)),
+            vec![("required_energy_amount", ValidationRule::Required)]
+        );
+        assert_eq!(
+            rules_broken(&rules, &ETPMessage::new_bid(1, -2.0, 10.0)),
+            vec![("bid_price", ValidationRule::NonNegative)]
+        );
+        // A query carries no amounts
+        assert!(rules.check(&ETPMessage::new_query(1, 100)).is_empty());
+    }
+
+    #[test]
+    fn test_configured_price_cap_and_voltage_band() {
+        let rules = ValidationRules {
+            max_price: Some(20.0),
+            voltage_band: Some((11.0, 14.0)),
+            ..ValidationRules::default()
+        };
+        assert!(rules.check(&ETPMessage::new_bid(1, 15.5, 10.0)).is_empty());
+        assert_eq!(
+            rules_broken(&rules, &ETPMessage::new_bid_accept(1, 100, 25.0, 5.0)),
+            vec![("sale_price", ValidationRule::PriceCap)]
+        );
+        assert_eq!(
+            rules_broken(&rules, &ETPMessage::new_bess_status(1, 100, 18.5, 1, 16.2, 2.5)),
+            vec![("battery_voltage", ValidationRule::VoltageBand)]
+        );
+    }
+
+    #[test]
+    fn test_every_violation_reported() {
+        let mut message = ETPMessage::new_with_type(12, 1, -1.0, 10.0);
+        message.ttl = 0;
+        message.percentage_for_sale = 150.0;
+        let violations = ValidationRules::default().check(&message);
+        assert_eq!(violations.len(), 4);
+
+        match ValidationRules::default().validate(&message) {
+            Err(ETPError::InvalidFields(reported)) => assert_eq!(reported, violations),
+            other => panic!("Expected InvalidFields, got {:?}", other),
+        }
+    }
+}
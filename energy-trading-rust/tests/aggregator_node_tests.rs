@@ -119,12 +119,12 @@ async fn test_aggregator_bid_evaluation() {
     
     // Test rejected bid
     let bid = ETPMessage::new_bid(124, 15.0, 10.0);
-    let evaluation = aggregator.evaluate_bid_response(bid, BidResponse::Reject { reason: "Price too low".to_string(), code: 1 }).await;
+    let evaluation = aggregator.evaluate_bid_response(bid, BidResponse::Reject { reason: "Price too low".to_string(), code: TerminationCode::PriceBelowReserve }).await;
     
     match evaluation {
         BidEvaluationResult::Rejected { reason, code } => {
             assert_eq!(reason, "Price too low");
-            assert_eq!(code, 1);
+            assert_eq!(code, TerminationCode::PriceBelowReserve);
         }
         _ => panic!("Expected Rejected evaluation"),
     }
@@ -191,7 +191,6 @@ async fn test_aggregator_concurrent_operations() {
     }
     
     // All operations should complete successfully
-    assert!(true);
 }
 
 #[tokio::test]
@@ -330,3 +329,22 @@ async fn test_aggregator_network_communication_simulation() {
         assert!(response.is_ok());
     }
 }
+
+#[tokio::test]
+async fn test_aggregator_skips_corrupt_reserve_prices() {
+    let aggregator = AggregatorNode::new(123, "AGG-001".to_string(), BiddingStrategy::Intelligent);
+    
+    let mut bess1 = BESSNode::new(100, "BESS-001".to_string(), 100.0, f64::NAN);
+    bess1.set_percentage_for_sale(50.0);
+    
+    let mut bess2 = BESSNode::new(101, "BESS-002".to_string(), 80.0, 16.0);
+    bess2.set_percentage_for_sale(75.0);
+    
+    aggregator.add_connected_bess(100, bess1).await;
+    aggregator.add_connected_bess(101, bess2).await;
+    
+    // The NaN-priced node is skipped rather than panicking the sort
+    let optimized_bids = aggregator.optimize_bids(100.0, 18.0).await;
+    assert_eq!(optimized_bids.len(), 1);
+    assert!(optimized_bids.iter().all(|bid| bid.validate().is_ok()));
+}
@@ -75,7 +75,7 @@ async fn test_bid_evaluation_reject_price_too_low() {
     match evaluation {
         BidEvaluation::Reject { reason, code } => {
             assert_eq!(reason, "Bid price below reserve price");
-            assert_eq!(code, 1);
+            assert_eq!(code, TerminationCode::PriceBelowReserve);
         }
         _ => panic!("Expected Reject evaluation"),
     }
@@ -92,7 +92,7 @@ async fn test_bid_evaluation_reject_insufficient_energy() {
     match evaluation {
         BidEvaluation::Reject { reason, code } => {
             assert_eq!(reason, "Insufficient energy available");
-            assert_eq!(code, 2);
+            assert_eq!(code, TerminationCode::InsufficientEnergy);
         }
         _ => panic!("Expected Reject evaluation"),
     }
@@ -109,7 +109,7 @@ async fn test_bid_evaluation_reject_offline() {
     match evaluation {
         BidEvaluation::Reject { reason, code } => {
             assert_eq!(reason, "BESS is offline");
-            assert_eq!(code, 3);
+            assert_eq!(code, TerminationCode::Offline);
         }
         _ => panic!("Expected Reject evaluation"),
     }
@@ -152,8 +152,8 @@ async fn test_generate_status_message() {
     assert_eq!(status_msg.message_id, 456);
     assert_eq!(status_msg.device_id, 123);
     assert_eq!(status_msg.remaining_battery_energy, 80.0);
-    assert_eq!(status_msg.battery_health_status_code, 1);
-    assert_eq!(status_msg.battery_voltage, 12.6);
+    assert_eq!(status_msg.battery_health_status_code, 0); // New battery
+    assert!((status_msg.battery_voltage - 12.6).abs() < 1e-9); // 80% charge on the default curve
     assert_eq!(status_msg.discharge_rate, 5.0);
 }
 
@@ -251,7 +251,7 @@ async fn test_bid_evaluation_edge_cases() {
     match evaluation {
         BidEvaluation::Reject { reason, code } => {
             assert_eq!(reason, "Insufficient energy available");
-            assert_eq!(code, 2);
+            assert_eq!(code, TerminationCode::InsufficientEnergy);
         }
         _ => panic!("Expected Reject for negative energy request"),
     }
@@ -337,3 +337,259 @@ async fn test_bess_node_validation() {
     assert!(bess.percentage_for_sale >= 0.0);
     assert!(bess.percentage_for_sale <= 100.0);
 }
+
+#[tokio::test]
+async fn test_battery_model_limits_charge_and_discharge() {
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    bess.set_percentage_for_sale(100.0);
+
+    // Discharging is capped at the 5 kW inverter and costs conversion losses
+    let delivered = bess.discharge(50.0, Duration::from_secs(3600));
+    assert!((delivered - 5.0).abs() < 1e-9);
+    assert!((bess.current_energy_level - (80.0 - 5.0 / 0.95)).abs() < 1e-9);
+    assert!(bess.battery_voltage < 12.6);
+
+    // It stops at the 10% depth-of-discharge floor
+    let delivered = bess.discharge(5.0, Duration::from_secs(100 * 3600));
+    assert!((bess.current_energy_level - bess.min_energy_level()).abs() < 1e-3); // Wear lowers the floor a little
+    assert!(delivered < 70.0 * 0.95);
+    assert!(!bess.can_provide_energy(1.0));
+
+    // Recharging runs at 0.5 C with charging losses and stops when full
+    let before = bess.current_energy_level;
+    bess.recharge_energy(3600.0);
+    assert!((bess.max_charge_power() - 50.0).abs() < 0.01);
+    assert!((bess.current_energy_level - before - bess.max_charge_power() * 0.95).abs() < 1e-9);
+    bess.recharge_energy(10.0 * 3600.0);
+    assert_eq!(bess.current_energy_level, bess.effective_capacity());
+    assert!((bess.battery_voltage - 12.8).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_sell_energy_respects_physical_limits() {
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    bess.set_percentage_for_sale(100.0);
+
+    // 80 kWh are for sale, but only 70 kWh above the floor, less losses, can be delivered
+    assert!(!bess.can_provide_energy(80.0));
+    assert!(bess.deliverable_energy(bess.delivery_window) < 70.0 * 0.95);
+    assert!(matches!(bess.sell_energy(80.0), Err(ETPError::InsufficientEnergy)));
+
+    bess.sell_energy(19.0).unwrap();
+    assert!((bess.current_energy_level - 60.0).abs() < 1e-9);
+
+    // A stricter model makes less energy deliverable, and is kept on save and load
+    bess.set_battery(BatteryModelConfig::Linear(LinearBatteryModel { depth_of_discharge: 0.5, ..Default::default() }));
+    assert!(bess.can_provide_energy(9.0));
+    assert!(!bess.can_provide_energy(10.0));
+    let restored: BESSNode = serde_json::from_str(&serde_json::to_string(&bess).unwrap()).unwrap();
+    assert_eq!(restored.battery, bess.battery);
+    assert!(!restored.can_provide_energy(10.0));
+}
+
+#[tokio::test]
+async fn test_voltage_follows_battery_model() {
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    assert_eq!(bess.battery_voltage, bess.battery_model().voltage(0.8));
+
+    bess.set_battery(BatteryModelConfig::Linear(LinearBatteryModel { empty_voltage: 46.0, full_voltage: 54.0, ..Default::default() }));
+    assert!((bess.battery_voltage - 52.4).abs() < 1e-9);
+
+    // A node saved before battery models were added uses the default one
+    let mut config = serde_json::to_value(&bess).unwrap();
+    config.as_object_mut().unwrap().remove("battery");
+    let restored: BESSNode = serde_json::from_value(config).unwrap();
+    assert_eq!(restored.battery, BatteryModelConfig::default());
+}
+
+#[tokio::test]
+async fn test_wear_cost_raises_reserve_price() {
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    assert_eq!(bess.wear_cost_per_kwh(), 0.0); // Replacement cost unknown
+
+    // $300/kWh over 6000 cycles is 5 c per kWh drawn, more per kWh delivered
+    bess.degradation.replacement_cost = 30000.0;
+    assert!((bess.wear_cost_per_kwh() - 5.0 / 0.95).abs() < 1e-9);
+    assert!(matches!(bess.evaluate_bid(15.0, 10.0), BidEvaluation::Reject { code: TerminationCode::PriceBelowReserve, .. }));
+    assert!(matches!(bess.evaluate_bid(20.0, 10.0), BidEvaluation::Accept { .. }));
+
+    let outcome = bess.settle_sale(20.0, 19.0).unwrap();
+    assert_eq!(outcome.revenue, 380.0);
+    assert!((outcome.wear_cost - 100.0).abs() < 1e-9); // 20 kWh drawn at 5 c
+    assert!(outcome.is_profitable());
+    assert!((bess.wear.equivalent_cycles - 0.2).abs() < 1e-9);
+    assert!((bess.wear.profit() - 280.0).abs() < 1e-9);
+
+    // Selling below the wear cost loses money
+    let outcome = bess.settle_sale(4.0, 1.0).unwrap();
+    assert!(!outcome.is_profitable());
+}
+
+#[tokio::test]
+async fn test_health_degrades_and_reports_threshold_crossings() {
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    assert_eq!(bess.battery_health_status, 0);
+
+    // Half a cycle life of discharging fades 10% of the capacity
+    for _ in 0..4000 {
+        bess.discharge(5.0, Duration::from_secs(14 * 3600));
+        bess.recharge_energy(2.0 * 3600.0);
+    }
+    assert!(bess.wear.equivalent_cycles > 2900.0);
+    assert!(bess.wear.state_of_health < 0.91);
+    assert!(bess.effective_capacity() < 91.0);
+    assert!(bess.current_energy_level <= bess.effective_capacity());
+    assert_eq!(bess.battery_health_status, 1);
+
+    // Six years on the shelf push it below 80%: fair, then poor
+    for _ in 0..6 {
+        bess.age(Duration::from_secs(365 * 24 * 3600));
+    }
+    assert_eq!(bess.battery_health_status, 3);
+    let events = bess.take_health_events();
+    let crossings: Vec<_> = events.iter().map(|event| (event.previous_status, event.status)).collect();
+    assert_eq!(crossings, [(0, 1), (1, 2), (2, 3)]);
+    assert!(bess.take_health_events().is_empty());
+
+    let event: SystemEvent = events[2].clone().into();
+    assert!(matches!(event, SystemEvent::BatteryHealthChanged { bess_id: 123, status: 3, .. }));
+}
+
+#[tokio::test]
+async fn test_schedule_discharges_over_the_window() {
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let start = std::time::SystemTime::now();
+    let window = DeliveryWindow::flat(start, Duration::from_secs(2 * 3600), 4.0); // 8 kWh
+
+    match bess.evaluate_delivery(20.0, &window) {
+        BidEvaluation::Accept { energy_amount, .. } => assert_eq!(energy_amount, 8.0),
+        other => panic!("expected the window to be accepted, got {:?}", other),
+    }
+    bess.schedule_delivery(1, 42, window.clone()).unwrap();
+    assert!(bess.schedule_delivery(1, 42, window).is_err());
+    assert!(!bess.can_provide_energy(35.0)); // 40 kWh for sale, 8 committed
+
+    let level = bess.current_energy_level;
+    let delivered = bess.run_schedule(start + Duration::from_secs(3600));
+    assert!((delivered - 4.0).abs() < 1e-3);
+    assert!((bess.current_energy_level - (level - 4.0 / 0.95)).abs() < 1e-3);
+    assert!((bess.schedule.get(1).unwrap().remaining() - 4.0).abs() < 1e-3);
+
+    bess.run_schedule(start + Duration::from_secs(2 * 3600));
+    assert!(bess.schedule.is_empty());
+    assert!((bess.current_energy_level - (level - 8.0 / 0.95)).abs() < 1e-3);
+}
+
+#[tokio::test]
+async fn test_schedule_rejects_overlapping_power_and_excess_energy() {
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let start = std::time::SystemTime::now() + Duration::from_secs(3600);
+    let hour = Duration::from_secs(3600);
+    bess.schedule_delivery(1, 42, DeliveryWindow::flat(start, hour * 2, 4.0)).unwrap();
+
+    // 4 kW + 2 kW is above the 5 kW discharge limit while the windows overlap
+    let overlapping = DeliveryWindow::flat(start + hour, hour * 2, 2.0);
+    assert!(!bess.can_schedule(&overlapping));
+    match bess.evaluate_delivery(20.0, &overlapping) {
+        BidEvaluation::Reject { reason, code } => {
+            assert_eq!(code, TerminationCode::InsufficientEnergy);
+            assert!(reason.contains("kW limit"));
+        }
+        other => panic!("expected the overlap to be rejected, got {:?}", other),
+    }
+    assert!(bess.can_schedule(&DeliveryWindow::flat(start + hour * 2, hour * 2, 2.0)));
+
+    // 8 kWh committed and 40 kWh for sale
+    let too_much = DeliveryWindow::flat(start + hour * 4, hour * 8, 4.5);
+    assert!(matches!(bess.schedule_delivery(2, 42, too_much), Err(ETPError::InsufficientEnergy)));
+    let ended = DeliveryWindow::flat(start - hour * 3, hour, 1.0);
+    assert!(!bess.can_schedule(&ended));
+}
+
+#[tokio::test]
+async fn test_reserved_bids_hold_energy_until_confirmed() {
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0); // 40 kWh for sale
+    let now = std::time::SystemTime::now();
+
+    assert!(matches!(bess.reserve_bid(1, 42, 18.0, 30.0, None, now), BidEvaluation::Accept { .. }));
+    assert_eq!(bess.held_energy(), 30.0);
+    assert!(!bess.can_provide_energy(20.0));
+    assert!(matches!(bess.reserve_bid(2, 43, 18.0, 20.0, None, now), BidEvaluation::Reject { code: TerminationCode::InsufficientEnergy, .. }));
+    assert!(matches!(bess.reserve_bid(1, 42, 18.0, 5.0, None, now), BidEvaluation::Reject { code: TerminationCode::InvalidMessage, .. }));
+
+    // A confirm that does not match the accept commits nothing
+    assert!(bess.confirm_hold(1, 18.0, 25.0, now).is_err());
+    assert_eq!(bess.held_energy(), 30.0);
+    let outcome = bess.confirm_hold(1, 18.0, 30.0, now).unwrap().unwrap();
+    assert_eq!(outcome.revenue, 540.0);
+    assert_eq!(bess.held_energy(), 0.0);
+    assert!((bess.current_energy_level - (80.0 - 30.0 / 0.95)).abs() < 1e-9);
+    assert!(bess.confirm_hold(1, 18.0, 30.0, now).is_err());
+
+    // An unconfirmed hold lapses
+    assert!(matches!(bess.reserve_bid(3, 42, 18.0, 10.0, None, now), BidEvaluation::Accept { .. }));
+    let later = now + bess.hold_duration;
+    assert_eq!(bess.expire_holds(later).len(), 1);
+    assert!(bess.holds().is_empty());
+    assert!(bess.confirm_hold(3, 18.0, 10.0, later).is_err());
+
+    // A held delivery window is scheduled on confirm
+    let window = DeliveryWindow::flat(now, Duration::from_secs(3600), 4.0);
+    assert!(matches!(bess.reserve_bid(4, 42, 18.0, 4.0, Some(&window), now), BidEvaluation::Accept { .. }));
+    assert!(!bess.can_schedule(&DeliveryWindow::flat(now, Duration::from_secs(3600), 2.0))); // 6 kW > 5 kW
+    assert!(bess.confirm_hold(4, 18.0, 4.0, now).unwrap().is_none());
+    assert!(bess.schedule.get(4).is_some());
+}
+
+#[derive(Debug)]
+struct FlatFeePricing(f64);
+
+impl PricingPolicy for FlatFeePricing {
+    fn reserve_price(&self, context: &PricingContext) -> f64 {
+        context.reserve_price + self.0
+    }
+}
+
+#[tokio::test]
+async fn test_pricing_policy_selected_from_config() {
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    assert_eq!(bess.pricing, PricingConfig::default());
+    assert!(matches!(bess.evaluate_bid(14.0, 10.0), BidEvaluation::Accept { .. })); // 10% off when high
+
+    // A node saved before pricing policies were added keeps the tiered behaviour
+    let mut config = serde_json::to_value(&bess).unwrap();
+    config.as_object_mut().unwrap().remove("pricing");
+    let restored: BESSNode = serde_json::from_value(config.clone()).unwrap();
+    assert_eq!(restored.pricing, PricingConfig::default());
+
+    config["pricing"] = serde_json::json!({ "policy": "static" });
+    let static_bess: BESSNode = serde_json::from_value(config.clone()).unwrap();
+    assert_eq!(static_bess.pricing, PricingConfig::Static);
+    assert!(matches!(static_bess.evaluate_bid(14.0, 10.0), BidEvaluation::Reject { code: TerminationCode::PriceBelowReserve, .. }));
+    let saved = serde_json::to_string(&static_bess).unwrap();
+    assert_eq!(serde_json::from_str::<BESSNode>(&saved).unwrap().pricing, PricingConfig::Static);
+
+    // Time of use prices a delivery by when it starts
+    config["pricing"] = serde_json::json!({
+        "policy": "time-of-use",
+        "periods": [{ "start_hour": 0, "end_hour": 24, "multiplier": 2.0 }],
+        "utc_offset_minutes": 0,
+    });
+    let tariff_bess: BESSNode = serde_json::from_value(config).unwrap();
+    let window = DeliveryWindow::flat(std::time::SystemTime::now(), Duration::from_secs(3600), 4.0);
+    assert!(matches!(tariff_bess.evaluate_delivery(29.0, &window), BidEvaluation::Reject { .. }));
+    assert!(matches!(tariff_bess.evaluate_delivery(30.0, &window), BidEvaluation::Accept { .. }));
+
+    // Opportunity cost asks at least the discounted forecast
+    bess.set_pricing(PricingConfig::OpportunityCost(OpportunityCostPricing {
+        forecast: vec![PriceForecast { start: std::time::SystemTime::now(), price: 40.0 }],
+        ..Default::default()
+    }));
+    assert!((bess.effective_reserve_price() - 36.0).abs() < 1e-9);
+
+    // Custom policies replace the configured one until another is chosen
+    bess.set_pricing_policy(FlatFeePricing(5.0));
+    assert_eq!(bess.effective_reserve_price(), 20.0);
+    bess.set_pricing(PricingConfig::Static);
+    assert_eq!(bess.effective_reserve_price(), 15.0);
+}
@@ -1,9 +1,20 @@
 use energy_trading::bess_node::BESSNode;
 use energy_trading::bess_tcp_server::BESSTCPServer;
+use energy_trading::codec::delivery_frame::delivery_time_now;
+use energy_trading::delivery::DeliveryWindow;
+use energy_trading::codec::batch::MessageBatch;
+use energy_trading::envelope::{EnvelopeVerifier, MessageSigner};
+use energy_trading::error::ETPError;
 use energy_trading::etp_message::ETPMessage;
+use energy_trading::network::client::EtpClient;
+use energy_trading::network::handshake::{Capabilities, Hello};
 use energy_trading::network::unicast_connection::UnicastConnection;
+use energy_trading::network::websocket_gateway::{SystemEvent, WebSocketGateway};
+use energy_trading::replay_protection::next_message_id;
+use energy_trading::termination::TerminationCode;
+use std::sync::Arc;
 use tokio::net::TcpStream;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::time::{timeout, Duration};
 
 /// Test BESS TCP Server functionality
@@ -15,7 +26,7 @@ use tokio::time::{timeout, Duration};
 async fn test_bess_tcp_server_startup() {
     // Test that BESS TCP server can start and bind to a port
     let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
-    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
     
     assert!(!server.is_running().await);
     assert!(server.local_addr().is_ok());
@@ -115,6 +126,31 @@ async fn test_bess_tcp_server_processes_bid_messages() {
     server_handle.abort();
 }
 
+#[tokio::test]
+async fn test_bess_tcp_server_rejects_with_termination_code() {
+    // Test that a bid under the reserve price is rejected with a registry code
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    client_connection.send_message(ETPMessage::new_bid(next_message_id(), 5.0, 10.0)).await.unwrap();
+    
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    let reject = response.unwrap().unwrap();
+    assert_eq!(reject.message_type, 6); // BidReject
+    assert_eq!(reject.termination().unwrap(), TerminationCode::PriceBelowReserve);
+    
+    server_handle.abort();
+}
+
 #[tokio::test]
 async fn test_bess_tcp_server_timing_constraints() {
     // Test that BESS server respects timing constraints for different message types
@@ -134,7 +170,7 @@ async fn test_bess_tcp_server_timing_constraints() {
     let client_stream = TcpStream::connect(server_addr).await.unwrap();
     let mut client_connection = UnicastConnection::new(client_stream);
     
-    let device_failure = ETPMessage::new_device_failure(789, 123, 1);
+    let device_failure = ETPMessage::new_device_failure(789, 123, TerminationCode::BatteryFault);
     client_connection.send_message(device_failure).await.unwrap();
     
     // DeviceFailure messages don't get responses, just test that server processes them quickly
@@ -193,3 +229,684 @@ async fn test_bess_tcp_server_error_handling() {
     server_handle.abort();
 }
 
+
+#[tokio::test]
+async fn test_bess_tcp_server_negotiates_protocol_version() {
+    // Test that aggregators can negotiate a protocol version before trading
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    server.set_hello(Hello::new(vec![1], Capabilities::PARTIAL_FILLS));
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    
+    let hello = Hello::new(vec![1, 2], Capabilities::PARTIAL_FILLS | Capabilities::COMPRESSION);
+    let protocol = client_connection.handshake(&hello).await.unwrap();
+    assert_eq!(protocol.version, 1);
+    assert_eq!(protocol.capabilities, Capabilities::PARTIAL_FILLS);
+    
+    // Normal traffic continues after the handshake
+    client_connection.send_message(ETPMessage::new_query(456, 789)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 2); // QueryResponse
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_rejects_incompatible_version() {
+    // Test that peers without a common version fail with a specific error
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    
+    let result = client_connection.handshake(&Hello::new(vec![2], Capabilities::empty())).await;
+    assert!(matches!(result, Err(ETPError::VersionMismatch { .. })));
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_bridges_go_text_peers() {
+    // Test that a legacy Go aggregator can query a bridge-mode BESS server
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    server.set_bridge_mode(true);
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    // Go side: conn.Write([]byte(message.QueryMessage(789).Serialize()))
+    let mut go_client = TcpStream::connect(server_addr).await.unwrap();
+    go_client.write_all(b"1 45678 789 0 0.00 0.00 0.00 0.00 0.00 0 0.00 0 0.00 0.00").await.unwrap();
+    
+    let mut buffer = [0u8; 1024];
+    let length = timeout(Duration::from_millis(500), go_client.read(&mut buffer)).await.unwrap().unwrap();
+    let response = ETPMessage::decode_go_bytes(&buffer[..length]).unwrap();
+    assert_eq!(response.message_type, 2); // QueryResponse
+    assert_eq!(response.message_id, 45678);
+    assert_eq!(response.device_id, 123);
+    
+    // ETP peers are still served by the same listener
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    client_connection.send_message(ETPMessage::new_query(456, 789)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 2);
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_requires_signed_bids() {
+    // Test that only bids signed by a trusted aggregator key are processed
+    let aggregator_key = MessageSigner::generate();
+    let bess_key = MessageSigner::generate();
+    let mut server_verifier = EnvelopeVerifier::default();
+    server_verifier.trust_key(aggregator_key.public_key(), 42);
+    
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    server.set_signer(bess_key.clone());
+    server.set_verifier(server_verifier);
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    // Signed bid from the trusted aggregator gets a signed answer
+    let mut client_verifier = EnvelopeVerifier::default();
+    client_verifier.trust_key(bess_key.public_key(), 123);
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    client_connection.set_signer(aggregator_key);
+    client_connection.set_verifier(Arc::new(client_verifier));
+    let mut bid = ETPMessage::new_bid(789, 18.0, 10.0);
+    bid.device_id = 42;
+    client_connection.send_message(bid).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 4); // BidAccept
+    
+    // Unsigned bid: connection is closed without an answer
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut unsigned_connection = UnicastConnection::new(client_stream);
+    unsigned_connection.send_message(ETPMessage::new_bid(790, 18.0, 10.0)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), unsigned_connection.receive_message()).await;
+    assert!(matches!(response.unwrap(), Err(ETPError::Io(_))));
+    
+    // Bid signed by an impostor: connection is closed without an answer
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut forged_connection = UnicastConnection::new(client_stream);
+    forged_connection.set_signer(MessageSigner::generate());
+    forged_connection.send_message(ETPMessage::new_bid(791, 18.0, 10.0)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), forged_connection.receive_message()).await;
+    assert!(matches!(response.unwrap(), Err(ETPError::Io(_))));
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_drops_replayed_messages() {
+    // Test that a captured signed bid cannot be replayed on another connection
+    let aggregator_key = MessageSigner::generate();
+    let mut verifier = EnvelopeVerifier::default();
+    verifier.trust_key(aggregator_key.public_key(), 42);
+    
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    server.set_verifier(verifier);
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let mut bid = ETPMessage::new_bid(next_message_id(), 18.0, 10.0);
+    bid.device_id = 42;
+    let captured = aggregator_key.sign(bid).unwrap().encode().unwrap();
+    
+    // Original delivery is answered
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    client_connection.send_frame(&captured).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 4); // BidAccept
+    
+    // Replay on a second connection is dropped; the connection stays usable
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut attacker_connection = UnicastConnection::new(client_stream);
+    attacker_connection.send_frame(&captured).await.unwrap();
+    attacker_connection.send_message(ETPMessage::new_query(next_message_id(), 42)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), attacker_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 2); // QueryResponse, not a BidAccept
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_message_id_collisions() {
+    // Test that the same message_id from different aggregators is not mistaken for a replay,
+    // while a duplicate from the same aggregator is dropped
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    for aggregator_id in [1, 2] {
+        let client_stream = TcpStream::connect(server_addr).await.unwrap();
+        let mut client_connection = UnicastConnection::new(client_stream);
+        client_connection.send_message(ETPMessage::new_query(5000, aggregator_id)).await.unwrap();
+        let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+        assert_eq!(response.unwrap().unwrap().message_id, 5000);
+    }
+    
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    client_connection.send_message(ETPMessage::new_query(5000, 1)).await.unwrap();
+    client_connection.send_message(ETPMessage::new_query(5001, 1)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_id, 5001);
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_drops_replays_by_default_except_from_go_peers() {
+    // Test that ETP replays are dropped without set_replay_cache, while Go peers may reuse ids
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    server.set_bridge_mode(true);
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    // Go side: every process sends device_id 0 and starts its ids over
+    for _ in 0..2 {
+        let mut go_client = TcpStream::connect(server_addr).await.unwrap();
+        go_client.write_all(b"1 1 0 0 0.00 0.00 0.00 0.00 0.00 0 0.00 0 0.00 0.00").await.unwrap();
+        let mut buffer = [0u8; 1024];
+        let length = timeout(Duration::from_millis(500), go_client.read(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!(ETPMessage::decode_go_bytes(&buffer[..length]).unwrap().message_type, 2); // QueryResponse
+    }
+    
+    let query_id = next_message_id();
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    client_connection.send_message(ETPMessage::new_query(query_id, 42)).await.unwrap();
+    client_connection.send_message(ETPMessage::new_query(query_id, 42)).await.unwrap();
+    let follow_up = next_message_id();
+    client_connection.send_message(ETPMessage::new_query(follow_up, 42)).await.unwrap();
+    for expected in [query_id, follow_up] {
+        let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+        assert_eq!(response.unwrap().unwrap().message_id, expected);
+    }
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_dispatches_queued_messages() {
+    // Test that a burst of messages is queued, dispatched and reflected in the metrics
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let monitor = server.clone();
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    // Status updates, a device failure, a pipelined register and a query written in a single burst
+    let mut burst = Vec::new();
+    let messages = [
+        ETPMessage::new_bess_status(next_message_id(), 77, 18.5, 1, 12.6, 2.5),
+        ETPMessage::new_bess_status(next_message_id(), 77, 18.0, 1, 12.5, 2.5),
+        ETPMessage::new_device_failure(next_message_id(), 77, TerminationCode::BatteryFault),
+        ETPMessage::new_register(next_message_id(), 77),
+        ETPMessage::new_query(next_message_id(), 77),
+    ];
+    for message in &messages {
+        let frame = message.serialize().unwrap();
+        burst.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        burst.extend_from_slice(&frame);
+    }
+    let mut client_stream = TcpStream::connect(server_addr).await.unwrap();
+    client_stream.write_all(&burst).await.unwrap();
+    
+    let mut client_connection = UnicastConnection::new(client_stream);
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 2); // QueryResponse
+    
+    // The register was handled before the query, so the session was not terminated
+    let response = timeout(Duration::from_millis(100), client_connection.receive_message()).await;
+    assert!(response.is_err());
+    let metrics = monitor.dispatcher_metrics();
+    assert_eq!(metrics.dispatched, 5);
+    assert_eq!(metrics.dropped_late, 0);
+    assert_eq!(metrics.total_queued(), 0);
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_drops_invalid_messages() {
+    // Test that a bid with a NaN price is rejected instead of being evaluated
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    let bid_id = next_message_id();
+    client_connection.send_message(ETPMessage::new_bid(bid_id, f64::NAN, 10.0)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    let reject = response.unwrap().unwrap();
+    assert_eq!((reject.message_type, reject.message_id), (6, bid_id)); // BidReject
+    assert_eq!(reject.termination().unwrap(), TerminationCode::InvalidMessage);
+    
+    // Other invalid messages are dropped without an answer
+    let mut status = ETPMessage::new_bess_status(next_message_id(), 77, 50.0, 1, 12.6, 2.5);
+    status.battery_voltage = -1.0;
+    client_connection.send_message(status).await.unwrap();
+    client_connection.send_message(ETPMessage::new_query(next_message_id(), 42)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 2); // QueryResponse
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_rejects_late_bids() {
+    // Test that a bid which missed its deadline while queued is answered rather than dropped
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let monitor = server.clone();
+    let bess_node = server.bess_node();
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    // The invalid bid's rejection waits on the node, so the valid bid behind it outlives its 1000ms deadline
+    let invalid_id = next_message_id();
+    let bid_id = next_message_id();
+    let mut burst = Vec::new();
+    for message in [ETPMessage::new_bid(invalid_id, f64::NAN, 10.0), ETPMessage::new_bid(bid_id, 18.0, 10.0)] {
+        let frame = message.serialize().unwrap();
+        burst.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        burst.extend_from_slice(&frame);
+    }
+    let busy = bess_node.write().await;
+    let mut client_stream = TcpStream::connect(server_addr).await.unwrap();
+    client_stream.write_all(&burst).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    drop(busy);
+    
+    let mut client_connection = UnicastConnection::new(client_stream);
+    for (expected_id, code) in [(invalid_id, TerminationCode::InvalidMessage), (bid_id, TerminationCode::TtlExpired)] {
+        let reject = timeout(Duration::from_millis(500), client_connection.receive_message()).await.unwrap().unwrap();
+        assert_eq!((reject.message_type, reject.message_id), (6, expected_id)); // BidReject
+        assert_eq!(reject.termination().unwrap(), code);
+    }
+    assert_eq!(bess_node.read().await.held_energy(), 0.0);
+    assert_eq!(monitor.dispatcher_metrics().flagged_late, 1);
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_unpacks_batched_status() {
+    // Test that delta-encoded status batches are unpacked into individual messages
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    server.set_hello(Hello::new(vec![1], Capabilities::BATCHING));
+    let monitor = server.clone();
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    client_connection.handshake(&Hello::new(vec![1], Capabilities::BATCHING)).await.unwrap();
+    
+    let mut batch = MessageBatch::new(
+        (0..20).map(|i| ETPMessage::new_bess_status(next_message_id(), 77, 18.5 - i as f64 * 0.1, 1, 12.6, 2.5)).collect(),
+    );
+    batch.push(ETPMessage::new_query(next_message_id(), 77));
+    batch.set_delta_status(true);
+    client_connection.send_batch(batch).await.unwrap();
+    
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 2); // QueryResponse
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(monitor.dispatcher_metrics().dispatched, 21);
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_compact_compressed_link() {
+    // Test that a constrained-link aggregator can trade with compact, compressed frames
+    let link = Capabilities::COMPACT_ENCODING | Capabilities::COMPRESSION | Capabilities::BATCHING;
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    server.set_hello(Hello::new(vec![1], link));
+    server.set_compression(true);
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    client_connection.set_compression(true);
+    let protocol = client_connection.handshake(&Hello::new(vec![1], link)).await.unwrap();
+    assert_eq!(protocol.capabilities, link);
+    
+    let mut batch = MessageBatch::new(
+        (0..50).map(|_| ETPMessage::new_bess_status(next_message_id(), 77, 18.5, 1, 12.6, 2.5)).collect(),
+    );
+    batch.push(ETPMessage::new_bid(next_message_id(), 18.0, 10.0));
+    client_connection.send_batch(batch).await.unwrap();
+    
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    let response = response.unwrap().unwrap();
+    assert_eq!(response.message_type, 4); // BidAccept
+    assert_eq!(response.sale_price, 18.0);
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_enforces_session_order() {
+    // Test that messages out of protocol order are dropped while the conversation continues
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let client_stream = TcpStream::connect(server_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    
+    // Confirm for a bid the BESS never accepted is rejected
+    let stray_id = next_message_id();
+    client_connection.send_message(ETPMessage::new_bid_confirm(stray_id, 42, 18.0, 10.0)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    let reject = response.unwrap().unwrap();
+    assert_eq!((reject.message_type, reject.message_id), (6, stray_id)); // BidReject
+    assert_eq!(reject.termination().unwrap(), TerminationCode::InvalidMessage);
+    let bid_id = next_message_id();
+    client_connection.send_message(ETPMessage::new_bid(bid_id, 18.0, 10.0)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    let accept = response.unwrap().unwrap();
+    assert_eq!(accept.message_type, 4); // BidAccept
+    assert_eq!(accept.message_id, bid_id);
+    
+    // A second bid before confirming the first is rejected
+    let second_id = next_message_id();
+    client_connection.send_message(ETPMessage::new_bid(second_id, 18.0, 10.0)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    let reject = response.unwrap().unwrap();
+    assert_eq!((reject.message_type, reject.message_id), (6, second_id));
+    assert_eq!(reject.termination().unwrap(), TerminationCode::InvalidMessage);
+    
+    // Confirming the accepted bid opens the next round
+    client_connection.send_message(ETPMessage::new_bid_confirm(bid_id, 42, 18.0, 10.0)).await.unwrap();
+    client_connection.send_message(ETPMessage::new_query(next_message_id(), 42)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 2); // QueryResponse
+    
+    // Any other out-of-order message ends the conversation
+    let register_id = next_message_id();
+    client_connection.send_message(ETPMessage::new_register(register_id, 42)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    let terminate = response.unwrap().unwrap();
+    assert_eq!((terminate.message_type, terminate.message_id), (7, register_id)); // Terminate
+    assert_eq!(terminate.termination().unwrap(), TerminationCode::InvalidMessage);
+    let closed = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert!(closed.unwrap().is_err());
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_reopens_session_after_hold_lapses() {
+    // Test that a bid never confirmed does not block the connection once its hold lapses
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    bess.hold_duration = Duration::from_millis(200);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let mut client_connection = UnicastConnection::new(TcpStream::connect(server_addr).await.unwrap());
+    client_connection.send_message(ETPMessage::new_bid(next_message_id(), 18.0, 10.0)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 4); // BidAccept
+    
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    client_connection.send_message(ETPMessage::new_query(next_message_id(), 42)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+    assert_eq!(response.unwrap().unwrap().message_type, 2); // QueryResponse
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_holds_energy_against_oversubscription() {
+    // Test that concurrent bids for more than the energy for sale are not all accepted
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0); // 40 kWh for sale
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let bess_node = server.bess_node();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let bidders = (0..8).map(|_| tokio::spawn(async move {
+        let client_stream = TcpStream::connect(server_addr).await.unwrap();
+        let mut client_connection = UnicastConnection::new(client_stream);
+        client_connection.send_message(ETPMessage::new_bid(next_message_id(), 18.0, 10.0)).await.unwrap();
+        let response = timeout(Duration::from_millis(500), client_connection.receive_message()).await;
+        (response.unwrap().unwrap(), client_connection)
+    }));
+    let mut accepted = Vec::new();
+    for bidder in bidders.collect::<Vec<_>>() {
+        let (response, client_connection) = bidder.await.unwrap();
+        match response.message_type {
+            4 => accepted.push((response, client_connection)), // BidAccept
+            _ => assert_eq!(response.termination().unwrap(), TerminationCode::InsufficientEnergy),
+        }
+    }
+    assert_eq!(accepted.len(), 4);
+    assert_eq!(bess_node.read().await.held_energy(), 40.0);
+    
+    // Confirming commits the sales and draws the energy from the battery
+    for (accept, client_connection) in &mut accepted {
+        let confirm = ETPMessage::new_bid_confirm(accept.message_id, 42, accept.sale_price, 10.0);
+        client_connection.send_message(confirm).await.unwrap();
+    }
+    for _ in 0..50 {
+        if bess_node.read().await.held_energy() == 0.0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let bess = bess_node.read().await;
+    assert_eq!(bess.held_energy(), 0.0);
+    assert!((bess.current_energy_level - (80.0 - 40.0 / 0.95)).abs() < 1e-6);
+    drop(bess);
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_releases_holds_on_terminate_and_expiry() {
+    // Test that held energy returns to sale when the bidder terminates or never confirms
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0); // 40 kWh for sale
+    bess.hold_duration = Duration::from_millis(300);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let bess_node = server.bess_node();
+    
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    
+    let mut first = UnicastConnection::new(TcpStream::connect(server_addr).await.unwrap());
+    let mut second = UnicastConnection::new(TcpStream::connect(server_addr).await.unwrap());
+    first.send_message(ETPMessage::new_bid(next_message_id(), 18.0, 30.0)).await.unwrap();
+    assert_eq!(timeout(Duration::from_millis(500), first.receive_message()).await.unwrap().unwrap().message_type, 4);
+    second.send_message(ETPMessage::new_bid(next_message_id(), 18.0, 30.0)).await.unwrap();
+    assert_eq!(timeout(Duration::from_millis(500), second.receive_message()).await.unwrap().unwrap().message_type, 6);
+    
+    // Terminate releases the first bidder's hold
+    first.send_message(ETPMessage::new_terminate(next_message_id(), 42, TerminationCode::Normal)).await.unwrap();
+    for _ in 0..50 {
+        if bess_node.read().await.held_energy() == 0.0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let bid_id = next_message_id();
+    second.send_message(ETPMessage::new_bid(bid_id, 18.0, 30.0)).await.unwrap();
+    let accept = timeout(Duration::from_millis(500), second.receive_message()).await.unwrap().unwrap();
+    assert_eq!(accept.message_type, 4);
+    
+    // The second bidder does not confirm in time, so a third is accepted and the late confirm is refused
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let mut third = UnicastConnection::new(TcpStream::connect(server_addr).await.unwrap());
+    let third_id = next_message_id();
+    third.send_message(ETPMessage::new_bid(third_id, 18.0, 30.0)).await.unwrap();
+    let third_accept = timeout(Duration::from_millis(500), third.receive_message()).await.unwrap().unwrap();
+    assert_eq!(third_accept.message_type, 4);
+    second.send_message(ETPMessage::new_bid_confirm(bid_id, 42, accept.sale_price, 30.0)).await.unwrap();
+    let refused = timeout(Duration::from_millis(500), second.receive_message()).await.unwrap().unwrap();
+    assert_eq!((refused.message_type, refused.message_id), (6, bid_id)); // BidReject
+    assert_eq!(refused.termination().unwrap(), TerminationCode::TtlExpired);
+    assert_eq!(bess_node.read().await.current_energy_level, 80.0);
+    
+    // A confirm that does not match the accept is refused and its energy released
+    third.send_message(ETPMessage::new_bid_confirm(third_id, 42, third_accept.sale_price, 25.0)).await.unwrap();
+    let refused = timeout(Duration::from_millis(500), third.receive_message()).await.unwrap().unwrap();
+    assert_eq!((refused.message_type, refused.message_id), (6, third_id));
+    assert_eq!(refused.termination().unwrap(), TerminationCode::InvalidMessage);
+    assert_eq!(bess_node.read().await.held_energy(), 0.0);
+    
+    // Both sessions are open for another bid
+    second.send_message(ETPMessage::new_bid(next_message_id(), 18.0, 30.0)).await.unwrap();
+    assert_eq!(timeout(Duration::from_millis(500), second.receive_message()).await.unwrap().unwrap().message_type, 4);
+    third.send_message(ETPMessage::new_query(next_message_id(), 42)).await.unwrap();
+    assert_eq!(timeout(Duration::from_millis(500), third.receive_message()).await.unwrap().unwrap().message_type, 2);
+    assert_eq!(bess_node.read().await.current_energy_level, 80.0);
+    
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_bess_tcp_server_reports_to_gateway() {
+    // Test that settled sales, health changes and the schedule reach the gateway
+    let mut bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    bess.wear.state_of_health = 0.9500001; // Next sale wears it below 95%
+    let gateway = Arc::new(WebSocketGateway::new(0).await.unwrap());
+    let mut events = gateway.subscribe();
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    server.set_gateway(gateway.clone());
+    let server_addr = server.local_addr().unwrap();
+
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+
+    let (client, _subscription) = EtpClient::connect(server_addr).await.unwrap();
+    let bid_id = next_message_id();
+    let accept = client.request(ETPMessage::new_bid(bid_id, 18.0, 10.0)).await.unwrap();
+    client.send(ETPMessage::new_bid_confirm(bid_id, 42, accept.sale_price, 10.0)).await.unwrap();
+
+    let mut received = Vec::new();
+    while received.len() < 2 {
+        received.push(timeout(Duration::from_millis(500), events.recv()).await.unwrap().unwrap());
+    }
+    assert!(received.iter().any(|event| matches!(event,
+        SystemEvent::TradeSettled { bess_id: 123, energy_sold, .. } if *energy_sold == 10.0)));
+    assert!(received.iter().any(|event| matches!(event,
+        SystemEvent::BatteryHealthChanged { bess_id: 123, previous_status: 0, status: 1, .. })));
+
+    // A confirmed window is published at once
+    let window = DeliveryWindow::new(delivery_time_now(), Duration::from_secs(1800), vec![2.0, 4.0]);
+    let bid_id = next_message_id();
+    let accept = client.request_with_window(ETPMessage::new_bid(bid_id, 18.0, 3.0), &window).await.unwrap();
+    client.send(ETPMessage::new_bid_confirm(bid_id, 42, accept.sale_price, 3.0)).await.unwrap();
+    client.request_schedule(next_message_id(), 42).await.unwrap(); // Answered after the confirm
+    let published = gateway.schedule(123).await.unwrap();
+    assert_eq!(published.iter().map(|delivery| delivery.trade_id).collect::<Vec<_>>(), [bid_id]);
+
+    server_handle.abort();
+}
@@ -0,0 +1,135 @@
+use energy_trading::*;
+use std::net::SocketAddr;
+use std::path::Path;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+
+async fn start_bess_server(reserve_price: f64, capture_dir: Option<&Path>) -> (SocketAddr, JoinHandle<()>) {
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, reserve_price);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    if let Some(capture_dir) = capture_dir {
+        server.set_capture_dir(capture_dir);
+    }
+    let server_addr = server.local_addr().unwrap();
+    let handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    (server_addr, handle)
+}
+
+/// Query, bid and confirm as an aggregator would
+async fn run_trade(connection: &mut UnicastConnection) {
+    connection.handshake(&Hello::default()).await.unwrap();
+    connection.send_message(ETPMessage::new_query(9001, 42)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), connection.receive_message()).await.unwrap().unwrap();
+    assert_eq!(response.message_type, 2); // QueryResponse
+    connection.send_message(ETPMessage::new_bid(9002, 18.0, 10.0)).await.unwrap();
+    let accept = timeout(Duration::from_millis(500), connection.receive_message()).await.unwrap().unwrap();
+    assert_eq!(accept.message_type, 4); // BidAccept
+    connection.send_message(ETPMessage::new_bid_confirm(9002, 42, accept.sale_price, 10.0)).await.unwrap();
+}
+
+/// Record one trade at a BESS server and return the capture
+async fn record_trade() -> Capture {
+    let capture_dir = tempfile::tempdir().unwrap();
+    let (server_addr, server_handle) = start_bess_server(15.0, Some(capture_dir.path())).await;
+
+    let mut client = UnicastConnection::new(TcpStream::connect(server_addr).await.unwrap());
+    run_trade(&mut client).await;
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    server_handle.abort();
+
+    let path = std::fs::read_dir(capture_dir.path()).unwrap().next().unwrap().unwrap().path();
+    Capture::open(path).unwrap()
+}
+
+#[tokio::test]
+async fn test_server_records_every_frame() {
+    let capture = record_trade().await;
+    assert!(capture.header.peer_addr.is_some());
+
+    let directions: Vec<_> = capture.records.iter().map(|record| record.direction).collect();
+    use CaptureDirection::{Received, Sent};
+    assert_eq!(directions, [Received, Sent, Received, Sent, Received, Sent, Received]);
+    assert!(Hello::is_hello_frame(&capture.records[0].frame));
+    let bid = InspectedFrame::decode(&capture.records[4].frame).unwrap();
+    assert_eq!(bid.messages()[0].message_type, 3); // Bid
+    assert!(capture.records.windows(2).all(|pair| pair[0].at_us <= pair[1].at_us));
+}
+
+#[tokio::test]
+async fn test_replay_reproduces_capture_on_fresh_server() {
+    let capture = record_trade().await;
+
+    // A fresh server each time, so the battery starts as recorded
+    for timing in [ReplayTiming::Original, ReplayTiming::Fast] {
+        let (server_addr, server_handle) = start_bess_server(15.0, None).await;
+        let mut replayer = CaptureReplayer::new(capture.clone());
+        replayer.set_timing(timing);
+        let mut connection = UnicastConnection::new(TcpStream::connect(server_addr).await.unwrap());
+        let report = replayer.run(&mut connection).await;
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.sent, 4); // Hello, query, bid, confirm
+        assert_eq!(report.matched, 3);
+        server_handle.abort();
+    }
+}
+
+#[tokio::test]
+async fn test_replay_reports_divergence() {
+    let capture = record_trade().await;
+    // A price cap below the recorded bid turns the accept into a reject
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    server.set_validation_rules(ValidationRules { max_price: Some(17.0), ..ValidationRules::default() });
+    let server_addr = server.local_addr().unwrap();
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+
+    let mut replayer = CaptureReplayer::new(capture);
+    replayer.set_timing(ReplayTiming::Fast);
+    replayer.set_response_window(Duration::from_millis(300));
+    let mut connection = UnicastConnection::new(TcpStream::connect(server_addr).await.unwrap());
+    let report = replayer.run(&mut connection).await;
+
+    assert!(!report.passed());
+    assert_eq!(report.matched, 2); // Hello and query response
+    match report.first_divergence() {
+        Some(Divergence::Mismatch { record: 5, actual, .. }) => {
+            let reject = InspectedFrame::decode(actual).unwrap();
+            assert_eq!(reject.messages()[0].message_type, 6); // BidReject
+        }
+        other => panic!("unexpected divergence {:?}", other),
+    }
+    assert!(report.to_string().contains("got      wire: BidReject #9002"));
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_replay_plays_bess_for_aggregator() {
+    let capture = record_trade().await;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // The replayer stands in for the recorded BESS
+    let replay = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut replayer = CaptureReplayer::new(capture);
+        replayer.set_side(ReplaySide::Recorder);
+        replayer.set_timing(ReplayTiming::Fast);
+        replayer.run(&mut UnicastConnection::new(stream)).await
+    });
+
+    let mut aggregator = UnicastConnection::new(TcpStream::connect(addr).await.unwrap());
+    run_trade(&mut aggregator).await;
+    drop(aggregator);
+
+    let report = replay.await.unwrap();
+    assert!(report.passed(), "{}", report);
+    assert_eq!(report.sent, 3); // Hello, query response, accept
+    assert_eq!(report.matched, 4);
+}
@@ -0,0 +1,218 @@
+use bytes::BytesMut;
+use energy_trading::*;
+use proptest::prelude::*;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+
+fn any_code() -> impl Strategy<Value = TerminationCode> {
+    prop::sample::select(TerminationCode::ALL.to_vec())
+}
+
+/// Any message built by one of the constructors, with any TTL
+fn any_message() -> impl Strategy<Value = ETPMessage> {
+    let id = any::<u64>();
+    let device = any::<u64>();
+    let float = any::<f64>(); // Includes NaN, infinities and subnormals
+    let built = prop_oneof![
+        (id, device).prop_map(|(id, device)| ETPMessage::new_register(id, device)),
+        (id, device).prop_map(|(id, device)| ETPMessage::new_query(id, device)),
+        (id, device, float, float).prop_map(|(id, device, total, pct)| ETPMessage::new_query_response(id, device, total, pct)),
+        (id, float, float).prop_map(|(id, price, energy)| ETPMessage::new_bid(id, price, energy)),
+        (id, device, float, float).prop_map(|(id, device, price, energy)| ETPMessage::new_bid_accept(id, device, price, energy)),
+        (id, device, float, float).prop_map(|(id, device, price, energy)| ETPMessage::new_bid_confirm(id, device, price, energy)),
+        (id, device, any_code()).prop_map(|(id, device, code)| ETPMessage::new_bid_reject(id, device, code)),
+        (id, device, any_code()).prop_map(|(id, device, code)| ETPMessage::new_terminate(id, device, code)),
+        (id, device, any_code()).prop_map(|(id, device, code)| ETPMessage::new_device_failure(id, device, code)),
+        any_status(device),
+    ];
+    (built, any::<u8>()).prop_map(|(mut message, ttl)| {
+        message.ttl = ttl;
+        message
+    })
+}
+
+/// BESSStatus samples; few distinct devices make delta entries likely in batches
+fn any_status(device: impl Strategy<Value = u64>) -> impl Strategy<Value = ETPMessage> {
+    (any::<u64>(), device, any::<f64>(), any::<u8>(), any::<f64>(), any::<f64>()).prop_map(
+        |(id, device, energy, health, voltage, rate)| ETPMessage::new_bess_status(id, device, energy, health, voltage, rate),
+    )
+}
+
+/// Frames that get past the magic check of each decoder
+fn any_frame() -> impl Strategy<Value = Vec<u8>> {
+    let magics: Vec<&[u8]> = vec![b"ET", b"EB", b"EC", b"EZ", b"ES", b"EH", b""];
+    (prop::sample::select(magics), prop::collection::vec(any::<u8>(), 0..600), any::<bool>()).prop_map(
+        |(magic, rest, sealed)| {
+            let mut frame = [magic, &rest[..]].concat();
+            if sealed {
+                reseal(&mut frame);
+            }
+            frame
+        },
+    )
+}
+
+/// Replace the last four bytes with the CRC-32 of everything before them
+fn reseal(frame: &mut [u8]) {
+    if frame.len() >= 4 {
+        let split = frame.len() - 4;
+        let checksum = crc32fast::hash(&frame[..split]);
+        frame[split..].copy_from_slice(&checksum.to_le_bytes());
+    }
+}
+
+/// Run every decoder on untrusted bytes; they may fail but must not panic
+fn decode_everything(frame: &[u8]) {
+    let _ = ETPMessage::deserialize(frame);
+    let _ = ETPMessage::decode_compact(frame);
+    let _ = ETPMessage::decode_go_bytes(frame);
+    let _ = MessageBatch::decode(frame);
+    let _ = SignedEnvelope::decode(frame);
+    let _ = Hello::decode(frame);
+    let _ = InspectedFrame::decode(frame);
+    if let Ok(expanded) = decompress_frame(frame, MAX_FRAME_SIZE) {
+        decode_everything(&expanded);
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_wire_roundtrip(message in any_message()) {
+        let encoded = message.serialize().unwrap();
+        prop_assert_eq!(encoded.len(), WIRE_MESSAGE_SIZE);
+        let decoded = ETPMessage::deserialize(&encoded).unwrap();
+        prop_assert_eq!(decoded.serialize().unwrap(), encoded);
+    }
+
+    #[test]
+    fn prop_compact_roundtrip(message in any_message()) {
+        let mut buf = BytesMut::new();
+        message.encode_compact(ETP_PROTOCOL_VERSION, &mut buf).unwrap();
+        prop_assert!(buf.len() <= COMPACT_MAX_SIZE);
+        let decoded = ETPMessage::decode_compact(&buf).unwrap();
+        prop_assert_eq!(decoded.serialize().unwrap(), message.serialize().unwrap());
+    }
+
+    #[test]
+    fn prop_typed_roundtrip(message in any_message()) {
+        let flat = message.to_typed().unwrap().to_flat();
+        prop_assert_eq!(flat.serialize().unwrap(), message.serialize().unwrap());
+    }
+
+    #[test]
+    fn prop_json_roundtrip(message in any_message()) {
+        let decoded = ETPMessage::from_json(&message.to_json().unwrap()).unwrap();
+        prop_assert_eq!(decoded.serialize().unwrap(), message.serialize().unwrap());
+    }
+
+    #[test]
+    fn prop_batch_roundtrip(
+        messages in prop::collection::vec(prop_oneof![any_message(), any_status(0u64..3)], 0..24),
+        delta_status in any::<bool>(),
+    ) {
+        let mut batch = MessageBatch::new(messages.clone());
+        batch.set_delta_status(delta_status);
+        let decoded = MessageBatch::decode(&batch.encode().unwrap()).unwrap();
+        prop_assert_eq!(decoded.len(), messages.len());
+        for (decoded, original) in decoded.into_iter().zip(&messages) {
+            prop_assert_eq!(decoded.serialize().unwrap(), original.serialize().unwrap());
+        }
+    }
+
+    #[test]
+    fn prop_compression_roundtrip(frame in prop::collection::vec(any::<u8>(), 0..2048)) {
+        prop_assert_eq!(decompress_frame(&compress_frame(&frame).unwrap(), MAX_FRAME_SIZE).unwrap(), frame);
+    }
+
+    #[test]
+    fn prop_decoders_never_panic(frame in any_frame()) {
+        decode_everything(&frame);
+    }
+
+    #[test]
+    fn prop_mutated_batches_never_panic(
+        messages in prop::collection::vec(prop_oneof![any_message(), any_status(0u64..3)], 1..8),
+        position in any::<prop::sample::Index>(),
+        flip in 1u8..,
+    ) {
+        // Valid CRC, so the mutation reaches the entry parser
+        let mut batch = MessageBatch::new(messages);
+        batch.set_delta_status(true);
+        let mut frame = batch.encode().unwrap();
+        let index = position.index(frame.len() - 4);
+        frame[index] ^= flip;
+        reseal(&mut frame);
+        let _ = MessageBatch::decode(&frame);
+    }
+
+    #[test]
+    fn prop_split_frame_reassembles_chunks(
+        frames in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..200), 0..8),
+        chunk in 1usize..64,
+    ) {
+        let mut stream = Vec::new();
+        for frame in &frames {
+            stream.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            stream.extend_from_slice(frame);
+        }
+
+        let mut buffer = BytesMut::new();
+        let mut received = Vec::new();
+        for piece in stream.chunks(chunk) {
+            buffer.extend_from_slice(piece);
+            while let Some(frame) = split_frame(&mut buffer, MAX_FRAME_SIZE).unwrap() {
+                received.push(frame);
+            }
+        }
+        prop_assert_eq!(received, frames);
+        prop_assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn prop_split_frame_bounds_buffering(bytes in prop::collection::vec(any::<u8>(), 0..1024), max in 0usize..256) {
+        let mut buffer = BytesMut::from(&bytes[..]);
+        loop {
+            match split_frame(&mut buffer, max) {
+                Ok(Some(frame)) => prop_assert!(frame.len() <= max),
+                Ok(None) => {
+                    // Waiting only for a frame that fits
+                    prop_assert!(buffer.len() < 4 + max);
+                    break;
+                }
+                Err(ETPError::Serialization(SerializationError::FrameTooLarge { size, .. })) => {
+                    prop_assert!(size > max);
+                    break;
+                }
+                Err(e) => prop_assert!(false, "unexpected error {}", e),
+            }
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn prop_framed_reader_survives_garbage(bytes in prop::collection::vec(any::<u8>(), 0..2048)) {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let writer = tokio::spawn(async move {
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                let _ = stream.write_all(&bytes).await;
+            });
+
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut connection = UnicastConnection::new(stream);
+            // Read until the garbage produces an error; the sender closing ends it at the latest
+            let outcome = tokio::time::timeout(Duration::from_secs(5), async {
+                while connection.receive_message().await.is_ok() {}
+            })
+            .await;
+            assert!(outcome.is_ok(), "reader hung on garbage input");
+            writer.await.unwrap();
+        });
+    }
+}
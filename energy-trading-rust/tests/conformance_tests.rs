@@ -0,0 +1,98 @@
+use energy_trading::*;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+async fn start_bess_server() -> (SocketAddr, JoinHandle<()>) {
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    (server_addr, handle)
+}
+
+async fn start_relay(relay_id: u64, next_hop: SocketAddr) -> (SocketAddr, JoinHandle<()>) {
+    let mut relay = EtpRelay::new(relay_id, "127.0.0.1:0".parse().unwrap(), next_hop).await.unwrap();
+    let relay_addr = relay.local_addr().unwrap();
+    let handle = tokio::spawn(async move {
+        relay.start().await.unwrap();
+    });
+    (relay_addr, handle)
+}
+
+/// Endpoint that answers every frame with a QueryResponse carrying message_id 0
+async fn start_sloppy_endpoint() -> (SocketAddr, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut connection = UnicastConnection::new(stream);
+                while connection.receive_frame().await.is_ok() {
+                    let response = ETPMessage::new_query_response(0, 77, 50.0, 20.0);
+                    if connection.send_message(response).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    (addr, handle)
+}
+
+#[tokio::test]
+async fn test_reference_bess_server_conforms() {
+    let (server_addr, server_handle) = start_bess_server().await;
+
+    let report = ConformanceSuite::new(server_addr).run().await;
+    assert!(report.passed(), "{}", report);
+    assert_eq!(report.outcome("query.answered"), Some(&RuleOutcome::Passed));
+    assert_eq!(report.outcome("confirm.silent"), Some(&RuleOutcome::Passed));
+    assert_eq!(report.outcome("reject.economic_code"), Some(&RuleOutcome::Passed));
+    assert_eq!(report.outcome("malformed.endpoint_survives"), Some(&RuleOutcome::Passed));
+    // TTL expiry needs a relay in the path
+    assert!(matches!(report.outcome("ttl.expired_notice"), Some(RuleOutcome::Skipped(_))));
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_relayed_bess_server_conforms() {
+    let (server_addr, server_handle) = start_bess_server().await;
+    let (site_addr, site_handle) = start_relay(901, server_addr).await;
+    let (edge_addr, edge_handle) = start_relay(900, site_addr).await;
+
+    let mut suite = ConformanceSuite::new(edge_addr);
+    suite.set_relay_hops(2);
+    let report = suite.run().await;
+    assert!(report.passed(), "{}", report);
+    assert_eq!(report.outcome("ttl.expired_notice"), Some(&RuleOutcome::Passed));
+    assert_eq!(report.outcome("ttl.expired_code"), Some(&RuleOutcome::Passed));
+
+    server_handle.abort();
+    site_handle.abort();
+    edge_handle.abort();
+}
+
+#[tokio::test]
+async fn test_nonconforming_endpoint_reported() {
+    let (addr, handle) = start_sloppy_endpoint().await;
+
+    // The endpoint never sends a hello, like the Go prototype
+    let mut suite = ConformanceSuite::new(addr);
+    suite.set_hello(None);
+    let report = suite.run().await;
+
+    assert!(!report.passed());
+    assert!(matches!(report.outcome("hello.negotiated"), Some(RuleOutcome::Skipped(_))));
+    assert_eq!(report.outcome("query.answered"), Some(&RuleOutcome::Passed));
+    assert!(matches!(report.outcome("query.message_id"), Some(RuleOutcome::Failed(_))));
+    assert!(matches!(report.outcome("bid.accepted"), Some(RuleOutcome::Failed(_))));
+    assert!(matches!(report.outcome("bid.terms"), Some(RuleOutcome::Skipped(_))));
+    assert!(matches!(report.outcome("malformed.bad_checksum"), Some(RuleOutcome::Failed(_))));
+    assert!(report.to_string().contains("FAIL  bid-reject"));
+
+    handle.abort();
+}
@@ -0,0 +1,85 @@
+use energy_trading::*;
+use std::net::SocketAddr;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+async fn start_bess_server(device_id: u64, capacity: f64, reserve_price: f64) -> (SocketAddr, JoinHandle<()>) {
+    let bess = BESSNode::new(device_id, format!("BESS-{}", device_id), capacity, reserve_price);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    (server_addr, handle)
+}
+
+#[tokio::test]
+async fn test_aggregator_trades_over_pooled_connections() {
+    let (addr1, server1) = start_bess_server(100, 100.0, 15.0).await; // Offers 40 kWh
+    let (addr2, server2) = start_bess_server(101, 80.0, 14.0).await; // Offers 32 kWh, cheaper
+
+    let aggregator = AggregatorNode::new(123, "AGG-001".to_string(), BiddingStrategy::Aggressive);
+    aggregator.connect_bess(100, addr1);
+    aggregator.connect_bess(101, addr2);
+    assert!(aggregator.connection_pool.wait_connected(100, Duration::from_secs(1)).await);
+    assert!(aggregator.connection_pool.wait_connected(101, Duration::from_secs(1)).await);
+
+    let responses = aggregator.query_bess_nodes(35.0).await;
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].device_id, 100);
+    assert_eq!(aggregator.query_bess_nodes(20.0).await.len(), 2);
+
+    // 32 kWh from the cheaper offer, the remaining 18 kWh from the larger one
+    let bids = aggregator.place_bids(50.0, 30.0).await;
+    assert_eq!(bids.len(), 2);
+    let history = aggregator.get_bid_history().await;
+    assert!(history.iter().all(|bid| bid.was_accepted));
+    let bought: Vec<_> = history.iter().map(|bid| (bid.bess_device_id, bid.energy_amount)).collect();
+    assert_eq!(bought, [(101, 32.0), (100, 18.0)]);
+
+    // Conservative bids start at the asking price, well above min_bid_price
+    let cheap = AggregatorNode::new(124, "AGG-002".to_string(), BiddingStrategy::Conservative);
+    assert!(cheap.min_bid_price < 14.0);
+    cheap.connect_bess(100, addr1);
+    assert!(cheap.connection_pool.wait_connected(100, Duration::from_secs(1)).await);
+    assert!(cheap.place_bids(10.0, 12.0).await.is_empty()); // Asks more than the limit
+    assert_eq!(cheap.place_bids(10.0, 30.0).await.len(), 1);
+    assert_eq!(cheap.get_success_rate().await, 1.0);
+    let history = cheap.get_bid_history().await;
+    assert_eq!((history[0].bess_device_id, history[0].energy_amount), (100, 10.0));
+    assert!(history[0].bid_price < 30.0);
+
+    server1.abort();
+    server2.abort();
+}
+
+#[tokio::test]
+async fn test_pool_heartbeats_and_replaces_discarded_connections() {
+    let (addr, server) = start_bess_server(100, 100.0, 15.0).await;
+    let settings = PoolSettings {
+        heartbeat_interval: Duration::from_millis(50),
+        initial_backoff: Duration::from_millis(20),
+        max_backoff: Duration::from_millis(100),
+        ..PoolSettings::default()
+    };
+    let pool = BessConnectionPool::with_settings(123, settings);
+    pool.add(100, addr);
+    assert!(pool.wait_connected(100, Duration::from_secs(1)).await);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let response = pool.status(100).unwrap().last_query_response.unwrap();
+    assert_eq!((response.message_type, response.device_id), (2, 100)); // QueryResponse
+
+    // A connection the caller gave up on is replaced
+    pool.checkout(100).await.unwrap().discard();
+    assert!(!pool.is_connected(100));
+    assert!(pool.wait_connected(100, Duration::from_secs(1)).await);
+    assert_eq!(pool.status(100).unwrap().reconnects, 1);
+
+    let connection = pool.checkout(100).await.unwrap();
+    let response = connection.request(ETPMessage::new_query(next_message_id(), 123)).await.unwrap();
+    assert_eq!(response.message_type, 2);
+    drop(connection);
+
+    server.abort();
+}
@@ -0,0 +1,97 @@
+use energy_trading::*;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::Duration;
+
+#[tokio::test]
+async fn test_client_trades_with_bess_server() {
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+
+    let (client, mut subscription) = EtpClient::connect(server_addr).await.unwrap();
+    let query_id = next_message_id();
+    let bid_id = next_message_id();
+    let (response, accept) = tokio::join!(
+        client.request(ETPMessage::new_query(query_id, 42)),
+        client.request(ETPMessage::new_bid(bid_id, 18.0, 10.0)),
+    );
+    let response = response.unwrap();
+    assert_eq!((response.message_type, response.message_id), (2, query_id)); // QueryResponse
+    let accept = accept.unwrap();
+    assert_eq!((accept.message_type, accept.message_id), (4, bid_id)); // BidAccept
+
+    client.send(ETPMessage::new_bid_confirm(bid_id, 42, accept.sale_price, 10.0)).await.unwrap();
+    assert!(subscription.try_recv().is_none());
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_client_deadline_returns_timing_violation() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // A peer that answers only after the client gave up
+    let peer = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut connection = UnicastConnection::new(stream);
+        let query = connection.receive_message().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        connection.send_message(ETPMessage::new_query_response(query.message_id, 123, 50.0, 20.0)).await.unwrap();
+        connection
+    });
+
+    let connection = UnicastConnection::new(TcpStream::connect(addr).await.unwrap());
+    let (mut client, mut subscription) = EtpClient::new(connection);
+    assert_eq!(client.deadline(MessageType::Bid), Duration::from_millis(500)); // A BidAccept's max delay
+    client.set_deadline(MessageType::Query, Duration::from_millis(100));
+
+    match client.request(ETPMessage::new_query(7, 42)).await {
+        Err(ETPError::TimingViolation { message_type: 1, elapsed_ms, max_ms: 100 }) => assert!(elapsed_ms >= 100),
+        other => panic!("expected a timing violation, got {:?}", other),
+    }
+    // The late answer is delivered as unsolicited
+    let late = subscription.recv().await.unwrap();
+    assert_eq!((late.message_type, late.message_id), (2, 7));
+
+    assert!(matches!(
+        client.request(ETPMessage::new_bid_confirm(8, 42, 18.0, 10.0)).await,
+        Err(ETPError::Validation(_))
+    ));
+    drop(peer.await.unwrap());
+}
+
+#[tokio::test]
+async fn test_client_schedules_windowed_bid() {
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+
+    let (client, _subscription) = EtpClient::connect(server_addr).await.unwrap();
+    let window = DeliveryWindow::new(delivery_time_now(), Duration::from_secs(1800), vec![2.0, 4.0]); // 3 kWh
+
+    // The bid must ask for the window's energy
+    let mismatched = client.request_with_window(ETPMessage::new_bid(next_message_id(), 18.0, 10.0), &window).await.unwrap();
+    assert_eq!(mismatched.message_type, 6); // BidReject
+
+    let bid_id = next_message_id();
+    let mut bid = ETPMessage::new_bid(bid_id, 18.0, 3.0);
+    bid.device_id = 42;
+    let accept = client.request_with_window(bid, &window).await.unwrap();
+    assert_eq!((accept.message_type, accept.message_id), (4, bid_id)); // BidAccept
+    client.send(ETPMessage::new_bid_confirm(bid_id, 42, accept.sale_price, 3.0)).await.unwrap();
+
+    // The schedule request is answered after the confirm sent before it
+    let deliveries = client.request_schedule(next_message_id(), 42).await.unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!((deliveries[0].trade_id, deliveries[0].aggregator_id), (bid_id, 42));
+    assert_eq!(deliveries[0].window, window);
+
+    server_handle.abort();
+}
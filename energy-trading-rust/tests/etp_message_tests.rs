@@ -1,3 +1,4 @@
+use bytes::BytesMut;
 use energy_trading::*;
 use std::time::{Duration, Instant};
 
@@ -141,6 +142,54 @@ fn test_message_size_consistency() {
     assert!(serialized.len() >= 50); // Should be at least 50 bytes for all fields
 }
 
+#[test]
+fn test_compact_message_size() {
+    // Compact encoding omits zeroed fields, so every type is well under the fixed layout
+    let messages = [
+        ETPMessage::new_register(next_message_id(), 100),
+        ETPMessage::new_query(next_message_id(), 100),
+        ETPMessage::new_bid(next_message_id(), 15.5, 10.0),
+        ETPMessage::new_bid_accept(next_message_id(), 100, 15.5, 10.0),
+        ETPMessage::new_device_failure(next_message_id(), 100, TerminationCode::BatteryFault),
+        ETPMessage::new_bess_status(next_message_id(), 100, 18.5, 1, 12.6, 2.5),
+    ];
+    for msg in &messages {
+        let mut compact = BytesMut::new();
+        msg.encode_compact(ETP_PROTOCOL_VERSION, &mut compact).unwrap();
+        assert!(compact.len() <= 48, "type {} took {} bytes", msg.message_type, compact.len());
+        assert!(compact.len() * 2 < msg.serialize().unwrap().len());
+    }
+}
+
+#[test]
+fn test_compact_roundtrip_performance() {
+    let msg = ETPMessage::new_bess_status(next_message_id(), 100, 18.5, 1, 12.6, 2.5);
+    
+    let start = Instant::now();
+    for _ in 0..1000 {
+        let mut compact = BytesMut::new();
+        msg.encode_compact(ETP_PROTOCOL_VERSION, &mut compact).unwrap();
+        assert_eq!(ETPMessage::decode_compact(&compact).unwrap(), msg);
+    }
+    let elapsed = start.elapsed();
+    
+    // Should round-trip 1000 messages in less than 20ms (reasonable for debug build)
+    assert!(elapsed < Duration::from_millis(20));
+}
+
+#[test]
+fn test_compressed_batch_size() {
+    let batch = MessageBatch::new(
+        (0..100).map(|i| ETPMessage::new_bess_status(next_message_id(), 100, 18.5 - i as f64 * 0.1, 1, 12.6, 2.5)).collect(),
+    );
+    let frame = batch.encode().unwrap();
+    let compressed = compress_frame(&frame).unwrap();
+    
+    // 100 separate frames would take 9500 bytes
+    assert!(compressed.len() < 2000);
+    assert_eq!(decompress_frame(&compressed, MAX_FRAME_SIZE).unwrap(), frame);
+}
+
 #[test]
 fn test_error_handling() {
     // Test deserialization with invalid data
@@ -154,6 +203,27 @@ fn test_error_handling() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_field_validation() {
+    // NaN and negative amounts are rejected with every violation listed
+    let bid = ETPMessage::new_bid(123, f64::NAN, -5.0);
+    match bid.validate() {
+        Err(ETPError::InvalidFields(violations)) => {
+            let fields: Vec<_> = violations.iter().map(|v| (v.field, v.rule)).collect();
+            assert_eq!(fields, vec![
+                ("bid_price", ValidationRule::Finite),
+                ("required_energy_amount", ValidationRule::NonNegative),
+            ]);
+        }
+        other => panic!("Expected InvalidFields, got {:?}", other),
+    }
+    
+    // Deployment-specific price cap
+    let rules = ValidationRules { max_price: Some(20.0), ..ValidationRules::default() };
+    assert!(ETPMessage::new_bid(123, 15.5, 10.0).validate_with(&rules).is_ok());
+    assert!(ETPMessage::new_bid(123, 25.0, 10.0).validate_with(&rules).is_err());
+}
+
 #[test]
 fn test_message_priority() {
     let device_failure = ETPMessage::new_with_type(8, 123, 15.5, 10.0);
@@ -215,19 +285,20 @@ fn test_message_creation_helpers() {
     assert_eq!(confirm.device_id, 200);
     
     // Test BidReject message
-    let reject = ETPMessage::new_bid_reject(123, 100, 1); // Code 1 = price too low
+    let reject = ETPMessage::new_bid_reject(123, 100, TerminationCode::PriceBelowReserve);
     assert_eq!(reject.message_type, 6);
     assert_eq!(reject.termination_code, 1);
+    assert_eq!(reject.termination().unwrap(), TerminationCode::PriceBelowReserve);
     
     // Test Terminate message
-    let terminate = ETPMessage::new_terminate(123, 100, 0); // Code 0 = normal termination
+    let terminate = ETPMessage::new_terminate(123, 100, TerminationCode::Normal);
     assert_eq!(terminate.message_type, 7);
     assert_eq!(terminate.termination_code, 0);
     
     // Test DeviceFailure message
-    let failure = ETPMessage::new_device_failure(123, 100, 2); // Code 2 = battery failure
+    let failure = ETPMessage::new_device_failure(123, 100, TerminationCode::BatteryFault);
     assert_eq!(failure.message_type, 8);
-    assert_eq!(failure.termination_code, 2);
+    assert_eq!(failure.termination_code, 6);
     
     // Test BESSStatus message
     let status = ETPMessage::new_bess_status(123, 100, 18.5, 1, 12.6, 2.5);
@@ -237,3 +308,72 @@ fn test_message_creation_helpers() {
     assert_eq!(status.battery_voltage, 12.6);
     assert_eq!(status.discharge_rate, 2.5);
 }
+
+#[test]
+fn test_message_type_enum_roundtrip() {
+    for value in 0..=9u8 {
+        let message_type = MessageType::try_from(value).unwrap();
+        assert_eq!(message_type.as_u8(), value);
+    }
+    assert!(MessageType::try_from(10).is_err());
+}
+
+#[test]
+fn test_typed_payload_conversions() {
+    // Every constructor should survive flat -> typed -> flat unchanged
+    let messages = [
+        ETPMessage::new_register(1, 100),
+        ETPMessage::new_query(2, 200),
+        ETPMessage::new_query_response(3, 100, 20.0, 50.0),
+        ETPMessage::new_bid(4, 15.5, 10.0),
+        ETPMessage::new_bid_accept(5, 100, 18.0, 5.0),
+        ETPMessage::new_bid_confirm(6, 200, 18.0, 5.0),
+        ETPMessage::new_bid_reject(7, 100, TerminationCode::PriceBelowReserve),
+        ETPMessage::new_terminate(8, 100, TerminationCode::Normal),
+        ETPMessage::new_device_failure(9, 100, TerminationCode::BatteryFault),
+        ETPMessage::new_bess_status(10, 100, 18.5, 1, 12.6, 2.5),
+    ];
+
+    for msg in messages {
+        let typed = TypedETPMessage::try_from(&msg).unwrap();
+        assert_eq!(typed.message_type().as_u8(), msg.message_type);
+        assert_eq!(ETPMessage::from(typed), msg);
+    }
+}
+
+#[test]
+fn test_typed_bid_payload_fields() {
+    let bid = ETPMessage::new_bid(123, 15.5, 10.0);
+    match bid.payload().unwrap() {
+        EtpPayload::Bid(payload) => {
+            assert_eq!(payload.bid_price, 15.5);
+            assert_eq!(payload.required_energy_amount, 10.0);
+        }
+        other => panic!("Expected Bid payload, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_typed_conversion_drops_fields_outside_the_payload() {
+    let mut reject = ETPMessage::new_bid_reject(7, 100, TerminationCode::PriceBelowReserve);
+    reject.bid_price = 15.5;
+    reject.battery_voltage = 12.6;
+
+    let flat = ETPMessage::from(reject.to_typed().unwrap());
+    assert_eq!(flat.termination_code, reject.termination_code);
+    assert_eq!(flat.bid_price, 0.0);
+    assert_eq!(flat.battery_voltage, 0.0);
+}
+
+#[test]
+fn test_wire_format_is_fixed_size() {
+    // Every message type encodes to the same fixed-layout frame
+    for message_type in MessageType::ALL {
+        let msg = ETPMessage::new_with_type(message_type.as_u8(), 123, 15.5, 10.0);
+        let serialized = msg.serialize().unwrap();
+        assert_eq!(serialized.len(), WIRE_MESSAGE_SIZE);
+        assert_eq!(&serialized[..2], &ETP_MAGIC);
+        assert_eq!(serialized[2], ETP_PROTOCOL_VERSION);
+        assert_eq!(serialized[3], message_type.as_u8());
+    }
+}
@@ -0,0 +1,142 @@
+//! Inputs found by fuzzing or review that once crashed or exhausted a decoder.
+//! Add every new `fuzz/artifacts` crash here before fixing it.
+
+use energy_trading::*;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+/// Frames that must fail cleanly in every decoder
+const DECODER_CASES: &[&[u8]] = &[
+    b"",
+    b"E",
+    b"ET",
+    b"EB",
+    b"EC",
+    b"EZ",
+    b"ES",
+    b"EH",
+    b"EH\x00",
+    b"EH\xff\x01",
+    b"EB\x01\x01\xff\xff",               // Claims 65535 entries, holds none
+    b"EC\x01\x03\xff\xff\xff\xff\xff",   // Unterminated varint
+    b"EZ\x00\x00\x00\xff\xff",           // Stored DEFLATE block without data
+    b"3 1 2 3 NaN inf -inf 1e999 0 0 0 0 0 0",
+];
+
+#[test]
+fn test_decoders_reject_short_and_truncated_frames() {
+    for frame in DECODER_CASES {
+        let _ = ETPMessage::deserialize(frame);
+        let _ = ETPMessage::decode_compact(frame);
+        let _ = ETPMessage::decode_go_bytes(frame);
+        let _ = MessageBatch::decode(frame);
+        let _ = SignedEnvelope::decode(frame);
+        let _ = Hello::decode(frame);
+        let _ = decompress_frame(frame, MAX_FRAME_SIZE);
+    }
+}
+
+async fn connected_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+    (client, server)
+}
+
+#[tokio::test]
+async fn test_4gb_length_prefix_rejected_without_allocating() {
+    let (mut client, server) = connected_pair().await;
+    let mut connection = UnicastConnection::new(server);
+
+    // The length alone must be enough to refuse the frame
+    client.write_all(&u32::MAX.to_le_bytes()).await.unwrap();
+    let result = timeout(Duration::from_millis(500), connection.receive_message()).await.unwrap();
+    assert!(matches!(
+        result,
+        Err(ETPError::Serialization(SerializationError::FrameTooLarge { size, max }))
+            if size == u32::MAX as usize && max == MAX_FRAME_SIZE
+    ));
+}
+
+#[tokio::test]
+async fn test_configured_frame_limit_applies_both_ways() {
+    let (client, server) = connected_pair().await;
+    let mut sender = UnicastConnection::new(client);
+    let mut receiver = UnicastConnection::new(server);
+    sender.set_max_frame_size(64);
+    receiver.set_max_frame_size(64);
+
+    assert!(matches!(
+        sender.send_frame(&[0u8; 65]).await,
+        Err(ETPError::Serialization(SerializationError::FrameTooLarge { size: 65, max: 64 }))
+    ));
+
+    // A 91-byte wire message is too large for the receiver
+    let mut unlimited = sender;
+    unlimited.set_max_frame_size(MAX_FRAME_SIZE);
+    unlimited.send_message(ETPMessage::new_query(1, 2)).await.unwrap();
+    assert!(matches!(
+        receiver.receive_message().await,
+        Err(ETPError::Serialization(SerializationError::FrameTooLarge { size: WIRE_MESSAGE_SIZE, max: 64 }))
+    ));
+}
+
+#[tokio::test]
+async fn test_configured_frame_limit_applies_after_decompression() {
+    let (client, server) = connected_pair().await;
+    let mut sender = UnicastConnection::new(client);
+    let mut receiver = UnicastConnection::new(server);
+    receiver.set_max_frame_size(64);
+
+    // The compressed frame fits the limit, the wire message inside does not
+    let compressed = compress_frame(&ETPMessage::new_query(1, 2).serialize().unwrap()).unwrap();
+    assert!(compressed.len() <= 64);
+    sender.send_frame(&compressed).await.unwrap();
+    assert!(matches!(
+        receiver.receive_message().await,
+        Err(ETPError::Serialization(SerializationError::Decompression(_)))
+    ));
+}
+
+#[tokio::test]
+async fn test_relay_closes_connection_on_oversized_frame() {
+    let mut relay = EtpRelay::new(900, "127.0.0.1:0".parse().unwrap(), "127.0.0.1:9".parse().unwrap()).await.unwrap();
+    let relay_addr = relay.local_addr().unwrap();
+    let relay_handle = tokio::spawn(async move {
+        relay.start().await.unwrap();
+    });
+
+    let mut client = TcpStream::connect(relay_addr).await.unwrap();
+    client.write_all(&u32::MAX.to_le_bytes()).await.unwrap();
+    let mut byte = [0u8; 1];
+    let read = timeout(Duration::from_millis(500), client.read(&mut byte)).await.unwrap();
+    assert!(matches!(read, Ok(0) | Err(_)));
+
+    relay_handle.abort();
+}
+
+#[tokio::test]
+async fn test_server_keeps_serving_after_oversized_frame() {
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut attacker = TcpStream::connect(server_addr).await.unwrap();
+    attacker.write_all(&u32::MAX.to_le_bytes()).await.unwrap();
+    let mut byte = [0u8; 1];
+    let read = timeout(Duration::from_millis(500), attacker.read(&mut byte)).await.unwrap();
+    assert!(matches!(read, Ok(0) | Err(_)));
+
+    let mut client = UnicastConnection::new(TcpStream::connect(server_addr).await.unwrap());
+    client.send_message(ETPMessage::new_query(next_message_id(), 456)).await.unwrap();
+    let response = timeout(Duration::from_millis(500), client.receive_message()).await.unwrap().unwrap();
+    assert_eq!(response.message_type, 2); // QueryResponse
+
+    server_handle.abort();
+}
@@ -128,6 +128,7 @@ async fn test_websocket_event_broadcasting() {
     };
     
     let bid_event = SystemEvent::BidPlaced {
+        auction_id: 1,
         aggregator_id: 100,
         bess_id: 123,
         bid_price: 18.0,
@@ -141,7 +142,6 @@ async fn test_websocket_event_broadcasting() {
     // Verify events were queued for broadcasting
     // Note: In a real implementation, this would check the event queue
     // For now, we'll just verify the broadcast didn't error
-    assert!(true);
 }
 
 #[tokio::test]
@@ -237,7 +237,7 @@ async fn test_concurrent_network_operations() {
 
 #[tokio::test]
 async fn test_network_error_handling() {
-    let discovery = MulticastDiscovery::new(
+    let _discovery = MulticastDiscovery::new(
         Ipv4Addr::new(224, 0, 0, 1),
         8888,
     ).await.unwrap();
@@ -274,6 +274,7 @@ async fn test_network_metrics_collection() {
     // Simulate some network activity
     for i in 0..100 {
         let event = SystemEvent::BidPlaced {
+            auction_id: 1,
             aggregator_id: 100,
             bess_id: 123,
             bid_price: 15.0 + (i as f64 * 0.1),
@@ -299,6 +300,7 @@ async fn test_network_performance_under_load() {
     
     for i in 0..event_count {
         let event = SystemEvent::BidPlaced {
+            auction_id: 1,
             aggregator_id: 100,
             bess_id: 123,
             bid_price: 15.0 + (i as f64 * 0.01),
@@ -316,3 +318,133 @@ async fn test_network_performance_under_load() {
     let events_per_second = event_count as f64 / elapsed.as_secs_f64();
     assert!(events_per_second > 1000.0);
 }
+
+/// Start a relay forwarding to `next_hop`, returning its address
+async fn start_relay(relay_id: u64, next_hop: std::net::SocketAddr) -> (std::net::SocketAddr, tokio::task::JoinHandle<()>) {
+    let mut relay = EtpRelay::new(relay_id, "127.0.0.1:0".parse().unwrap(), next_hop).await.unwrap();
+    let relay_addr = relay.local_addr().unwrap();
+    let handle = tokio::spawn(async move {
+        relay.start().await.unwrap();
+    });
+    (relay_addr, handle)
+}
+
+#[tokio::test]
+async fn test_multi_hop_relay_reaches_bess() {
+    // Aggregator -> relay -> site controller relay -> BESS
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    let (site_addr, site_handle) = start_relay(901, server_addr).await;
+    let (edge_addr, edge_handle) = start_relay(900, site_addr).await;
+    
+    let client_stream = TcpStream::connect(edge_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    let protocol = client_connection.handshake(&Hello::default()).await.unwrap();
+    assert_eq!(protocol.version, ETP_PROTOCOL_VERSION);
+    
+    client_connection.send_message(ETPMessage::new_query(next_message_id(), 456)).await.unwrap();
+    let response = tokio::time::timeout(Duration::from_millis(500), client_connection.receive_message())
+        .await.unwrap().unwrap();
+    assert_eq!(response.message_type, 2); // QueryResponse
+    assert_eq!(response.device_id, 123);
+    assert_eq!(response.ttl, 3); // Two hops back from the BESS
+    
+    server_handle.abort();
+    site_handle.abort();
+    edge_handle.abort();
+}
+
+#[tokio::test]
+async fn test_relay_forwards_windowed_bids() {
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    let (relay_addr, relay_handle) = start_relay(900, server_addr).await;
+    
+    // The window and schedule frames cross the relay alongside the bid
+    let (client, _subscription) = EtpClient::connect(relay_addr).await.unwrap();
+    let window = DeliveryWindow::new(delivery_time_now(), Duration::from_secs(1800), vec![2.0, 4.0]); // 3 kWh
+    let bid_id = next_message_id();
+    let mut bid = ETPMessage::new_bid(bid_id, 18.0, 3.0);
+    bid.device_id = 42;
+    let accept = client.request_with_window(bid, &window).await.unwrap();
+    assert_eq!((accept.message_type, accept.message_id), (4, bid_id)); // BidAccept
+    client.send(ETPMessage::new_bid_confirm(bid_id, 42, accept.sale_price, 3.0)).await.unwrap();
+    
+    let deliveries = client.request_schedule(next_message_id(), 42).await.unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].window, window);
+    
+    server_handle.abort();
+    relay_handle.abort();
+}
+
+#[tokio::test]
+async fn test_relay_expires_ttl() {
+    let bess = BESSNode::new(123, "BESS-001".to_string(), 100.0, 15.0);
+    let mut server = BESSTCPServer::new(bess, "127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let server_handle = tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    let (site_addr, site_handle) = start_relay(901, server_addr).await;
+    let (edge_addr, edge_handle) = start_relay(900, site_addr).await;
+    
+    // TTL 2 survives the first relay but not the second
+    let client_stream = TcpStream::connect(edge_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    let mut bid = ETPMessage::new_bid(next_message_id(), 18.0, 10.0);
+    bid.ttl = 2;
+    client_connection.send_message(bid.clone()).await.unwrap();
+    
+    let notice = tokio::time::timeout(Duration::from_millis(500), client_connection.receive_message())
+        .await.unwrap().unwrap();
+    assert_eq!(notice.message_type, 7); // Terminate
+    assert_eq!(notice.message_id, bid.message_id);
+    assert_eq!(notice.device_id, 901);
+    assert_eq!(notice.termination().unwrap(), TerminationCode::TtlExpired);
+    
+    server_handle.abort();
+    site_handle.abort();
+    edge_handle.abort();
+}
+
+#[tokio::test]
+async fn test_relay_breaks_routing_loops() {
+    // Two relays misconfigured to forward to each other
+    let mut first = EtpRelay::new(900, "127.0.0.1:0".parse().unwrap(), "127.0.0.1:9".parse().unwrap()).await.unwrap();
+    let first_addr = first.local_addr().unwrap();
+    let (second_addr, second_handle) = start_relay(901, first_addr).await;
+    first.set_next_hop(second_addr);
+    let first_handle = tokio::spawn(async move {
+        first.start().await.unwrap();
+    });
+    
+    let client_stream = TcpStream::connect(first_addr).await.unwrap();
+    let mut client_connection = UnicastConnection::new(client_stream);
+    client_connection.send_message(ETPMessage::new_query(next_message_id(), 456)).await.unwrap();
+    
+    // The query is dropped when it comes round again instead of circling until its TTL runs out
+    let response = tokio::time::timeout(Duration::from_millis(300), client_connection.receive_message()).await;
+    assert!(response.is_err());
+    
+    // A frame the relays cannot route is dropped, and the next valid frame still gets through
+    client_connection.send_frame(&[0xff; 16]).await.unwrap();
+    let mut bid = ETPMessage::new_bid(next_message_id(), 18.0, 10.0);
+    bid.ttl = 2;
+    client_connection.send_message(bid.clone()).await.unwrap();
+    let notice = tokio::time::timeout(Duration::from_millis(500), client_connection.receive_message())
+        .await.unwrap().unwrap();
+    assert_eq!((notice.message_type, notice.message_id), (7, bid.message_id)); // Terminate
+    assert_eq!(notice.device_id, 901); // Crossed the first relay
+    
+    first_handle.abort();
+    second_handle.abort();
+}
@@ -102,7 +102,7 @@ export const LiveEventsPanel: React.FC<LiveEventsPanelProps> = ({
           bgColor: "bg-red-50 dark:bg-red-900/20",
           title: "Bid Rejected",
           description: `Aggregator ${data.aggregator_id} → BESS ${data.bess_id}`,
-          details: `Rejected: ${data.reason}`,
+          details: `Rejected: ${data.reason} (${data.code})`,
         };
       }
       case "QuerySent": {
@@ -102,10 +102,31 @@ export interface BidAcceptedEvent {
   energy_amount: number; // Energy in kWh
 }
 
+export type TerminationCode =
+  | "Normal"
+  | "PriceBelowReserve"
+  | "InsufficientEnergy"
+  | "Offline"
+  | "TtlExpired"
+  | "NotCompetitive"
+  | "BatteryFault"
+  | "InvalidMessage";
+
+export type TerminationCategory = "Economic" | "Physical" | "Protocol";
+
+// Entry served by the gateway at GET /termination-codes
+export interface TerminationCodeInfo {
+  value: number;
+  name: TerminationCode;
+  description: string;
+  category: TerminationCategory;
+}
+
 export interface BidRejectedEvent {
   aggregator_id: number;
   bess_id: number;
   reason: string;
+  code: TerminationCode;
 }
 
 export interface AuctionCompletedEvent {